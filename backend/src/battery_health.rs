@@ -0,0 +1,436 @@
+// 电池健康状态与衰减跟踪
+// Battery state-of-health analytics: equivalent full cycles from throughput, rainflow cycle counting
+// on SOC history, capacity fade from full-charge events, SOH trend and remaining-useful-life estimate
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use crate::types::{BatteryCellStatus, BatteryHealthStatus, SohTrendPoint};
+
+/// Depth-of-discharge histogram bins (10% wide)
+const DOD_BINS: usize = 10;
+/// Maximum number of daily trend points kept
+const MAX_TREND_POINTS: usize = 3650;
+
+/// Battery health tracker configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryHealthConfig {
+    /// Nameplate energy capacity in kWh
+    pub nominal_capacity: f32,
+    /// Interval between cell status polls in seconds
+    pub cell_poll_interval: u64,
+    /// Rated cycle life at 100% depth of discharge
+    pub rated_cycles: f32,
+    /// Wöhler exponent relating cycle life to depth of discharge
+    pub dod_exponent: f32,
+    /// State of health at end of life (0-100%)
+    pub end_of_life_soh: f32,
+    /// SOC reversal deadband for rainflow counting (0-100%)
+    pub reversal_deadband: f32,
+    /// SOC regarded as a full charge (0-100%)
+    pub full_charge_soc: f32,
+    /// Minimum SOC span of a charge used for a capacity estimate (0-100%)
+    pub min_capacity_span: f32,
+    /// One-way charge efficiency used to convert charged energy to stored energy (0-1)
+    pub charge_efficiency: f32,
+    /// Weight of a new capacity measurement (0-1)
+    pub capacity_filter: f32,
+    /// File the tracker state and SOH trend are persisted to
+    pub state_path: Option<String>,
+}
+
+impl Default for BatteryHealthConfig {
+    fn default() -> Self {
+        Self {
+            nominal_capacity: 200.0,
+            cell_poll_interval: 60,
+            rated_cycles: 6000.0,
+            dod_exponent: 1.3,
+            end_of_life_soh: 80.0,
+            reversal_deadband: 2.0,
+            full_charge_soc: 98.0,
+            min_capacity_span: 50.0,
+            charge_efficiency: 0.95,
+            capacity_filter: 0.3,
+            state_path: Some("data_cache/battery_health.json".to_string()),
+        }
+    }
+}
+
+/// Persisted tracker state
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct HealthState {
+    charged_kwh: f32,
+    discharged_kwh: f32,
+    /// Rainflow cycle counts per DoD bin
+    histogram: Vec<f32>,
+    /// Accumulated fatigue damage (1.0 = end of life)
+    damage: f32,
+    /// Unclosed SOC reversals
+    reversals: Vec<f32>,
+    capacity_estimate: Option<f32>,
+    trend: Vec<SohTrendPoint>,
+}
+
+/// Charge session used for a capacity measurement
+#[derive(Debug, Clone, Copy)]
+struct ChargeSession {
+    start_soc: f32,
+    energy: f32,
+}
+
+/// Battery health tracker
+#[derive(Debug, Clone)]
+pub struct BatteryHealthTracker {
+    config: BatteryHealthConfig,
+    state: HealthState,
+    /// Last SOC extreme and its direction (true = rising)
+    extreme: Option<(f32, bool)>,
+    session: Option<ChargeSession>,
+    cells: Option<BatteryCellStatus>,
+    day: Option<NaiveDate>,
+}
+
+impl BatteryHealthTracker {
+    /// Create a new tracker, restoring persisted state if available
+    ///
+    /// # Arguments
+    /// * `config` - Capacity, cycle life and measurement parameters
+    pub fn new(config: BatteryHealthConfig) -> Self {
+        let mut state: HealthState = config.state_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        state.histogram.resize(DOD_BINS, 0.0);
+        Self {
+            config,
+            state,
+            extreme: None,
+            session: None,
+            cells: None,
+            day: None,
+        }
+    }
+
+    /// Interval between cell status polls in seconds
+    pub fn cell_poll_interval(&self) -> u64 {
+        self.config.cell_poll_interval
+    }
+
+    /// Record the latest BMS cell status
+    ///
+    /// # Arguments
+    /// * `cells` - Cell voltage/temperature extremes and BMS counters
+    pub fn observe_cells(&mut self, cells: BatteryCellStatus) {
+        self.cells = Some(cells);
+    }
+
+    /// Latest BMS cell status, if polled
+    pub fn cell_status(&self) -> Option<&BatteryCellStatus> {
+        self.cells.as_ref()
+    }
+
+    /// Account for one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `soc` - Battery state of charge (0-100%)
+    /// * `battery_power` - Battery power in kW (positive = discharging)
+    /// * `dt_hours` - Time since the previous cycle in hours
+    pub fn observe(&mut self, now: NaiveDateTime, soc: f32, battery_power: f32, dt_hours: f32) {
+        let energy = battery_power.abs() * dt_hours;
+        if battery_power < 0.0 {
+            self.state.charged_kwh += energy;
+        } else {
+            self.state.discharged_kwh += energy;
+        }
+
+        self.track_reversals(soc);
+        self.track_charge_session(soc, battery_power, energy);
+
+        // Daily trend point and persistence
+        let today = now.date();
+        if let Some(day) = self.day.filter(|&d| d != today) {
+            self.push_trend(day);
+            self.save();
+        }
+        self.day = Some(today);
+    }
+
+    /// Detect SOC turning points and feed them to the rainflow counter
+    fn track_reversals(&mut self, soc: f32) {
+        let deadband = self.config.reversal_deadband;
+        match self.extreme {
+            None => self.extreme = Some((soc, true)),
+            Some((extreme, rising)) => {
+                if (rising && soc > extreme) || (!rising && soc < extreme) {
+                    self.extreme = Some((soc, rising));
+                } else if (extreme - soc).abs() >= deadband {
+                    // Direction changed by more than the deadband: the extreme is a reversal
+                    self.add_reversal(extreme);
+                    self.extreme = Some((soc, !rising));
+                }
+            }
+        }
+    }
+
+    /// Three-point rainflow counting on the reversal stack
+    fn add_reversal(&mut self, value: f32) {
+        self.state.reversals.push(value);
+        loop {
+            let n = self.state.reversals.len();
+            if n < 3 {
+                break;
+            }
+            let r = &self.state.reversals;
+            let x = (r[n - 1] - r[n - 2]).abs();
+            let y = (r[n - 2] - r[n - 3]).abs();
+            if x < y {
+                break;
+            }
+            if n == 3 {
+                // Range containing the starting point counts as a half cycle
+                self.count_cycle(y, 0.5);
+                self.state.reversals.remove(0);
+            } else {
+                self.count_cycle(y, 1.0);
+                self.state.reversals.drain(n - 3..n - 1);
+            }
+        }
+    }
+
+    fn count_cycle(&mut self, depth: f32, count: f32) {
+        let bin = ((depth / 100.0 * DOD_BINS as f32) as usize).min(DOD_BINS - 1);
+        self.state.histogram[bin] += count;
+        self.state.damage += count * self.cycle_damage(depth);
+    }
+
+    /// Fatigue damage of one cycle of the given depth
+    fn cycle_damage(&self, depth: f32) -> f32 {
+        let dod = (depth / 100.0).clamp(0.01, 1.0);
+        let life = self.config.rated_cycles.max(1.0) * dod.powf(-self.config.dod_exponent);
+        1.0 / life
+    }
+
+    /// Measure capacity from charges that reach full from a low SOC
+    fn track_charge_session(&mut self, soc: f32, battery_power: f32, energy: f32) {
+        if battery_power >= 0.0 {
+            // Discharging or idle restarts the measurement window
+            if battery_power > 0.0 {
+                self.session = None;
+            }
+            return;
+        }
+        let session = self.session.get_or_insert(ChargeSession { start_soc: soc, energy: 0.0 });
+        session.energy += energy;
+        let span = soc - session.start_soc;
+        if soc >= self.config.full_charge_soc && span >= self.config.min_capacity_span {
+            let measured = session.energy * self.config.charge_efficiency / (span / 100.0);
+            self.session = None;
+            let estimate = match self.state.capacity_estimate {
+                Some(c) => c + self.config.capacity_filter * (measured - c),
+                None => measured,
+            };
+            log::info!("Battery capacity measurement {:.1} kWh, estimate {:.1} kWh", measured, estimate);
+            self.state.capacity_estimate = Some(estimate);
+            // A full-charge sample is rare; do not lose it to a restart before the day ends
+            self.save();
+        }
+    }
+
+    /// Accumulated damage including the unclosed half cycles
+    fn total_damage(&self) -> f32 {
+        let residual: f32 = self.state.reversals.windows(2)
+            .map(|w| 0.5 * self.cycle_damage((w[1] - w[0]).abs()))
+            .sum();
+        self.state.damage + residual
+    }
+
+    /// Current state of health and its source
+    fn soh(&self) -> (f32, &'static str) {
+        if let Some(capacity) = self.state.capacity_estimate {
+            return ((capacity / self.config.nominal_capacity.max(1.0) * 100.0).min(100.0), "Capacity");
+        }
+        match self.cells {
+            Some(ref cells) if cells.health_percentage > 0.0 => (cells.health_percentage, "Bms"),
+            _ => {
+                let fade = 100.0 - self.config.end_of_life_soh;
+                (100.0 - self.total_damage() * fade, "CycleModel")
+            }
+        }
+    }
+
+    fn equivalent_full_cycles(&self) -> f32 {
+        (self.state.charged_kwh + self.state.discharged_kwh) / (2.0 * self.config.nominal_capacity.max(1.0))
+    }
+
+    fn push_trend(&mut self, date: NaiveDate) {
+        let (soh, source) = self.soh();
+        let point = SohTrendPoint {
+            date: date.format("%Y-%m-%d").to_string(),
+            soh,
+            equivalent_full_cycles: self.equivalent_full_cycles(),
+            source: source.to_string(),
+        };
+        self.state.trend.push(point);
+        if self.state.trend.len() > MAX_TREND_POINTS {
+            self.state.trend.remove(0);
+        }
+    }
+
+    /// Persist tracker state
+    fn save(&self) {
+        if let Some(ref path) = self.config.state_path {
+            match serde_json::to_string(&self.state) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save battery health to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize battery health: {}", e),
+            }
+        }
+    }
+
+    /// Remaining useful life from the SOH trend
+    ///
+    /// Only trend points from the current SOH source are fitted, since the sources disagree
+    /// on the absolute value and a switch between them would read as fade or recovery.
+    ///
+    /// # Arguments
+    /// * `soh` - Current state of health (0-100%)
+    /// * `source` - Source of the current state of health
+    ///
+    /// # Returns
+    /// Tuple of (days, equivalent full cycles) until end of life; None where the trend shows no fade
+    fn remaining_useful_life(&self, soh: f32, source: &str) -> (Option<f32>, Option<f32>) {
+        let margin = soh - self.config.end_of_life_soh;
+        if margin <= 0.0 {
+            return (Some(0.0), Some(0.0));
+        }
+        let points: Vec<(f32, f32, f32)> = self.state.trend.iter()
+            .filter(|p| p.source == source)
+            .filter_map(|p| {
+                let date = NaiveDate::parse_from_str(&p.date, "%Y-%m-%d").ok()?;
+                Some((date.num_days_from_ce() as f32, p.equivalent_full_cycles, p.soh))
+            })
+            .collect();
+        if points.len() < 2 {
+            return (None, None);
+        }
+        let days = Self::fade_slope(points.iter().map(|&(d, _, s)| (d, s)))
+            .filter(|&rate| rate < 0.0)
+            .map(|rate| margin / -rate);
+        let cycles = Self::fade_slope(points.iter().map(|&(_, c, s)| (c, s)))
+            .filter(|&rate| rate < 0.0)
+            .map(|rate| margin / -rate);
+        (days, cycles)
+    }
+
+    /// Least-squares slope of SOH against x
+    fn fade_slope(points: impl Iterator<Item = (f32, f32)> + Clone) -> Option<f32> {
+        let n = points.clone().count() as f32;
+        let (sx, sy) = points.clone().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+        let (mx, my) = (sx / n, sy / n);
+        let (sxy, sxx) = points.fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+            (sxy + (x - mx) * (y - my), sxx + (x - mx) * (x - mx))
+        });
+        if sxx <= f32::EPSILON {
+            None
+        } else {
+            Some(sxy / sxx)
+        }
+    }
+
+    /// Daily SOH trend
+    pub fn trend(&self) -> &[SohTrendPoint] {
+        &self.state.trend
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> BatteryHealthStatus {
+        let (soh, source) = self.soh();
+        let (rul_days, rul_cycles) = self.remaining_useful_life(soh, source);
+        BatteryHealthStatus {
+            soh,
+            soh_source: source.to_string(),
+            bms_soh: self.cells.as_ref().map(|c| c.health_percentage),
+            capacity_estimate: self.state.capacity_estimate,
+            equivalent_full_cycles: self.equivalent_full_cycles(),
+            bms_cycle_count: self.cells.as_ref().map(|c| c.cycle_count),
+            working_time: self.cells.as_ref().map(|c| c.working_time),
+            cycle_histogram: self.state.histogram.clone(),
+            cycle_damage: self.total_damage() * 100.0,
+            rul_days,
+            rul_cycles,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> BatteryHealthTracker {
+        BatteryHealthTracker::new(BatteryHealthConfig { state_path: None, ..Default::default() })
+    }
+
+    #[test]
+    fn rainflow_counts_an_enclosed_range_as_a_full_cycle() {
+        let mut tracker = tracker();
+        for value in [0.0, 60.0, 20.0, 80.0] {
+            tracker.add_reversal(value);
+        }
+        // 60 -> 20 is enclosed by 0 -> 80
+        assert_eq!(tracker.state.histogram[4], 1.0);
+        assert_eq!(tracker.state.reversals, vec![0.0, 80.0]);
+        assert!((tracker.state.damage - tracker.cycle_damage(40.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rainflow_counts_a_range_containing_the_start_as_a_half_cycle() {
+        let mut tracker = tracker();
+        for value in [50.0, 0.0, 100.0] {
+            tracker.add_reversal(value);
+        }
+        assert_eq!(tracker.state.histogram[5], 0.5);
+        assert_eq!(tracker.state.reversals, vec![0.0, 100.0]);
+        // The open range still counts towards the total as a half cycle
+        let expected = 0.5 * tracker.cycle_damage(50.0) + 0.5 * tracker.cycle_damage(100.0);
+        assert!((tracker.total_damage() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn remaining_useful_life_fits_only_the_current_source() {
+        let mut tracker = tracker();
+        let point = |date: &str, soh, source: &str| SohTrendPoint {
+            date: date.to_string(),
+            soh,
+            equivalent_full_cycles: 0.0,
+            source: source.to_string(),
+        };
+        tracker.state.trend = vec![
+            point("2026-01-01", 99.0, "Bms"),
+            point("2026-01-11", 98.0, "Capacity"),
+            point("2026-01-21", 97.0, "Capacity"),
+        ];
+        // Capacity fades 0.1%/day: 17 points above end of life last 170 days
+        let (days, _) = tracker.remaining_useful_life(97.0, "Capacity");
+        assert!((days.expect("fading trend") - 170.0).abs() < 0.5);
+        // A single BMS point is no trend
+        assert_eq!(tracker.remaining_useful_life(99.0, "Bms"), (None, None));
+    }
+
+    #[test]
+    fn reversals_within_the_deadband_are_ignored() {
+        let mut tracker = tracker();
+        for soc in [50.0, 60.0, 59.0, 60.5, 55.0, 56.0] {
+            tracker.track_reversals(soc);
+        }
+        // Only the 60.5 peak turned by more than the deadband
+        assert_eq!(tracker.state.reversals, vec![60.5]);
+        tracker.track_reversals(70.0);
+        assert_eq!(tracker.state.reversals, vec![60.5, 55.0]);
+    }
+}
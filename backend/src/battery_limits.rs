@@ -0,0 +1,301 @@
+// 电池功率限制 (SOP)
+// Battery power limiter: clamps PCS setpoints to the BMS charge/discharge power limits,
+// derates for cell temperature and voltage margins, ramps soft limits and records limited setpoints
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::types::{BatteryCellStatus, BatteryLimitStatus, BatteryStatus, PowerLimitRecord};
+
+/// Maximum number of limit records kept in memory
+const MAX_RECORDS: usize = 20;
+
+/// Battery power limit configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryLimitConfig {
+    /// Rated charge power in kW (BMS SOP is a percentage of this; the summed rack rating with racks)
+    pub rated_charge_power: f32,
+    /// Rated discharge power in kW (BMS SOP is a percentage of this; the summed rack rating with racks)
+    pub rated_discharge_power: f32,
+    /// Charge derating from this cell temperature down to zero at `charge_temp_min` (°C)
+    pub charge_temp_derate_low: f32,
+    pub charge_temp_min: f32,
+    /// Charge derating from this cell temperature up to zero at `charge_temp_max` (°C)
+    pub charge_temp_derate_high: f32,
+    pub charge_temp_max: f32,
+    /// Discharge derating from this cell temperature down to zero at `discharge_temp_min` (°C)
+    pub discharge_temp_derate_low: f32,
+    pub discharge_temp_min: f32,
+    /// Discharge derating from this cell temperature up to zero at `discharge_temp_max` (°C)
+    pub discharge_temp_derate_high: f32,
+    pub discharge_temp_max: f32,
+    /// Charge tapers from this cell voltage to zero at `max_cell_voltage` (V)
+    pub charge_taper_voltage: f32,
+    pub max_cell_voltage: f32,
+    /// Discharge tapers from this cell voltage to zero at `min_cell_voltage` (V)
+    pub discharge_taper_voltage: f32,
+    pub min_cell_voltage: f32,
+    /// Rate at which temperature/voltage derates tighten the limit, in kW/s
+    pub ramp_down_rate: f32,
+    /// Rate at which a limit recovers after it relaxes, in kW/s
+    pub ramp_up_rate: f32,
+}
+
+impl Default for BatteryLimitConfig {
+    fn default() -> Self {
+        // LFP cells
+        Self {
+            rated_charge_power: 50.0,
+            rated_discharge_power: 50.0,
+            charge_temp_derate_low: 10.0,
+            charge_temp_min: 0.0,
+            charge_temp_derate_high: 45.0,
+            charge_temp_max: 55.0,
+            discharge_temp_derate_low: 0.0,
+            discharge_temp_min: -20.0,
+            discharge_temp_derate_high: 50.0,
+            discharge_temp_max: 60.0,
+            charge_taper_voltage: 3.50,
+            max_cell_voltage: 3.65,
+            discharge_taper_voltage: 2.90,
+            min_cell_voltage: 2.50,
+            ramp_down_rate: 10.0,
+            ramp_up_rate: 2.0,
+        }
+    }
+}
+
+/// Reason a battery power limit is below its rating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitReason {
+    None,
+    Sop,
+    Temperature,
+    CellVoltage,
+    Ramp,
+}
+
+/// Live limit for one power direction
+#[derive(Debug, Clone, Copy)]
+struct DirectionLimit {
+    limit: f32,
+    reason: LimitReason,
+}
+
+/// Battery power limiter
+#[derive(Debug, Clone)]
+pub struct BatteryPowerLimiter {
+    config: BatteryLimitConfig,
+    bms: Option<BatteryStatus>,
+    charge: DirectionLimit,
+    discharge: DirectionLimit,
+    last_update: Option<Instant>,
+    records: Vec<PowerLimitRecord>,
+    limited_count: u64,
+}
+
+/// Linear factor: 1 at or before `full`, 0 at or beyond `zero` (no derating if they coincide)
+fn taper(value: f32, full: f32, zero: f32) -> f32 {
+    let span = zero - full;
+    if span.abs() < f32::EPSILON {
+        return 1.0;
+    }
+    (1.0 - (value - full) / span).clamp(0.0, 1.0)
+}
+
+impl BatteryPowerLimiter {
+    /// Create a new limiter at rated power
+    ///
+    /// # Arguments
+    /// * `config` - Ratings, derating curves and ramp rates
+    pub fn new(config: BatteryLimitConfig) -> Self {
+        let charge = DirectionLimit { limit: config.rated_charge_power, reason: LimitReason::None };
+        let discharge = DirectionLimit { limit: config.rated_discharge_power, reason: LimitReason::None };
+        Self {
+            config,
+            bms: None,
+            charge,
+            discharge,
+            last_update: None,
+            records: Vec::new(),
+            limited_count: 0,
+        }
+    }
+
+    /// Record the latest BMS pack status
+    ///
+    /// # Arguments
+    /// * `status` - BMS status with SOP and pack temperature
+    pub fn observe_bms(&mut self, status: &BatteryStatus) {
+        self.bms = Some(status.clone());
+    }
+
+    /// Recompute the charge and discharge limits
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `cells` - Latest cell extremes, if polled
+    pub fn update(&mut self, now: Instant, cells: Option<&BatteryCellStatus>) {
+        let dt = self.last_update.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+        let bms = match self.bms {
+            Some(ref bms) => bms,
+            None => return,
+        };
+        let c = &self.config;
+        let (t_max, t_min) = cells
+            .map(|cells| (cells.max_cell_temperature, cells.min_cell_temperature))
+            .unwrap_or((bms.temperature, bms.temperature));
+
+        // Charge: SOP, temperature window and approach to the upper cell voltage
+        let charge_sop = c.rated_charge_power * (bms.sop_charge / 100.0).clamp(0.0, 1.0);
+        let charge_temp = taper(t_max, c.charge_temp_derate_high, c.charge_temp_max)
+            .min(taper(t_min, c.charge_temp_derate_low, c.charge_temp_min));
+        let charge_voltage = cells
+            .map(|cells| taper(cells.max_cell_voltage, c.charge_taper_voltage, c.max_cell_voltage))
+            .unwrap_or(1.0);
+        let charge_target = Self::target(c.rated_charge_power, charge_sop, charge_temp, charge_voltage);
+
+        // Discharge: SOP, temperature window and approach to the lower cell voltage
+        let discharge_sop = c.rated_discharge_power * (bms.sop_discharge / 100.0).clamp(0.0, 1.0);
+        let discharge_temp = taper(t_max, c.discharge_temp_derate_high, c.discharge_temp_max)
+            .min(taper(t_min, c.discharge_temp_derate_low, c.discharge_temp_min));
+        let discharge_voltage = cells
+            .map(|cells| taper(cells.min_cell_voltage, c.discharge_taper_voltage, c.min_cell_voltage))
+            .unwrap_or(1.0);
+        let discharge_target = Self::target(c.rated_discharge_power, discharge_sop, discharge_temp, discharge_voltage);
+
+        let (down, up) = (c.ramp_down_rate * dt, c.ramp_up_rate * dt);
+        self.charge = Self::ramp(self.charge, charge_target, charge_sop, down, up);
+        self.discharge = Self::ramp(self.discharge, discharge_target, discharge_sop, down, up);
+    }
+
+    /// Tightest of the SOP and derated limits and its reason
+    fn target(rated: f32, sop: f32, temp_factor: f32, voltage_factor: f32) -> DirectionLimit {
+        let candidates = [
+            (sop, LimitReason::Sop),
+            (rated * temp_factor, LimitReason::Temperature),
+            (rated * voltage_factor, LimitReason::CellVoltage),
+        ];
+        candidates.iter()
+            .filter(|(limit, _)| *limit < rated)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|&(limit, reason)| DirectionLimit { limit, reason })
+            .unwrap_or(DirectionLimit { limit: rated, reason: LimitReason::None })
+    }
+
+    /// Move the live limit toward the target; the BMS SOP always applies immediately
+    fn ramp(current: DirectionLimit, target: DirectionLimit, sop: f32, down: f32, up: f32) -> DirectionLimit {
+        let limit = if target.limit < current.limit {
+            (current.limit - down).max(target.limit)
+        } else {
+            (current.limit + up).min(target.limit)
+        }
+        .min(sop);
+        let reason = if limit < target.limit { LimitReason::Ramp } else { target.reason };
+        DirectionLimit { limit, reason }
+    }
+
+    /// Clamp a charge setpoint to the live limit
+    ///
+    /// # Arguments
+    /// * `requested` - Requested charge power in kW
+    ///
+    /// # Returns
+    /// Permitted charge power in kW
+    pub fn clamp_charge(&mut self, requested: f32) -> f32 {
+        let limit = self.charge;
+        self.clamp("Charge", requested, limit)
+    }
+
+    /// Clamp a discharge setpoint to the live limit
+    ///
+    /// # Arguments
+    /// * `requested` - Requested discharge power in kW
+    ///
+    /// # Returns
+    /// Permitted discharge power in kW
+    pub fn clamp_discharge(&mut self, requested: f32) -> f32 {
+        let limit = self.discharge;
+        self.clamp("Discharge", requested, limit)
+    }
+
+    fn clamp(&mut self, direction: &str, requested: f32, limit: DirectionLimit) -> f32 {
+        let applied = requested.clamp(0.0, limit.limit);
+        if requested - applied < 0.01 {
+            return applied;
+        }
+        self.limited_count += 1;
+        let reason = match limit.reason {
+            // A limit below rating always has a reason; rating itself caps the request otherwise
+            LimitReason::None => "Rating".to_string(),
+            reason => format!("{:?}", reason),
+        };
+        let timestamp = Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+        match self.records.last_mut() {
+            // Collapse repeats of the same limitation into one record
+            Some(last) if last.direction == direction && last.reason == reason => {
+                last.timestamp = timestamp;
+                last.requested = requested;
+                last.applied = applied;
+                last.count += 1;
+            }
+            _ => {
+                log::info!("Battery {} setpoint limited {:.1} -> {:.1} kW ({})", direction.to_lowercase(), requested, applied, reason);
+                self.records.push(PowerLimitRecord {
+                    timestamp,
+                    direction: direction.to_string(),
+                    requested,
+                    applied,
+                    reason,
+                    count: 1,
+                });
+                if self.records.len() > MAX_RECORDS {
+                    self.records.remove(0);
+                }
+            }
+        }
+        applied
+    }
+
+    /// Replace the ratings the BMS SOP is scaled by
+    ///
+    /// # Arguments
+    /// * `charge` - Rated charge power in kW
+    /// * `discharge` - Rated discharge power in kW
+    pub fn set_rated_power(&mut self, charge: f32, discharge: f32) {
+        self.config.rated_charge_power = charge;
+        self.config.rated_discharge_power = discharge;
+        if self.bms.is_none() {
+            // Nothing computed yet: start at the new rating as `new` does
+            self.charge.limit = charge;
+            self.discharge.limit = discharge;
+        } else {
+            self.charge.limit = self.charge.limit.min(charge);
+            self.discharge.limit = self.discharge.limit.min(discharge);
+        }
+    }
+
+    /// Live charge power limit in kW
+    pub fn max_charge_power(&self) -> f32 {
+        self.charge.limit
+    }
+
+    /// Live discharge power limit in kW
+    pub fn max_discharge_power(&self) -> f32 {
+        self.discharge.limit
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> BatteryLimitStatus {
+        BatteryLimitStatus {
+            max_charge_power: self.charge.limit,
+            max_discharge_power: self.discharge.limit,
+            charge_limit_reason: format!("{:?}", self.charge.reason),
+            discharge_limit_reason: format!("{:?}", self.discharge.reason),
+            limited_setpoints: self.limited_count,
+            records: self.records.clone(),
+        }
+    }
+}
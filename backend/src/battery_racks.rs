@@ -0,0 +1,349 @@
+// 多电池簇并联管理
+// Parallel battery racks, each with its own BMS and PCS: capacity-weighted SOC aggregation,
+// SOP-respecting summed power limits, rack dropout without tripping the site and SOC balancing
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use crate::devices::bms::BatteryDevice;
+use crate::devices::pcs::{PcsDevice, PcsMode, PcsReactiveMode};
+use crate::types::{BatteryRackStatus, BatteryStatus, RackStatus};
+
+/// One battery rack and the PCS that drives it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RackConfig {
+    /// Rack identifier (a rack with the site battery_id reuses the primary BMS and PCS)
+    pub id: String,
+    /// CAN interface of the rack BMS
+    pub battery_interface: String,
+    /// PCS identifier
+    pub pcs_id: String,
+    /// PCS Modbus host
+    pub pcs_host: String,
+    /// PCS Modbus port
+    pub pcs_port: u16,
+    /// Usable rack capacity in kWh
+    pub capacity_kwh: f32,
+    /// Rated rack power in kW (BMS SOP is a percentage of this)
+    pub rated_power: f32,
+}
+
+/// Battery rack group configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryRackConfig {
+    /// Racks operated in parallel
+    pub racks: Vec<RackConfig>,
+    /// Setpoint bias per % SOC deviation from the group mean, in kW
+    pub soc_balance_gain: f32,
+    /// Maximum bias as a fraction of a rack's proportional share (0-1)
+    pub max_balance_bias: f32,
+    /// Consecutive failed BMS reads before a rack is dropped from dispatch
+    pub dropout_failures: u32,
+}
+
+impl Default for BatteryRackConfig {
+    fn default() -> Self {
+        Self {
+            racks: Vec::new(),
+            soc_balance_gain: 2.0,
+            max_balance_bias: 0.3,
+            dropout_failures: 3,
+        }
+    }
+}
+
+/// Runtime state of one rack
+#[derive(Debug)]
+struct Rack {
+    config: RackConfig,
+    battery: Arc<Mutex<BatteryDevice>>,
+    pcs: Arc<Mutex<PcsDevice>>,
+    status: Option<BatteryStatus>,
+    failures: u32,
+    available: bool,
+    setpoint: f32,
+}
+
+impl Rack {
+    /// Power limit in the requested direction from the rack SOP, in kW
+    fn limit(&self, discharge: bool) -> f32 {
+        let sop = self.status.as_ref()
+            .map(|s| if discharge { s.sop_discharge } else { s.sop_charge })
+            .unwrap_or(0.0);
+        self.config.rated_power * (sop / 100.0).clamp(0.0, 1.0)
+    }
+
+    fn soc(&self) -> f32 {
+        self.status.as_ref().map(|s| s.soc).unwrap_or(0.0)
+    }
+
+    /// Write the rack PCS mode and setpoint
+    ///
+    /// # Arguments
+    /// * `power` - Rack power in kW (positive = discharging, 0 = standby)
+    fn apply(&mut self, power: f32) -> Result<(), String> {
+        let mode = if power > 0.0 {
+            PcsMode::Discharging
+        } else if power < 0.0 {
+            PcsMode::Charging
+        } else {
+            PcsMode::Standby
+        };
+        let mut pcs_locked = self.pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+        pcs_locked.set_mode(mode)
+            .map_err(|e| format!("Failed to set rack {} PCS mode: {:?}", self.config.id, e))?;
+        pcs_locked.set_power_setpoint(power)
+            .map_err(|e| format!("Failed to set rack {} PCS power: {:?}", self.config.id, e))?;
+        self.setpoint = power;
+        Ok(())
+    }
+}
+
+/// Parallel battery rack group
+#[derive(Debug)]
+pub struct BatteryRackGroup {
+    config: BatteryRackConfig,
+    racks: Vec<Rack>,
+}
+
+impl BatteryRackGroup {
+    /// Create an empty rack group
+    ///
+    /// # Arguments
+    /// * `config` - Balancing and dropout parameters
+    pub fn new(config: BatteryRackConfig) -> Self {
+        Self { config, racks: Vec::new() }
+    }
+
+    /// Add a rack; it joins dispatch after its first successful BMS read
+    ///
+    /// # Arguments
+    /// * `config` - Rack capacity and rating
+    /// * `battery` - Rack BMS
+    /// * `pcs` - Rack PCS
+    pub fn add_rack(&mut self, config: RackConfig, battery: Arc<Mutex<BatteryDevice>>, pcs: Arc<Mutex<PcsDevice>>) {
+        self.racks.push(Rack {
+            config,
+            battery,
+            pcs,
+            status: None,
+            failures: 0,
+            available: false,
+            setpoint: 0.0,
+        });
+    }
+
+    /// Summed rating of all racks in kW, the base of the aggregated SOP
+    pub fn rated_power(&self) -> f32 {
+        self.racks.iter().map(|r| r.config.rated_power).sum()
+    }
+
+    /// Read every rack BMS and aggregate the available racks
+    ///
+    /// # Returns
+    /// Aggregated pack status (SOP as a percentage of the summed rack ratings), None if no rack is available
+    pub fn read(&mut self) -> Result<Option<BatteryStatus>, String> {
+        for rack in &mut self.racks {
+            let result = rack.battery.lock().map_err(|_| "Mutex poisoned".to_string())?.read_status();
+            match result {
+                Ok(status) => {
+                    if !rack.available {
+                        log::info!("Battery rack {} online at {:.1}% SOC", rack.config.id, status.soc);
+                    }
+                    rack.status = Some(status);
+                    rack.failures = 0;
+                    rack.available = true;
+                }
+                Err(e) => {
+                    rack.failures += 1;
+                    log::warn!("Failed to read battery rack {} status: {}", rack.config.id, e);
+                    if rack.available && rack.failures >= self.config.dropout_failures {
+                        log::error!("Battery rack {} dropped out after {} failed reads", rack.config.id, rack.failures);
+                        rack.available = false;
+                        if let Err(e) = rack.apply(0.0) {
+                            log::warn!("{}", e);
+                        }
+                    }
+                }
+            }
+        }
+
+        let online: Vec<&Rack> = self.racks.iter().filter(|r| r.available).collect();
+        let statuses: Vec<(&Rack, &BatteryStatus)> = online.iter()
+            .filter_map(|r| r.status.as_ref().map(|s| (*r, s)))
+            .collect();
+        if statuses.is_empty() {
+            return Ok(None);
+        }
+        let capacity: f32 = statuses.iter().map(|(r, _)| r.config.capacity_kwh).sum::<f32>().max(f32::EPSILON);
+        let rated = self.rated_power().max(f32::EPSILON);
+        let n = statuses.len() as f32;
+        let voltage = statuses.iter().map(|(_, s)| s.voltage).sum::<f32>() / n;
+        let power: f32 = statuses.iter().map(|(_, s)| s.current * s.voltage).sum();
+        Ok(Some(BatteryStatus {
+            soc: statuses.iter().map(|(r, s)| s.soc * r.config.capacity_kwh).sum::<f32>() / capacity,
+            voltage,
+            current: if voltage > 0.0 { power / voltage } else { 0.0 },
+            temperature: statuses.iter().map(|(_, s)| s.temperature).fold(f32::MIN, f32::max),
+            sop_charge: online.iter().map(|r| r.limit(false)).sum::<f32>() / rated * 100.0,
+            sop_discharge: online.iter().map(|r| r.limit(true)).sum::<f32>() / rated * 100.0,
+        }))
+    }
+
+    /// Split a group setpoint across the available racks
+    ///
+    /// Each rack gets a share proportional to its SOP limit, biased toward the racks furthest
+    /// from the group SOC so that the racks converge; dropped racks are held in standby.
+    /// A rack whose PCS rejects its setpoint drops out until its next good read and its
+    /// share goes to the remaining racks.
+    ///
+    /// # Arguments
+    /// * `power` - Group power in kW (positive = discharging, negative = charging)
+    ///
+    /// # Returns
+    /// Result containing the applied group power
+    pub fn dispatch(&mut self, power: f32) -> Result<f32, String> {
+        let discharge = power > 0.0;
+        let sign = if discharge { 1.0 } else { -1.0 };
+        let mut online: Vec<usize> = (0..self.racks.len()).filter(|&i| self.racks[i].available).collect();
+        let applied = loop {
+            let setpoints = self.split(&online, discharge, power.abs());
+            let failed = online.iter().zip(&setpoints).position(|(&i, &setpoint)| {
+                let rack = &mut self.racks[i];
+                match rack.apply(sign * setpoint) {
+                    Ok(()) => false,
+                    Err(e) => {
+                        log::error!("{}; dropping battery rack {} from dispatch", e, rack.config.id);
+                        rack.available = false;
+                        true
+                    }
+                }
+            });
+            match failed {
+                Some(k) => {
+                    online.remove(k);
+                }
+                None => break setpoints.iter().sum::<f32>(),
+            }
+        };
+        for rack in self.racks.iter_mut().filter(|r| !r.available && r.setpoint != 0.0) {
+            if let Err(e) = rack.apply(0.0) {
+                log::warn!("{}", e);
+            }
+        }
+        Ok(sign * applied)
+    }
+
+    /// Rack shares of a group power, in the order of `online`
+    ///
+    /// # Arguments
+    /// * `online` - Indices of the racks taking part
+    /// * `discharge` - Direction of the group power
+    /// * `power` - Group power magnitude in kW
+    fn split(&self, online: &[usize], discharge: bool, power: f32) -> Vec<f32> {
+        let limits: Vec<f32> = online.iter().map(|&i| self.racks[i].limit(discharge)).collect();
+        let total_limit: f32 = limits.iter().sum();
+        let requested = power.min(total_limit);
+
+        let mut setpoints = vec![0.0; online.len()];
+        if requested > 0.0 {
+            let capacity: f32 = online.iter().map(|&i| self.racks[i].config.capacity_kwh).sum::<f32>().max(f32::EPSILON);
+            let mean_soc = online.iter()
+                .map(|&i| self.racks[i].soc() * self.racks[i].config.capacity_kwh)
+                .sum::<f32>() / capacity;
+            // Discharge high-SOC racks harder, charge low-SOC racks harder
+            let sign = if discharge { 1.0 } else { -1.0 };
+            let shares: Vec<f32> = limits.iter().map(|l| requested * l / total_limit).collect();
+            let biases: Vec<f32> = online.iter().zip(&shares)
+                .map(|(&i, share)| {
+                    let max_bias = self.config.max_balance_bias * share;
+                    (sign * self.config.soc_balance_gain * (self.racks[i].soc() - mean_soc)).clamp(-max_bias, max_bias)
+                })
+                .collect();
+            let mean_bias = biases.iter().sum::<f32>() / biases.len() as f32;
+            for (k, setpoint) in setpoints.iter_mut().enumerate() {
+                *setpoint = (shares[k] + biases[k] - mean_bias).clamp(0.0, limits[k]);
+            }
+
+            // Hand any shortfall from clamping to racks with headroom
+            let shortfall = requested - setpoints.iter().sum::<f32>();
+            let headroom: f32 = setpoints.iter().zip(&limits).map(|(s, l)| l - s).sum();
+            if shortfall > 0.01 && headroom > 0.0 {
+                for (setpoint, limit) in setpoints.iter_mut().zip(&limits) {
+                    *setpoint += shortfall * (limit - *setpoint) / headroom;
+                }
+            }
+        }
+        setpoints
+    }
+
+    /// Command every rack PCS into an operating mode (e.g. OffGrid for island operation)
+    ///
+    /// # Arguments
+    /// * `mode` - PCS operating mode
+    ///
+    /// # Returns
+    /// Result indicating success or PCS control error
+    pub fn set_operating_mode(&mut self, mode: PcsMode) -> Result<(), String> {
+        for rack in &self.racks {
+            let mut pcs_locked = rack.pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_mode(mode)
+                .map_err(|e| format!("Failed to set rack {} PCS mode: {:?}", rack.config.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Send a reactive control command to every rack PCS, splitting reactive power by rating
+    ///
+    /// # Arguments
+    /// * `mode` - Reactive power control mode
+    /// * `value` - Power factor for FixedPowerFactor, site reactive power in kvar for FixedReactivePower
+    ///
+    /// # Returns
+    /// Result indicating success or PCS control error
+    pub fn set_reactive(&mut self, mode: PcsReactiveMode, value: f32) -> Result<(), String> {
+        // Reactive power does not draw on the battery, so dropped-out racks still take their share
+        let rating = self.rated_power().max(f32::EPSILON);
+        for rack in &self.racks {
+            let value = match mode {
+                PcsReactiveMode::FixedReactivePower => value * rack.config.rated_power / rating,
+                _ => value,
+            };
+            let mut pcs_locked = rack.pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_reactive_control(mode, value)
+                .map_err(|e| format!("Failed to set rack {} PCS reactive control: {:?}", rack.config.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> BatteryRackStatus {
+        let racks: Vec<RackStatus> = self.racks.iter()
+            .map(|r| RackStatus {
+                id: r.config.id.clone(),
+                online: r.available,
+                soc: r.status.as_ref().map(|s| s.soc),
+                power: r.status.as_ref().map(|s| s.current * s.voltage / 1000.0).unwrap_or(0.0),
+                setpoint: r.setpoint,
+                max_charge_power: if r.available { r.limit(false) } else { 0.0 },
+                max_discharge_power: if r.available { r.limit(true) } else { 0.0 },
+                capacity_kwh: r.config.capacity_kwh,
+                failed_reads: r.failures,
+            })
+            .collect();
+        let online_socs: Vec<f32> = racks.iter().filter(|r| r.online).filter_map(|r| r.soc).collect();
+        BatteryRackStatus {
+            online_racks: racks.iter().filter(|r| r.online).count(),
+            total_racks: racks.len(),
+            online_capacity_kwh: racks.iter().filter(|r| r.online).map(|r| r.capacity_kwh).sum(),
+            max_charge_power: racks.iter().map(|r| r.max_charge_power).sum(),
+            max_discharge_power: racks.iter().map(|r| r.max_discharge_power).sum(),
+            soc_spread: if online_socs.is_empty() {
+                0.0
+            } else {
+                online_socs.iter().cloned().fold(f32::MIN, f32::max) - online_socs.iter().cloned().fold(f32::MAX, f32::min)
+            },
+            racks,
+        }
+    }
+}
@@ -0,0 +1,281 @@
+// 电芯一致性分析
+// Cell imbalance analytics on per-cell BMS data: voltage/temperature spread, statistical outlier cells,
+// weak-cell tracking from persistent voltage deviation and a daily imbalance trend; per-cell reads run
+// on their own BMS connection in a background thread
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use crate::devices::bms::BatteryDevice;
+use crate::types::{BatteryCellDetails, CellHeatmap, CellImbalanceStatus, ImbalanceTrendPoint};
+
+/// Maximum number of daily trend points kept
+const MAX_TREND_POINTS: usize = 365;
+/// Number of weakest cells reported
+const WEAK_CELLS_REPORTED: usize = 5;
+
+/// Cell analytics configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CellAnalyticsConfig {
+    /// Interval between per-cell reads in seconds
+    pub poll_interval: u64,
+    /// Number of cells per module
+    pub cells_per_module: u16,
+    /// Outlier threshold in standard deviations from the pack mean
+    pub outlier_sigma: f32,
+    /// Minimum deviation from the pack mean for an outlier, in mV
+    pub outlier_min_mv: f32,
+    /// Average deviation below the pack mean that marks a weak cell, in mV
+    pub weak_cell_mv: f32,
+    /// Weight of a new reading in the per-cell deviation average (0-1)
+    pub deviation_filter: f32,
+    /// Voltage spread that raises an imbalance alarm, in mV
+    pub spread_alarm_mv: f32,
+    /// File the per-cell deviation history and imbalance trend are persisted to
+    pub state_path: Option<String>,
+}
+
+impl Default for CellAnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: 300,
+            cells_per_module: 16,
+            outlier_sigma: 3.0,
+            outlier_min_mv: 30.0,
+            weak_cell_mv: 20.0,
+            deviation_filter: 0.05,
+            spread_alarm_mv: 100.0,
+            state_path: Some("data_cache/cell_analytics.json".to_string()),
+        }
+    }
+}
+
+/// Persisted analyser state
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct AnalyticsState {
+    /// Long-term average deviation from the pack mean per cell, in mV
+    deviation: Vec<f32>,
+    trend: Vec<ImbalanceTrendPoint>,
+}
+
+/// Cell imbalance analyser
+#[derive(Debug, Clone)]
+pub struct CellAnalytics {
+    config: CellAnalyticsConfig,
+    details: Option<BatteryCellDetails>,
+    state: AnalyticsState,
+    status: CellImbalanceStatus,
+    day: Option<NaiveDate>,
+}
+
+impl CellAnalytics {
+    /// Create a new analyser, restoring persisted state if available
+    ///
+    /// # Arguments
+    /// * `config` - Polling, outlier and weak-cell thresholds
+    pub fn new(config: CellAnalyticsConfig) -> Self {
+        let state: AnalyticsState = config.state_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        // A trend point already recorded today is not repeated after a restart
+        let day = state.trend.last().and_then(|p| NaiveDate::parse_from_str(&p.date, "%Y-%m-%d").ok());
+        Self {
+            config,
+            details: None,
+            state,
+            status: CellImbalanceStatus::default(),
+            day,
+        }
+    }
+
+    /// Persist the deviation history and trend
+    fn save(&self) {
+        if let Some(ref path) = self.config.state_path {
+            match serde_json::to_string(&self.state) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save cell analytics to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize cell analytics: {}", e),
+            }
+        }
+    }
+
+    /// Interval between per-cell reads in seconds
+    pub fn poll_interval(&self) -> u64 {
+        self.config.poll_interval
+    }
+
+    /// Number of cells per module
+    pub fn cells_per_module(&self) -> u16 {
+        self.config.cells_per_module
+    }
+
+    /// Analyse a new per-cell reading
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `details` - Per-cell voltages, temperatures and balancing state
+    pub fn update(&mut self, now: NaiveDateTime, details: BatteryCellDetails) {
+        let voltages: Vec<(usize, f32)> = details.cells.iter()
+            .filter_map(|c| c.voltage.map(|v| (c.index as usize, v)))
+            .collect();
+        let temperatures: Vec<f32> = details.cells.iter().filter_map(|c| c.temperature).collect();
+        if voltages.is_empty() {
+            self.details = Some(details);
+            return;
+        }
+
+        let n = voltages.len() as f32;
+        let mean = voltages.iter().map(|&(_, v)| v).sum::<f32>() / n;
+        let std_dev = (voltages.iter().map(|&(_, v)| (v - mean).powi(2)).sum::<f32>() / n).sqrt();
+        let (v_min, v_max) = voltages.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &(_, v)| (lo.min(v), hi.max(v)));
+        let (t_min, t_max) = temperatures.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &t| (lo.min(t), hi.max(t)));
+
+        // Outliers: far from the mean both statistically and in absolute terms
+        let threshold = (self.config.outlier_sigma * std_dev).max(self.config.outlier_min_mv / 1000.0);
+        let outlier_cells: Vec<u16> = voltages.iter()
+            .filter(|&&(_, v)| (v - mean).abs() > threshold)
+            .map(|&(i, _)| i as u16)
+            .collect();
+
+        // Weak cells: persistently below the pack mean
+        let deviation = &mut self.state.deviation;
+        if deviation.len() != details.cells.len() {
+            *deviation = vec![0.0; details.cells.len()];
+        }
+        let alpha = self.config.deviation_filter;
+        for &(i, v) in &voltages {
+            deviation[i] += alpha * ((v - mean) * 1000.0 - deviation[i]);
+        }
+        let mut ranked: Vec<(usize, f32)> = deviation.iter().copied().enumerate().collect();
+        ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let weak_cells: Vec<u16> = ranked.iter()
+            .take(WEAK_CELLS_REPORTED)
+            .filter(|&&(_, d)| d <= -self.config.weak_cell_mv)
+            .map(|&(i, _)| i as u16)
+            .collect();
+
+        let spread_mv = (v_max - v_min) * 1000.0;
+        if spread_mv > self.config.spread_alarm_mv && self.status.voltage_spread_mv <= self.config.spread_alarm_mv {
+            log::warn!("Cell voltage spread {:.0} mV exceeds {:.0} mV", spread_mv, self.config.spread_alarm_mv);
+        }
+        self.status = CellImbalanceStatus {
+            cell_count: details.cells.len(),
+            mean_voltage: mean,
+            voltage_std_dev_mv: std_dev * 1000.0,
+            voltage_spread_mv: spread_mv,
+            temperature_spread: if temperatures.is_empty() { 0.0 } else { t_max - t_min },
+            balancing_cells: details.cells.iter().filter(|c| c.balancing).count(),
+            outlier_cells,
+            weak_cells,
+            weakest_deviation_mv: ranked.first().map(|&(_, d)| d).unwrap_or(0.0),
+            spread_alarm: spread_mv > self.config.spread_alarm_mv,
+            missing_values: details.missing_values,
+        };
+        self.details = Some(details);
+
+        // One trend point per day from the first reading of the day
+        let today = now.date();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.state.trend.push(ImbalanceTrendPoint {
+                date: today.format("%Y-%m-%d").to_string(),
+                voltage_spread_mv: self.status.voltage_spread_mv,
+                weakest_cell: ranked.first().map(|&(i, _)| i as u16),
+                weakest_deviation_mv: self.status.weakest_deviation_mv,
+            });
+            if self.state.trend.len() > MAX_TREND_POINTS {
+                self.state.trend.remove(0);
+            }
+        }
+        // Saved on every read: the deviation average takes weeks to build up
+        self.save();
+    }
+
+    /// Imbalance summary for EmsStatus
+    pub fn status(&self) -> CellImbalanceStatus {
+        self.status.clone()
+    }
+
+    /// Full per-cell data for the heat-map view
+    pub fn heatmap(&self) -> CellHeatmap {
+        CellHeatmap {
+            details: self.details.clone().unwrap_or_default(),
+            deviation_mv: self.state.deviation.clone(),
+            summary: self.status.clone(),
+            trend: self.state.trend.clone(),
+        }
+    }
+}
+
+/// Per-cell reads on a dedicated BMS connection in a background thread, so the multi-frame burst
+/// neither stalls the control cycle nor holds the battery device lock
+#[derive(Debug)]
+pub struct CellDetailReader {
+    requests: Sender<(u16, u16)>,
+    results: Receiver<Result<BatteryCellDetails, String>>,
+    pending: bool,
+}
+
+impl CellDetailReader {
+    /// Start the reader thread
+    ///
+    /// # Arguments
+    /// * `device` - Battery device with its own CAN connection, used only by the reader
+    pub fn spawn(device: BatteryDevice) -> Self {
+        let (requests, request_rx) = mpsc::channel::<(u16, u16)>();
+        let (result_tx, results) = mpsc::channel();
+        thread::spawn(move || {
+            for (cell_count, cells_per_module) in request_rx {
+                let result = device.read_cell_details(cell_count, cells_per_module).map_err(|e| e.to_string());
+                if result_tx.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results, pending: false }
+    }
+
+    /// Start a read unless one is still in progress
+    ///
+    /// # Arguments
+    /// * `cell_count` - Number of cells in the pack
+    /// * `cells_per_module` - Number of cells per module
+    ///
+    /// # Returns
+    /// Whether a read was started
+    pub fn request(&mut self, cell_count: u16, cells_per_module: u16) -> bool {
+        if self.pending {
+            return false;
+        }
+        self.pending = self.requests.send((cell_count, cells_per_module)).is_ok();
+        self.pending
+    }
+
+    /// Result of the read in progress, once it has finished
+    ///
+    /// # Returns
+    /// None while the read is running or when none was requested
+    pub fn poll(&mut self) -> Option<Result<BatteryCellDetails, String>> {
+        if !self.pending {
+            return None;
+        }
+        match self.results.try_recv() {
+            Ok(result) => {
+                self.pending = false;
+                Some(result)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.pending = false;
+                Some(Err("Cell detail reader stopped".to_string()))
+            }
+        }
+    }
+}
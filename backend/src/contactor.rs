@@ -0,0 +1,438 @@
+// 电池接触器与预充控制
+// Battery contactor sequencing: weld check, precharge against the DC link voltage, main contactor
+// closing, current-free opening, feedback supervision and latched faults
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::types::{ContactorCommand, ContactorFeedback, ContactorStatus};
+
+/// Contactor sequencing configuration
+///
+/// The sequence advances once per EMS control cycle (5 s by default), so every timing below is
+/// rounded up to whole cycles. The defaults are whole cycles: one for the minimum precharge,
+/// two for feedback and current-free opening, three for the precharge and communication timeouts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContactorConfig {
+    /// Link voltage as a percentage of pack voltage that completes precharge
+    pub precharge_threshold: f32,
+    /// Minimum precharge time in milliseconds
+    pub min_precharge_ms: u64,
+    /// Maximum precharge time in milliseconds
+    pub precharge_timeout_ms: u64,
+    /// Time allowed for contactor feedback to follow a command, in milliseconds
+    pub feedback_timeout_ms: u64,
+    /// Pack current below which contactors may open, in A
+    pub open_current: f32,
+    /// Maximum wait for the current to fall before opening anyway, in milliseconds
+    pub open_delay_ms: u64,
+    /// Link voltage as a percentage of pack voltage that indicates a welded main contactor while open
+    pub weld_voltage_threshold: f32,
+    /// Time without BMS contactor data before the contactors are opened, in milliseconds
+    pub comm_timeout_ms: u64,
+}
+
+impl Default for ContactorConfig {
+    fn default() -> Self {
+        Self {
+            precharge_threshold: 95.0,
+            min_precharge_ms: 5000,
+            precharge_timeout_ms: 15000,
+            feedback_timeout_ms: 10000,
+            open_current: 5.0,
+            open_delay_ms: 10000,
+            weld_voltage_threshold: 50.0,
+            comm_timeout_ms: 15000,
+        }
+    }
+}
+
+/// Contactor sequence state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactorState {
+    Open,          // All contactors open, battery offline
+    Precharging,   // Main negative and precharge closed, link voltage rising
+    Closing,       // Main positive commanded, waiting for feedback
+    Closed,        // Battery online
+    Unloading,     // Waiting for the pack current to fall before opening
+    Opening,       // Open commanded, waiting for feedback
+    Fault,         // Latched fault, contactors open
+}
+
+const OPEN: ContactorCommand = ContactorCommand { main_positive: false, main_negative: false, precharge: false };
+const PRECHARGE: ContactorCommand = ContactorCommand { main_positive: false, main_negative: true, precharge: true };
+const CLOSING: ContactorCommand = ContactorCommand { main_positive: true, main_negative: true, precharge: true };
+const CLOSED: ContactorCommand = ContactorCommand { main_positive: true, main_negative: true, precharge: false };
+
+/// Battery contactor controller
+#[derive(Debug, Clone)]
+pub struct ContactorController {
+    config: ContactorConfig,
+    state: ContactorState,
+    state_since: Instant,
+    command: ContactorCommand,
+    online_requested: bool,
+    feedback: ContactorFeedback,
+    last_feedback: Option<Instant>,
+    fault: Option<String>,
+}
+
+impl ContactorController {
+    /// Create a new controller with all contactors open
+    ///
+    /// # Arguments
+    /// * `config` - Precharge thresholds and timeouts
+    pub fn new(config: ContactorConfig) -> Self {
+        Self {
+            config,
+            state: ContactorState::Open,
+            state_since: Instant::now(),
+            command: OPEN,
+            online_requested: false,
+            feedback: ContactorFeedback::default(),
+            last_feedback: None,
+            fault: None,
+        }
+    }
+
+    fn enter(&mut self, state: ContactorState, now: Instant) {
+        log::info!("Battery contactors: {:?} -> {:?}", self.state, state);
+        self.state = state;
+        self.state_since = now;
+    }
+
+    fn elapsed_ms(&self, now: Instant) -> u64 {
+        now.duration_since(self.state_since).as_millis() as u64
+    }
+
+    fn trip(&mut self, now: Instant, reason: String) {
+        log::error!("Battery contactor fault: {}", reason);
+        self.fault = Some(reason);
+        self.command = OPEN;
+        self.enter(ContactorState::Fault, now);
+    }
+
+    fn feedback_matches(&self) -> bool {
+        self.feedback.main_positive == self.command.main_positive
+            && self.feedback.main_negative == self.command.main_negative
+            && self.feedback.precharge == self.command.precharge
+    }
+
+    /// Request the battery online or offline
+    ///
+    /// # Arguments
+    /// * `online` - True to close the contactors, false to open them
+    pub fn request_online(&mut self, online: bool) {
+        self.online_requested = online;
+    }
+
+    /// Clear a latched fault; the contactors stay open until online is requested again
+    pub fn reset_fault(&mut self) {
+        if self.state == ContactorState::Fault {
+            self.fault = None;
+            self.online_requested = false;
+            self.enter(ContactorState::Open, Instant::now());
+        }
+    }
+
+    /// Whether the battery is connected to the DC link
+    pub fn is_online(&self) -> bool {
+        self.state == ContactorState::Closed
+    }
+
+    /// Contactor command to hold on the bus
+    pub fn command(&self) -> ContactorCommand {
+        self.command
+    }
+
+    /// Note a failed contactor status read; opens the contactors once the data is stale
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    pub fn comm_lost(&mut self, now: Instant) {
+        let stale = self.last_feedback
+            .map(|t| now.duration_since(t) >= Duration::from_millis(self.config.comm_timeout_ms))
+            .unwrap_or(true);
+        if stale && !matches!(self.state, ContactorState::Open | ContactorState::Fault) {
+            self.trip(now, "BMS contactor communication lost".to_string());
+        }
+    }
+
+    /// Take over contactors left closed by a previous EMS run instead of treating them as welded
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    fn adopt(&mut self, now: Instant) {
+        let closed = self.feedback.main_positive && self.feedback.main_negative;
+        if closed && self.feedback.permit {
+            log::info!("Battery contactors found closed, adopting the online state");
+            self.command = CLOSED;
+            self.enter(ContactorState::Closed, now);
+        } else if self.feedback.main_positive || self.feedback.main_negative || self.feedback.precharge {
+            log::warn!("Battery contactors found partly closed or without permit, opening them");
+            self.command = OPEN;
+            self.enter(ContactorState::Opening, now);
+        }
+    }
+
+    /// Advance the contactor sequence
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `feedback` - Latest contactor feedback and voltages
+    /// * `pack_current` - Pack current in A
+    pub fn update(&mut self, now: Instant, feedback: ContactorFeedback, pack_current: f32) {
+        let first = self.last_feedback.is_none();
+        self.feedback = feedback;
+        self.last_feedback = Some(now);
+        if first && self.state == ContactorState::Open {
+            self.adopt(now);
+            return;
+        }
+        let pack = self.feedback.pack_voltage.max(1.0);
+        let link_pct = self.feedback.link_voltage / pack * 100.0;
+
+        // Loss of BMS permit or feedback disagreeing with a settled command opens immediately
+        let settled = self.elapsed_ms(now) >= self.config.feedback_timeout_ms;
+        if !matches!(self.state, ContactorState::Open | ContactorState::Fault | ContactorState::Opening) {
+            if !self.feedback.permit {
+                self.trip(now, "BMS withdrew contactor permit".to_string());
+            } else if self.state == ContactorState::Closed && settled && !self.feedback_matches() {
+                self.trip(now, "Contactor feedback mismatch while closed".to_string());
+            }
+        }
+
+        match self.state {
+            ContactorState::Open => {
+                if self.feedback.main_positive || self.feedback.main_negative {
+                    self.trip(now, "Main contactor closed while commanded open (welded)".to_string());
+                } else if self.online_requested && self.feedback.permit {
+                    // Weld check: the link must be discharged before precharge starts
+                    if link_pct >= self.config.weld_voltage_threshold && !self.feedback.precharge {
+                        self.trip(now, format!("DC link at {:.0}% of pack voltage before precharge (welded main contactor)", link_pct));
+                    } else {
+                        self.command = PRECHARGE;
+                        self.enter(ContactorState::Precharging, now);
+                    }
+                }
+            }
+            ContactorState::Precharging => {
+                let elapsed = self.elapsed_ms(now);
+                if !self.online_requested {
+                    self.command = OPEN;
+                    self.enter(ContactorState::Opening, now);
+                } else if link_pct >= self.config.precharge_threshold && elapsed >= self.config.min_precharge_ms {
+                    self.command = CLOSING;
+                    self.enter(ContactorState::Closing, now);
+                } else if elapsed >= self.config.precharge_timeout_ms {
+                    self.trip(now, format!("Precharge timeout: link {:.0} V, pack {:.0} V", self.feedback.link_voltage, self.feedback.pack_voltage));
+                }
+            }
+            ContactorState::Closing => {
+                if self.feedback.main_positive && self.feedback.main_negative {
+                    self.command = CLOSED;
+                    self.enter(ContactorState::Closed, now);
+                } else if self.elapsed_ms(now) >= self.config.feedback_timeout_ms {
+                    self.trip(now, "Main positive contactor failed to close".to_string());
+                }
+            }
+            ContactorState::Closed => {
+                if !self.online_requested {
+                    self.enter(ContactorState::Unloading, now);
+                }
+            }
+            ContactorState::Unloading => {
+                if self.online_requested {
+                    self.enter(ContactorState::Closed, now);
+                } else if pack_current.abs() <= self.config.open_current || self.elapsed_ms(now) >= self.config.open_delay_ms {
+                    if pack_current.abs() > self.config.open_current {
+                        log::warn!("Opening battery contactors under {:.1} A", pack_current);
+                    }
+                    self.command = OPEN;
+                    self.enter(ContactorState::Opening, now);
+                }
+            }
+            ContactorState::Opening => {
+                if !self.feedback.main_positive && !self.feedback.main_negative && !self.feedback.precharge {
+                    self.enter(ContactorState::Open, now);
+                } else if self.elapsed_ms(now) >= self.config.feedback_timeout_ms {
+                    self.trip(now, "Contactor failed to open (welded)".to_string());
+                }
+            }
+            ContactorState::Fault => {
+                self.command = OPEN;
+            }
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> ContactorStatus {
+        ContactorStatus {
+            state: format!("{:?}", self.state),
+            online_requested: self.online_requested,
+            main_positive: self.feedback.main_positive,
+            main_negative: self.feedback.main_negative,
+            precharge: self.feedback.precharge,
+            pack_voltage: self.feedback.pack_voltage,
+            link_voltage: self.feedback.link_voltage,
+            fault: self.fault.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(command: ContactorCommand, link_voltage: f32) -> ContactorFeedback {
+        ContactorFeedback {
+            main_positive: command.main_positive,
+            main_negative: command.main_negative,
+            precharge: command.precharge,
+            pack_voltage: 800.0,
+            link_voltage,
+            permit: true,
+        }
+    }
+
+    fn at(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    /// Controller that has already seen the contactors open
+    fn open_controller(start: Instant) -> ContactorController {
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.update(start, feedback(OPEN, 0.0), 0.0);
+        contactor.request_online(true);
+        contactor
+    }
+
+    #[test]
+    fn precharges_then_closes_the_main_contactor() {
+        let start = Instant::now();
+        let mut contactor = open_controller(start);
+
+        contactor.update(at(start, 1), feedback(OPEN, 0.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Precharging);
+        assert_eq!(contactor.command(), PRECHARGE);
+
+        // Link charged but the minimum precharge time has not passed
+        contactor.update(at(start, 3), feedback(PRECHARGE, 790.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Precharging);
+
+        contactor.update(at(start, 6), feedback(PRECHARGE, 790.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Closing);
+        assert_eq!(contactor.command(), CLOSING);
+
+        contactor.update(at(start, 11), feedback(CLOSING, 800.0), 0.0);
+        assert!(contactor.is_online());
+        assert_eq!(contactor.command(), CLOSED);
+    }
+
+    #[test]
+    fn charged_link_before_precharge_trips_the_weld_check() {
+        let start = Instant::now();
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.update(start, feedback(OPEN, 0.0), 0.0);
+        contactor.request_online(true);
+
+        contactor.update(at(start, 5), feedback(OPEN, 600.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Fault);
+        assert_eq!(contactor.command(), OPEN);
+
+        contactor.reset_fault();
+        assert_eq!(contactor.state, ContactorState::Open);
+    }
+
+    #[test]
+    fn precharge_timeout_trips() {
+        let start = Instant::now();
+        let mut contactor = open_controller(start);
+        contactor.update(at(start, 1), feedback(OPEN, 0.0), 0.0);
+
+        contactor.update(at(start, 10), feedback(PRECHARGE, 200.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Precharging);
+        contactor.update(at(start, 16), feedback(PRECHARGE, 300.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Fault);
+        assert!(contactor.status().fault.unwrap().starts_with("Precharge timeout"));
+    }
+
+    #[test]
+    fn main_contactor_that_never_closes_trips() {
+        let start = Instant::now();
+        let mut contactor = open_controller(start);
+        contactor.update(at(start, 1), feedback(OPEN, 0.0), 0.0);
+        contactor.update(at(start, 6), feedback(PRECHARGE, 790.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Closing);
+
+        contactor.update(at(start, 16), feedback(PRECHARGE, 790.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Fault);
+    }
+
+    #[test]
+    fn unloads_before_opening_and_detects_a_weld() {
+        let start = Instant::now();
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.update(start, feedback(CLOSED, 800.0), 0.0);
+        assert!(contactor.is_online());
+
+        contactor.request_online(false);
+        contactor.update(at(start, 5), feedback(CLOSED, 800.0), 50.0);
+        assert_eq!(contactor.state, ContactorState::Unloading);
+        assert_eq!(contactor.command(), CLOSED);
+
+        // Current still flowing: hold until it falls
+        contactor.update(at(start, 10), feedback(CLOSED, 800.0), 50.0);
+        assert_eq!(contactor.state, ContactorState::Unloading);
+        contactor.update(at(start, 12), feedback(CLOSED, 800.0), 2.0);
+        assert_eq!(contactor.state, ContactorState::Opening);
+        assert_eq!(contactor.command(), OPEN);
+
+        // Main contactor stays closed after the open command
+        contactor.update(at(start, 22), feedback(CLOSED, 800.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Fault);
+    }
+
+    #[test]
+    fn unloading_opens_after_the_delay_even_under_current() {
+        let start = Instant::now();
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.update(start, feedback(CLOSED, 800.0), 0.0);
+        contactor.request_online(false);
+        contactor.update(at(start, 5), feedback(CLOSED, 800.0), 50.0);
+
+        contactor.update(at(start, 15), feedback(CLOSED, 800.0), 50.0);
+        assert_eq!(contactor.state, ContactorState::Opening);
+        contactor.update(at(start, 20), feedback(OPEN, 0.0), 0.0);
+        assert_eq!(contactor.state, ContactorState::Open);
+    }
+
+    #[test]
+    fn restart_adopts_closed_contactors_instead_of_tripping() {
+        let start = Instant::now();
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.request_online(true);
+
+        contactor.update(start, feedback(CLOSED, 800.0), 0.0);
+        assert!(contactor.is_online());
+        assert!(contactor.status().fault.is_none());
+
+        // Without permit the adopted contactors are opened, not faulted
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.update(start, ContactorFeedback { permit: false, ..feedback(CLOSED, 800.0) }, 0.0);
+        assert_eq!(contactor.state, ContactorState::Opening);
+        assert_eq!(contactor.command(), OPEN);
+    }
+
+    #[test]
+    fn stale_feedback_opens_the_contactors() {
+        let start = Instant::now();
+        let mut contactor = ContactorController::new(ContactorConfig::default());
+        contactor.update(start, feedback(CLOSED, 800.0), 0.0);
+
+        contactor.comm_lost(at(start, 10));
+        assert!(contactor.is_online());
+        contactor.comm_lost(at(start, 15));
+        assert_eq!(contactor.state, ContactorState::Fault);
+    }
+}
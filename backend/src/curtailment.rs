@@ -0,0 +1,180 @@
+// 光伏限功率控制
+// PV curtailment: caps PV output to load plus battery acceptance when the surplus has nowhere to go,
+// raises the cap as demand returns, releases to MPPT and accounts the curtailed energy
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::types::CurtailmentStatus;
+
+/// PV curtailment configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CurtailmentConfig {
+    /// Battery SOC at or above which the battery no longer accepts PV surplus (0-100%)
+    pub full_soc: f32,
+    /// Cap margin above the absorbable power so that rising demand shows up as PV output, in kW
+    pub headroom: f32,
+    /// Export the grid can take in kW (None: unlimited). Nothing is exported without a live grid
+    /// meter reading or while islanded. A contractual export limit is held by the export limiter instead.
+    pub export_allowance: Option<f32>,
+    /// Rate at which the cap is raised as demand returns, in kW/s
+    pub ramp_rate: f32,
+    /// PV output this far below the cap means the cap is not needed, in kW
+    pub release_margin: f32,
+    /// How long PV must stay below the cap before returning to MPPT, in seconds
+    pub release_delay: u64,
+}
+
+impl Default for CurtailmentConfig {
+    fn default() -> Self {
+        Self {
+            full_soc: 90.0,
+            headroom: 2.0,
+            export_allowance: None,
+            ramp_rate: 5.0,
+            release_margin: 3.0,
+            release_delay: 60,
+        }
+    }
+}
+
+/// Command for the EMS to send to the PV converters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurtailAction {
+    /// Keep the current setting
+    Hold,
+    /// Constant-voltage mode limited to this total power in kW
+    Cap(f32),
+    /// Return to MPPT
+    Release,
+}
+
+/// PV curtailment controller
+#[derive(Debug, Clone)]
+pub struct PvCurtailer {
+    config: CurtailmentConfig,
+    cap: Option<f32>,
+    last_sent: Option<f32>,
+    last_update: Option<Instant>,
+    below_cap_since: Option<Instant>,
+    lost_power: f32,
+    energy_today: f32,
+    energy_total: f32,
+    day: Option<NaiveDate>,
+    events: u32,
+}
+
+impl PvCurtailer {
+    /// Create a new controller with PV uncapped
+    ///
+    /// # Arguments
+    /// * `config` - Thresholds, margins and ramp rate
+    pub fn new(config: CurtailmentConfig) -> Self {
+        Self {
+            config,
+            cap: None,
+            last_sent: None,
+            last_update: None,
+            below_cap_since: None,
+            lost_power: 0.0,
+            energy_today: 0.0,
+            energy_total: 0.0,
+            day: None,
+            events: 0,
+        }
+    }
+
+    /// Battery SOC at or above which the battery no longer accepts PV surplus
+    pub fn full_soc(&self) -> f32 {
+        self.config.full_soc
+    }
+
+    /// Whether PV is currently capped
+    pub fn is_curtailing(&self) -> bool {
+        self.cap.is_some()
+    }
+
+    /// Current PV cap in kW
+    pub fn cap(&self) -> Option<f32> {
+        self.cap
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `local_now` - Local wall-clock time, for the daily energy counter
+    /// * `pv_power` - Measured PV power in kW
+    /// * `absorbable` - Load plus battery acceptance in kW
+    /// * `potential` - Estimated uncurtailed PV power in kW, if known
+    /// * `grid_connected` - Whether surplus can be exported to the grid
+    ///
+    /// # Returns
+    /// Command to send to the PV converters
+    pub fn update(&mut self, now: Instant, local_now: NaiveDateTime, pv_power: f32, absorbable: f32, potential: Option<f32>, grid_connected: bool) -> CurtailAction {
+        let dt = self.last_update.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+        if self.day != Some(local_now.date()) {
+            self.day = Some(local_now.date());
+            self.energy_today = 0.0;
+        }
+        let export = if grid_connected { self.config.export_allowance.unwrap_or(f32::INFINITY) } else { 0.0 };
+        let target = (absorbable + export + self.config.headroom).max(0.0);
+
+        let cap = match self.cap {
+            None => {
+                if pv_power <= target {
+                    return CurtailAction::Hold;
+                }
+                self.events += 1;
+                log::info!("PV curtailment engaged: {:.1} kW produced, {:.1} kW absorbable", pv_power, absorbable);
+                target
+            }
+            Some(cap) => {
+                // Follow falling demand immediately, rising demand at the ramp rate
+                let cap = if target > cap { (cap + self.config.ramp_rate * dt).min(target) } else { target };
+                if pv_power < cap - self.config.release_margin {
+                    let since = *self.below_cap_since.get_or_insert(now);
+                    if now.duration_since(since).as_secs() >= self.config.release_delay {
+                        log::info!("PV curtailment released at {:.1} kW", pv_power);
+                        self.cap = None;
+                        self.last_sent = None;
+                        self.below_cap_since = None;
+                        self.lost_power = 0.0;
+                        return CurtailAction::Release;
+                    }
+                } else {
+                    self.below_cap_since = None;
+                }
+                cap
+            }
+        };
+        self.cap = Some(cap);
+
+        self.lost_power = potential.map(|p| (p - pv_power).max(0.0)).unwrap_or(0.0);
+        let lost_kwh = self.lost_power * dt / 3600.0;
+        self.energy_today += lost_kwh;
+        self.energy_total += lost_kwh;
+
+        let changed = self.last_sent.map(|sent| (sent - cap).abs() >= 0.1).unwrap_or(true);
+        if changed {
+            self.last_sent = Some(cap);
+            CurtailAction::Cap(cap)
+        } else {
+            CurtailAction::Hold
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> CurtailmentStatus {
+        CurtailmentStatus {
+            active: self.cap.is_some(),
+            cap: self.cap,
+            lost_power: self.lost_power,
+            curtailed_energy_today: self.energy_today,
+            curtailed_energy_total: self.energy_total,
+            events: self.events,
+        }
+    }
+}
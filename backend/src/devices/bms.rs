@@ -2,9 +2,10 @@
 // Battery device abstraction using CAN communication for separation of concerns
 
 use crate::types::*;
-use crate::drivers::can::CanDriver;
+use crate::drivers::can::{CanDriver, CanError};
 use socketcan::{CanFrame, CanDataFrame, EmbeddedFrame, StandardId, Id};
 use std::io;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Default)]
 pub struct BatteryDevice {
@@ -81,21 +82,42 @@ impl BatteryDevice {
         }
     }
 
+    // Single-frame responses to a request
+    const RESPONSE_TIMEOUT: Duration = Duration::from_millis(200);
+    const RESPONSE_POLL: Duration = Duration::from_millis(10);
+
+    /// Wait for the response frame with the given ID
+    ///
+    /// Other BMS traffic shares the bus (including cell detail bursts requested on another
+    /// connection), so unrelated frames are skipped until the response arrives.
+    ///
+    /// # Arguments
+    /// * `driver` - Connected CAN driver the request was sent on
+    /// * `id` - CAN ID of the expected response
+    ///
+    /// # Returns
+    /// Result containing the response frame or IO error on timeout
+    fn recv_response(driver: &CanDriver, id: u16) -> Result<CanDataFrame, io::Error> {
+        let deadline = Instant::now() + Self::RESPONSE_TIMEOUT;
+        while Instant::now() < deadline {
+            match driver.try_recv_frame() {
+                Ok(Some(CanFrame::Data(frame))) if frame.id() == Id::Standard(StandardId::new(id).unwrap()) => return Ok(frame),
+                Ok(Some(_)) => continue,
+                Ok(None) | Err(CanError::Timeout) => std::thread::sleep(Self::RESPONSE_POLL),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::TimedOut, format!("No response frame 0x{:03X} from BMS", id)))
+    }
+
     pub fn read_status(&self) -> Result<BatteryStatus, io::Error> {
         if let Some(driver) = &self.can_driver {
             // Send read request
             let request_frame = CanFrame::Data(CanDataFrame::new(StandardId::new(0x100).unwrap(), &[0x01, 0, 0, 0, 0, 0, 0, 0]).unwrap());
             driver.send_frame(&request_frame).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
-            // Receive response (in real impl, might need timeout/loop)
-            let response_frame = driver.recv_frame().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            match response_frame {
-                CanFrame::Data(data_frame) if data_frame.id() == Id::Standard(StandardId::new(0x101).unwrap()) => {
-                    Ok(Self::unpack_battery_status(&data_frame.data()[..data_frame.dlc() as usize]))
-                }
-                CanFrame::Data(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "Unexpected CAN frame ID")),
-                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Expected data frame")),
-            }
+            let data_frame = Self::recv_response(driver, 0x101)?;
+            Ok(Self::unpack_battery_status(&data_frame.data()[..data_frame.dlc() as usize]))
         } else {
             Err(io::Error::new(io::ErrorKind::NotConnected, "CAN driver not initialized"))
         }
@@ -128,15 +150,8 @@ impl BatteryDevice {
             let request_frame = CanFrame::Data(CanDataFrame::new(StandardId::new(0x103).unwrap(), &[0x01]).unwrap());
             driver.send_frame(&request_frame).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
-            // Receive response
-            let response_frame = driver.recv_frame().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            match response_frame {
-                CanFrame::Data(data_frame) if data_frame.id() == Id::Standard(StandardId::new(0x104).unwrap()) => {
-                    Ok(Self::unpack_battery_cell_status(&data_frame.data()[..data_frame.dlc() as usize]))
-                }
-                CanFrame::Data(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "Unexpected CAN frame ID for cell status")),
-                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Expected data frame for cell status")),
-            }
+            let data_frame = Self::recv_response(driver, 0x104)?;
+            Ok(Self::unpack_battery_cell_status(&data_frame.data()[..data_frame.dlc() as usize]))
         } else {
             Err(io::Error::new(io::ErrorKind::NotConnected, "CAN driver not initialized"))
         }
@@ -191,4 +206,159 @@ impl BatteryDevice {
             health_percentage: health,
         }
     }
+
+    // Multi-frame per-cell data (request 0x105, responses 0x106-0x108)
+    const CELL_DETAIL_REQUEST_ID: u16 = 0x105;
+    const CELL_VOLTAGE_FRAME_ID: u16 = 0x106;
+    const CELL_TEMP_FRAME_ID: u16 = 0x107;
+    const BALANCING_FRAME_ID: u16 = 0x108;
+    const CELL_DETAIL_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Read every cell's voltage and temperature and each module's balancing state
+    ///
+    /// The BMS answers one request with a burst of frames. Voltage (0x106) and temperature (0x107)
+    /// frames carry start cell index (u16) followed by three u16 values; balancing frames (0x108)
+    /// carry module index (u16) followed by a u32 bitmask of balancing cells in that module.
+    /// This blocks for up to two seconds, so it is called from `CellDetailReader`'s thread on a
+    /// dedicated connection rather than from the control cycle.
+    ///
+    /// # Arguments
+    /// * `cell_count` - Number of cells in the pack
+    /// * `cells_per_module` - Number of cells per module
+    ///
+    /// # Returns
+    /// Result containing BatteryCellDetails or IO error
+    pub fn read_cell_details(&self, cell_count: u16, cells_per_module: u16) -> Result<BatteryCellDetails, io::Error> {
+        let driver = self.can_driver.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "CAN driver not initialized"))?;
+        let cells_per_module = cells_per_module.max(1);
+        let module_count = cell_count.div_ceil(cells_per_module);
+
+        let request_frame = CanFrame::Data(CanDataFrame::new(StandardId::new(Self::CELL_DETAIL_REQUEST_ID).unwrap(), &[0x01]).unwrap());
+        driver.send_frame(&request_frame).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let mut voltages: Vec<Option<f32>> = vec![None; cell_count as usize];
+        let mut temperatures: Vec<Option<f32>> = vec![None; cell_count as usize];
+        let mut balancing: Vec<Option<u32>> = vec![None; module_count as usize];
+        let deadline = Instant::now() + Self::CELL_DETAIL_TIMEOUT;
+
+        while Instant::now() < deadline
+            && (voltages.iter().any(Option::is_none) || temperatures.iter().any(Option::is_none) || balancing.iter().any(Option::is_none))
+        {
+            // Poll so the deadline, not the driver's receive timeout, ends the burst
+            let frame = match driver.try_recv_frame() {
+                Ok(Some(CanFrame::Data(frame))) => frame,
+                Ok(Some(_)) => continue,
+                Ok(None) | Err(CanError::Timeout) => {
+                    std::thread::sleep(Self::RESPONSE_POLL);
+                    continue;
+                }
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+            };
+            let data = &frame.data()[..frame.dlc() as usize];
+            if data.len() < 2 {
+                continue;
+            }
+            let index = u16::from_be_bytes([data[0], data[1]]) as usize;
+            let values = data[2..].chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+            match frame.id() {
+                Id::Standard(id) if id.as_raw() == Self::CELL_VOLTAGE_FRAME_ID => {
+                    for (offset, raw) in values.enumerate() {
+                        if let Some(slot) = voltages.get_mut(index + offset) {
+                            *slot = Some(raw as f32 / Self::SCALE_CELL_VOLTAGE);
+                        }
+                    }
+                }
+                Id::Standard(id) if id.as_raw() == Self::CELL_TEMP_FRAME_ID => {
+                    for (offset, raw) in values.enumerate() {
+                        if let Some(slot) = temperatures.get_mut(index + offset) {
+                            *slot = Some(raw as f32 / Self::SCALE_CELL_TEMP - 50.0);
+                        }
+                    }
+                }
+                Id::Standard(id) if id.as_raw() == Self::BALANCING_FRAME_ID && data.len() >= 6 => {
+                    if let Some(slot) = balancing.get_mut(index) {
+                        *slot = Some(u32::from_be_bytes([data[2], data[3], data[4], data[5]]));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let missing = voltages.iter().filter(|v| v.is_none()).count() + temperatures.iter().filter(|t| t.is_none()).count();
+        if missing == voltages.len() + temperatures.len() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "No cell detail frames received"));
+        }
+
+        let cells = (0..cell_count as usize)
+            .map(|i| {
+                let module = i / cells_per_module as usize;
+                let position = i % cells_per_module as usize;
+                CellData {
+                    index: i as u16,
+                    module: module as u16,
+                    voltage: voltages[i],
+                    temperature: temperatures[i],
+                    balancing: balancing[module].map(|mask| position < 32 && mask & (1 << position) != 0).unwrap_or(false),
+                }
+            })
+            .collect();
+
+        Ok(BatteryCellDetails {
+            cells,
+            module_count,
+            cells_per_module,
+            missing_values: missing as u32,
+        })
+    }
+
+    // Contactor control (command 0x110, status request 0x111, status response 0x112)
+    const CONTACTOR_COMMAND_ID: u16 = 0x110;
+    const CONTACTOR_REQUEST_ID: u16 = 0x111;
+    const CONTACTOR_STATUS_ID: u16 = 0x112;
+
+    /// Command the main and precharge contactors
+    ///
+    /// # Arguments
+    /// * `command` - Requested state of each contactor (true = closed)
+    ///
+    /// # Returns
+    /// Result indicating success or IO error
+    pub fn set_contactors(&self, command: ContactorCommand) -> Result<(), io::Error> {
+        if let Some(driver) = &self.can_driver {
+            let data = [command.main_positive as u8, command.main_negative as u8, command.precharge as u8];
+            let frame = CanFrame::Data(CanDataFrame::new(StandardId::new(Self::CONTACTOR_COMMAND_ID).unwrap(), &data).unwrap());
+            driver.send_frame(&frame).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotConnected, "CAN driver not initialized"))
+        }
+    }
+
+    /// Read contactor auxiliary feedback and the pack/link voltages
+    /// Format: feedback bits (u8: main+, main-, precharge), pack voltage (u16), link voltage (u16), permit (u8)
+    ///
+    /// # Returns
+    /// Result containing ContactorFeedback or IO error
+    pub fn read_contactor_status(&self) -> Result<ContactorFeedback, io::Error> {
+        if let Some(driver) = &self.can_driver {
+            let request_frame = CanFrame::Data(CanDataFrame::new(StandardId::new(Self::CONTACTOR_REQUEST_ID).unwrap(), &[0x01]).unwrap());
+            driver.send_frame(&request_frame).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+            let data_frame = Self::recv_response(driver, Self::CONTACTOR_STATUS_ID)?;
+            let data = &data_frame.data()[..data_frame.dlc() as usize];
+            if data.len() < 6 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Short contactor status frame"));
+            }
+            Ok(ContactorFeedback {
+                main_positive: data[0] & 0x01 != 0,
+                main_negative: data[0] & 0x02 != 0,
+                precharge: data[0] & 0x04 != 0,
+                pack_voltage: u16::from_be_bytes([data[1], data[2]]) as f32 / Self::SCALE_VOLTAGE,
+                link_voltage: u16::from_be_bytes([data[3], data[4]]) as f32 / Self::SCALE_VOLTAGE,
+                permit: data[5] != 0,
+            })
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotConnected, "CAN driver not initialized"))
+        }
+    }
 }
@@ -0,0 +1,81 @@
+// 并网断路器设备
+// Grid interconnection breaker abstraction using Modbus communication (close/open coil, position feedback input)
+
+use crate::drivers::modbus::{ModbusClient, ModbusError};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct GridBreakerDevice {
+    /// Device identifier
+    pub id: String,
+    /// Modbus client for communication
+    modbus_client: Option<ModbusClient>,
+    /// Coil commanding the breaker (true = close)
+    command_coil: u16,
+    /// Discrete input reporting the breaker position (true = closed)
+    feedback_input: u16,
+    // Cached status fields for performance
+    pub closed: bool,
+}
+
+impl GridBreakerDevice {
+    /// Create a new grid breaker with Modbus communication
+    ///
+    /// # Arguments
+    /// * `id` - Unique device identifier
+    /// * `host` - Modbus server (or I/O module) host
+    /// * `port` - Modbus server port
+    /// * `unit_id` - Modbus unit identifier of the I/O module
+    /// * `command_coil` - Coil address of the close/open command
+    /// * `feedback_input` - Discrete input address of the position feedback
+    ///
+    /// # Returns
+    /// Result containing the device or ModbusError
+    pub fn new(id: String, host: &str, port: u16, unit_id: u8, command_coil: u16, feedback_input: u16) -> Result<Self, ModbusError> {
+        let mut modbus_client = ModbusClient::with_config(host, port, Duration::from_secs(2), unit_id);
+        modbus_client.connect()?;
+        Ok(Self {
+            id,
+            modbus_client: Some(modbus_client),
+            command_coil,
+            feedback_input,
+            closed: false,
+        })
+    }
+
+    /// Read the breaker position via Modbus
+    ///
+    /// # Returns
+    /// Result containing whether the breaker is closed or ModbusError
+    pub fn read_status(&mut self) -> Result<bool, ModbusError> {
+        if let Some(client) = &mut self.modbus_client {
+            let inputs = client.read_discrete_inputs(self.feedback_input, 1)?;
+            let closed = inputs.first().copied()
+                .ok_or_else(|| ModbusError::InvalidData("Empty breaker feedback".to_string()))?;
+            self.closed = closed;
+            Ok(closed)
+        } else {
+            Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
+        }
+    }
+
+    /// Command the breaker closed or open
+    ///
+    /// # Arguments
+    /// * `closed` - True to close, false to open
+    ///
+    /// # Returns
+    /// Result indicating success or ModbusError
+    pub fn set_closed(&mut self, closed: bool) -> Result<(), ModbusError> {
+        if let Some(client) = &mut self.modbus_client {
+            client.write_single_coil(self.command_coil, closed)
+        } else {
+            Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
+        }
+    }
+
+    /// Check if device is connected
+    pub fn is_connected(&self) -> bool {
+        self.modbus_client.as_ref().map(|c| c.is_connected()).unwrap_or(false)
+    }
+}
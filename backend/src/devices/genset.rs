@@ -56,6 +56,16 @@ impl GensetDevice {
         })
     }
 
+    /// Create a GensetDevice without a connection, for a unit that was unreachable at startup
+    ///
+    /// Every read and command on it fails, so controllers treat the unit as unavailable.
+    ///
+    /// # Arguments
+    /// * `id` - Device identifier
+    pub fn unconnected(id: String) -> Self {
+        Self { id, ..Default::default() }
+    }
+
     /// Read the current status from the genset via Modbus
     ///
     /// # Returns
@@ -0,0 +1,147 @@
+// 电能表设备
+// Three-phase energy meter abstraction using Modbus communication (SDM630-compatible register map)
+
+use crate::types::*;
+use crate::drivers::modbus::{ModbusClient, ModbusError};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct EnergyMeterDevice {
+    /// Device identifier
+    pub id: String,
+    /// Modbus client for communication
+    modbus_client: Option<ModbusClient>,
+    /// Invert the power sign for meters whose CTs face the load side
+    pub reversed: bool,
+    // Cached status fields for performance
+    pub phases: Vec<PhaseMeasurement>,
+    pub active_power: f32,     // Total active power in kW (positive: importing)
+    pub reactive_power: f32,   // Total reactive power in kvar
+    pub power_factor: f32,     // Total power factor
+    pub frequency: f32,        // Grid frequency in Hz
+    pub import_energy: f64,    // Imported energy counter in kWh
+    pub export_energy: f64,    // Exported energy counter in kWh
+}
+
+impl EnergyMeterDevice {
+    // Input register addresses (IEEE 754 float, two registers each, high word first)
+    const REG_BLOCK_START: u16 = 0x0000;
+    const REG_BLOCK_LEN: u16 = 0x004C;
+    const REG_VOLTAGE: usize = 0x0000;        // Phase 1-3 line-to-neutral voltage in V
+    const REG_CURRENT: usize = 0x0006;        // Phase 1-3 current in A
+    const REG_ACTIVE_POWER: usize = 0x000C;   // Phase 1-3 active power in W
+    const REG_REACTIVE_POWER: usize = 0x0018; // Phase 1-3 reactive power in var
+    const REG_POWER_FACTOR: usize = 0x001E;   // Phase 1-3 power factor
+    const REG_TOTAL_POWER: usize = 0x0034;    // Total active power in W
+    const REG_TOTAL_VAR: usize = 0x003C;      // Total reactive power in var
+    const REG_TOTAL_PF: usize = 0x003E;       // Total power factor
+    const REG_FREQUENCY: usize = 0x0046;      // Frequency in Hz
+    const REG_IMPORT_KWH: usize = 0x0048;     // Total import energy in kWh
+    const REG_EXPORT_KWH: usize = 0x004A;     // Total export energy in kWh
+
+    /// Create a new energy meter with Modbus communication
+    ///
+    /// # Arguments
+    /// * `id` - Unique device identifier
+    /// * `host` - Modbus server (or RTU gateway) host
+    /// * `port` - Modbus server port
+    /// * `unit_id` - Modbus unit identifier of the meter
+    ///
+    /// # Returns
+    /// Result containing the device or ModbusError
+    pub fn new(id: String, host: &str, port: u16, unit_id: u8) -> Result<Self, ModbusError> {
+        let mut modbus_client = ModbusClient::with_config(host, port, Duration::from_secs(5), unit_id);
+        modbus_client.connect()?;
+        Ok(Self {
+            id,
+            modbus_client: Some(modbus_client),
+            ..Default::default()
+        })
+    }
+
+    /// Decode a float from two registers, high word first
+    fn float_at(registers: &[u16], address: usize) -> f32 {
+        match (registers.get(address), registers.get(address + 1)) {
+            (Some(&hi), Some(&lo)) => f32::from_bits(((hi as u32) << 16) | lo as u32),
+            _ => 0.0,
+        }
+    }
+
+    /// Read current measurements from the meter via Modbus
+    ///
+    /// # Returns
+    /// Result containing MeterStatus or ModbusError
+    pub fn read_status(&mut self) -> Result<MeterStatus, ModbusError> {
+        if let Some(client) = &mut self.modbus_client {
+            let registers = client.read_input_registers(Self::REG_BLOCK_START, Self::REG_BLOCK_LEN)?;
+            if registers.len() < Self::REG_BLOCK_LEN as usize {
+                return Err(ModbusError::InvalidData(format!("Expected {} registers, got {}", Self::REG_BLOCK_LEN, registers.len())));
+            }
+            let sign = if self.reversed { -1.0 } else { 1.0 };
+
+            let phases: Vec<PhaseMeasurement> = (0..3)
+                .map(|p| PhaseMeasurement {
+                    voltage: Self::float_at(&registers, Self::REG_VOLTAGE + p * 2),
+                    current: Self::float_at(&registers, Self::REG_CURRENT + p * 2),
+                    active_power: sign * Self::float_at(&registers, Self::REG_ACTIVE_POWER + p * 2) / 1000.0, // Convert W to kW
+                    reactive_power: sign * Self::float_at(&registers, Self::REG_REACTIVE_POWER + p * 2) / 1000.0, // Convert var to kvar
+                    power_factor: Self::float_at(&registers, Self::REG_POWER_FACTOR + p * 2),
+                })
+                .collect();
+            let active_power = sign * Self::float_at(&registers, Self::REG_TOTAL_POWER) / 1000.0;
+            let reactive_power = sign * Self::float_at(&registers, Self::REG_TOTAL_VAR) / 1000.0;
+            let power_factor = Self::float_at(&registers, Self::REG_TOTAL_PF);
+            let frequency = Self::float_at(&registers, Self::REG_FREQUENCY);
+            let (import_reg, export_reg) = if self.reversed {
+                (Self::REG_EXPORT_KWH, Self::REG_IMPORT_KWH)
+            } else {
+                (Self::REG_IMPORT_KWH, Self::REG_EXPORT_KWH)
+            };
+            let import_energy = Self::float_at(&registers, import_reg) as f64;
+            let export_energy = Self::float_at(&registers, export_reg) as f64;
+
+            if !active_power.is_finite() || !frequency.is_finite() {
+                return Err(ModbusError::InvalidData("Non-finite meter reading".to_string()));
+            }
+
+            // Update cached fields
+            self.phases = phases.clone();
+            self.active_power = active_power;
+            self.reactive_power = reactive_power;
+            self.power_factor = power_factor;
+            self.frequency = frequency;
+            self.import_energy = import_energy;
+            self.export_energy = export_energy;
+
+            Ok(MeterStatus {
+                phases,
+                active_power,
+                reactive_power,
+                power_factor,
+                frequency,
+                import_energy,
+                export_energy,
+            })
+        } else {
+            Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
+        }
+    }
+
+    /// Get cached status without reading from device
+    pub fn get_cached_status(&self) -> MeterStatus {
+        MeterStatus {
+            phases: self.phases.clone(),
+            active_power: self.active_power,
+            reactive_power: self.reactive_power,
+            power_factor: self.power_factor,
+            frequency: self.frequency,
+            import_energy: self.import_energy,
+            export_energy: self.export_energy,
+        }
+    }
+
+    /// Check if device is connected
+    pub fn is_connected(&self) -> bool {
+        self.modbus_client.as_ref().map(|c| c.is_connected()).unwrap_or(false)
+    }
+}
@@ -1,14 +1,18 @@
 //! Device module - Contains all device abstractions.
 
 pub mod bms;
+pub mod breaker;
 pub mod charger;
 pub mod genset;
+pub mod meter;
 pub mod pcs;
 pub mod pv_dcdc;
 
 // Re-export main types for external use.
 pub use bms::BatteryDevice;
+pub use breaker::GridBreakerDevice;
 pub use charger::ChargerDevice;
 pub use genset::GensetDevice;
+pub use meter::EnergyMeterDevice;
 pub use pcs::PcsDevice;
 pub use pv_dcdc::PvDcdcDevice as PvDevice;
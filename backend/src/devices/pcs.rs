@@ -37,13 +37,29 @@ pub enum PcsMode {
     Fault,        // Fault mode
 }
 
+/// Reactive power control modes of the PCS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u16)]
+pub enum PcsReactiveMode {
+    #[default]
+    Disabled = 0,          // Unity power factor, no reactive setpoint
+    FixedPowerFactor = 1,  // Hold the power factor setpoint
+    FixedReactivePower = 2, // Hold the reactive power setpoint
+}
+
 impl PcsDevice {
     // Scaling constants for Modbus register values
     const SCALE_POWER: f32 = 10.0;     // Power in 0.1 kW units
     const SCALE_VOLTAGE: f32 = 10.0;   // Voltage in 0.1V units
     const SCALE_CURRENT: f32 = 10.0;   // Current in 0.1A units
-    const SCALE_FREQ: f32 = 10.0;      // Frequency in 0.1 Hz units
-    const SCALE_EFFICIENCY: f32 = 100.0; // Efficiency as 0-100%
+    const SCALE_FREQ: f32 = 100.0;     // Frequency in 0.01 Hz units
+    const SCALE_EFFICIENCY: f32 = 100.0; // Efficiency in 0.01% units
+    const SCALE_PF: f32 = 1000.0;      // Power factor in 0.001 units
+    const STATUS_REGISTER: u16 = 1;    // First status register (mode)
+    const STATUS_COUNT: u16 = 10;      // mode, P, Q, Vac, Iac, Vdc, Idc, f, efficiency, fault code
+    const REACTIVE_MODE_REGISTER: u16 = 20; // Reactive power control mode
+    const REACTIVE_POWER_REGISTER: u16 = 21; // Reactive power setpoint
+    const POWER_FACTOR_REGISTER: u16 = 22;   // Power factor setpoint
 
     /// Create a new PCS device with Modbus communication
     ///
@@ -64,14 +80,27 @@ impl PcsDevice {
         })
     }
 
+    /// Create a PCS device without a connection, for a unit that was unreachable at startup
+    ///
+    /// Every read and command on it fails, so controllers treat the unit as unavailable.
+    ///
+    /// # Arguments
+    /// * `id` - Unique device identifier
+    pub fn unconnected(id: String) -> Self {
+        Self { id, ..Default::default() }
+    }
+
     /// Read current status from the PCS device via Modbus
     ///
     /// # Returns
     /// Result containing PcsStatus or ModbusError
     pub fn read_status(&mut self) -> Result<PcsStatus, ModbusError> {
         if let Some(client) = &mut self.modbus_client {
-            // Read registers: assuming mode(1), power(2)
-            let registers = client.read_holding_registers(1, 2)?;
+            // Read registers: mode(1), active power(2), reactive power(3), AC voltage(4), AC current(5),
+            // DC voltage(6), DC current(7), frequency(8), efficiency(9), fault code(10)
+            let registers = client.read_holding_registers(Self::STATUS_REGISTER, Self::STATUS_COUNT)?;
+            let unsigned = |i: usize, scale: f32| registers.get(i).map(|v| *v as f32 / scale).unwrap_or(0.0);
+            let signed = |i: usize, scale: f32| registers.get(i).map(|v| *v as i16 as f32 / scale).unwrap_or(0.0);
 
             let mode_index = registers.get(0).copied().unwrap_or(0) as usize;
             let mode = match mode_index {
@@ -84,17 +113,26 @@ impl PcsDevice {
                 _ => "Unknown",
             }.to_string();
 
-            let power = registers.get(1).map(|v| *v as f32 / Self::SCALE_POWER).unwrap_or(0.0);
+            let power = signed(1, Self::SCALE_POWER);
+            let reactive_power = signed(2, Self::SCALE_POWER); // 0.1 kvar units
+            let voltage_ac = unsigned(3, Self::SCALE_VOLTAGE);
+            let current_ac = unsigned(4, Self::SCALE_CURRENT);
+            let voltage_dc = unsigned(5, Self::SCALE_VOLTAGE);
+            let current_dc = signed(6, Self::SCALE_CURRENT);
+            let frequency = unsigned(7, Self::SCALE_FREQ);
+            let efficiency = unsigned(8, Self::SCALE_EFFICIENCY);
+            let fault_code = registers.get(9).copied().unwrap_or(0);
+            let fault = mode == "Fault" || fault_code != 0;
 
             // Update cached fields
             self.power_active = power;
-            self.power_reactive = 0.0; // TODO: Add to PcsStatus
-            self.voltage_ac = 0.0; // TODO: Add to PcsStatus
-            self.current_ac = 0.0; // TODO: Add to PcsStatus
-            self.voltage_dc = 0.0; // TODO: Add to PcsStatus
-            self.current_dc = 0.0; // TODO: Add to PcsStatus
-            self.frequency = 0.0; // TODO: Add to PcsStatus
-            self.efficiency = 0.0; // TODO: Add to PcsStatus
+            self.power_reactive = reactive_power;
+            self.voltage_ac = voltage_ac;
+            self.current_ac = current_ac;
+            self.voltage_dc = voltage_dc;
+            self.current_dc = current_dc;
+            self.frequency = frequency;
+            self.efficiency = efficiency;
             self.mode = match mode.as_str() {
                 "Standby" => PcsMode::Standby,
                 "Charging" => PcsMode::Charging,
@@ -104,10 +142,10 @@ impl PcsDevice {
                 "Fault" => PcsMode::Fault,
                 _ => PcsMode::Standby,
             };
-            self.fault = mode == "Fault"; // TODO: Add to PcsStatus
-            self.fault_codes = vec![]; // TODO: Add to PcsStatus
+            self.fault = fault;
+            self.fault_codes = if fault_code != 0 { vec![fault_code] } else { vec![] };
 
-            Ok(PcsStatus { mode, power })
+            Ok(self.get_cached_status())
         } else {
             Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
         }
@@ -118,6 +156,15 @@ impl PcsDevice {
         PcsStatus {
             mode: format!("{:?}", self.mode), // TODO: Use proper enum in PcsStatus
             power: self.power_active,
+            reactive_power: self.power_reactive,
+            voltage_ac: self.voltage_ac,
+            current_ac: self.current_ac,
+            voltage_dc: self.voltage_dc,
+            current_dc: self.current_dc,
+            frequency: self.frequency,
+            efficiency: self.efficiency,
+            fault: self.fault,
+            fault_codes: self.fault_codes.clone(),
         }
     }
 
@@ -188,4 +235,73 @@ impl PcsDevice {
         }
     }
 
+    /// Set reactive power control mode
+    ///
+    /// # Arguments
+    /// * `mode` - Reactive power control mode
+    ///
+    /// # Returns
+    /// Result indicating success or ModbusError
+    pub fn set_reactive_mode(&mut self, mode: PcsReactiveMode) -> Result<(), ModbusError> {
+        if let Some(client) = &mut self.modbus_client {
+            client.write_single_register(Self::REACTIVE_MODE_REGISTER, mode as u16)?;
+            Ok(())
+        } else {
+            Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
+        }
+    }
+
+    /// Set reactive power setpoint, used in fixed reactive power mode
+    ///
+    /// # Arguments
+    /// * `reactive_power` - Reactive power in kvar (positive: injecting, over-excited)
+    ///
+    /// # Returns
+    /// Result indicating success or ModbusError
+    pub fn set_reactive_power_setpoint(&mut self, reactive_power: f32) -> Result<(), ModbusError> {
+        if let Some(client) = &mut self.modbus_client {
+            let clamped = reactive_power.clamp(-100.0, 100.0);
+            let register_value = ((clamped * Self::SCALE_POWER) as i16) as u16;
+            client.write_single_register(Self::REACTIVE_POWER_REGISTER, register_value)?;
+            Ok(())
+        } else {
+            Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
+        }
+    }
+
+    /// Set reactive control mode together with its setpoint
+    ///
+    /// # Arguments
+    /// * `mode` - Reactive power control mode
+    /// * `value` - Power factor for FixedPowerFactor, reactive power in kvar for FixedReactivePower
+    ///
+    /// # Returns
+    /// Result indicating success or ModbusError
+    pub fn set_reactive_control(&mut self, mode: PcsReactiveMode, value: f32) -> Result<(), ModbusError> {
+        match mode {
+            PcsReactiveMode::FixedPowerFactor => self.set_power_factor_setpoint(value)?,
+            PcsReactiveMode::FixedReactivePower => self.set_reactive_power_setpoint(value)?,
+            PcsReactiveMode::Disabled => self.set_reactive_power_setpoint(0.0)?,
+        }
+        self.set_reactive_mode(mode)
+    }
+
+    /// Set power factor setpoint, used in fixed power factor mode
+    ///
+    /// # Arguments
+    /// * `power_factor` - Power factor magnitude 0-1 (positive: over-excited, negative: under-excited)
+    ///
+    /// # Returns
+    /// Result indicating success or ModbusError
+    pub fn set_power_factor_setpoint(&mut self, power_factor: f32) -> Result<(), ModbusError> {
+        if let Some(client) = &mut self.modbus_client {
+            let clamped = power_factor.clamp(-1.0, 1.0);
+            let register_value = ((clamped * Self::SCALE_PF) as i16) as u16;
+            client.write_single_register(Self::POWER_FACTOR_REGISTER, register_value)?;
+            Ok(())
+        } else {
+            Err(ModbusError::ConnectionFailed("Modbus client not initialized".to_string()))
+        }
+    }
+
 }
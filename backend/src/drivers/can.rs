@@ -200,7 +200,7 @@ impl CanDriver {
 
         match socket.read_frame_timeout(Duration::from_millis(0)) {
             Ok(frame) => Ok(Some(frame)),
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
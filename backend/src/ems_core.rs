@@ -1,9 +1,34 @@
 // 核心 EMS 控制逻辑
 // Energy Management System controller implementing power balancing between PV, battery, generator, and chargers
 
+use crate::battery_health::{BatteryHealthConfig, BatteryHealthTracker};
+use crate::battery_limits::{BatteryLimitConfig, BatteryPowerLimiter};
+use crate::battery_racks::BatteryRackGroup;
+use crate::cell_analytics::{CellAnalytics, CellAnalyticsConfig, CellDetailReader};
+use crate::contactor::{ContactorConfig, ContactorController};
+use crate::curtailment::{CurtailAction, CurtailmentConfig, PvCurtailer};
 use crate::devices::*;
+use crate::fuel::{FuelAlarm, FuelConfig, FuelManager};
+use crate::genset_fleet::GensetFleet;
+use crate::genset_min_load::{GensetMinLoad, MinLoadConfig, MinLoadInputs};
+use crate::genset_control::{GensetAction, GensetControlConfig, GensetController, GensetDemand};
+use crate::grid_limit::{ExportLimitConfig, ExportLimitInputs, ExportLimiter, ImportLimitConfig, ImportLimitInputs, ImportLimiter};
+use crate::grid_support::{GridSupport, GridSupportConfig};
+use crate::islanding::{IslandController, IslandInputs, IslandingConfig};
+use crate::load_forecast::{LoadForecastConfig, LoadForecaster};
+use crate::load_shedding::{LoadShedder, LoadSheddingConfig};
+use crate::maintenance::{MaintenanceConfig, MaintenancePlanner};
+use crate::mpc::{MpcConfig, MpcInputs, MpcPlanner, STEP_MINUTES};
+use crate::pcs_group::PcsGroup;
+use crate::pv_forecast::{PvForecastConfig, PvForecaster};
+use crate::reactive_power::{ReactiveCommand, ReactivePowerConfig, ReactivePowerController};
+use crate::soc_estimator::{SocEstimator, SocEstimatorConfig};
+use crate::sync_check::{SyncCheckConfig, SyncChecker, SyncInputs};
+use crate::tariff::{PriceBand, TariffTracker, TouDispatchConfig};
 use crate::types::*;
+use chrono::{Duration, Local, NaiveDateTime, Timelike, Utc};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use log;
 
 /// Configuration for EMS operation
@@ -26,6 +51,8 @@ pub enum EmsMode {
     PeakShaving, // Reducing peak demand
     Emergency,   // Emergency generator-only operation
     Fault,       // System fault condition
+    TimeOfUse,   // Tariff-driven battery arbitrage
+    Optimized,   // Model-predictive dispatch plan
 }
 
 /// Energy Management System Controller
@@ -35,16 +62,100 @@ pub struct EmsController {
 pv_devices: Vec<Arc<Mutex<PvDevice>>>,
 /// Battery management system
 battery_device: Option<Arc<Mutex<BatteryDevice>>>,
+/// Parallel battery racks (None: single battery and PCS)
+battery_racks: Option<BatteryRackGroup>,
 /// Generator set
 genset_device: Option<Arc<Mutex<GensetDevice>>>,
+/// Genset fleet with lead/lag control (None: single genset)
+genset_fleet: Option<GensetFleet>,
 /// Power conversion system
 pcs_device: Option<Arc<Mutex<PcsDevice>>>,
+/// Parallel PCS units on the battery (None: single PCS)
+pcs_group: Option<PcsGroup>,
+/// Grid connection energy meter
+grid_meter: Option<Arc<Mutex<EnergyMeterDevice>>>,
+/// Latest valid grid meter reading
+grid_measurement: Option<MeterStatus>,
+/// PCS AC bus voltage in V and frequency in Hz from this cycle's read (None if the read failed)
+bus_measurement: Option<(f32, f32)>,
+/// Primary genset status from this cycle's read (None if the read failed)
+genset_reading: Option<GensetStatus>,
+/// Time without a genset reading, charged to fuel tracking at the next successful read, in hours
+genset_untracked_hours: f32,
+/// Breaker between the site and the grid, opened for island operation
+grid_breaker: Option<Arc<Mutex<GridBreakerDevice>>>,
 /// Charging stations (vector of charger devices)
 charger_devices: Vec<Arc<Mutex<ChargerDevice>>>,
 /// EMS configuration
 config: EmsConfig,
 /// Current operational mode
 current_mode: EmsMode,
+/// Time-of-use dispatch configuration
+tou_config: Option<TouDispatchConfig>,
+/// Tariff savings and demand accounting
+tariff_tracker: TariffTracker,
+/// Start time of the previous control cycle
+last_cycle: Option<Instant>,
+/// PV production forecaster
+pv_forecaster: PvForecaster,
+/// Charging load forecaster
+load_forecaster: LoadForecaster,
+/// Model-predictive dispatch planner
+mpc_planner: Option<MpcPlanner>,
+/// Genset start/stop controller
+genset_controller: GensetController,
+/// Deficit left after PV and battery in the last dispatch, in kW
+unmet_deficit: f32,
+/// Genset output measured this cycle in kW
+generator_output: f32,
+/// Explicit genset run request from the dispatch plan
+genset_request: Option<bool>,
+/// Genset fuel consumption, refuel/theft detection and alarms
+fuel_manager: FuelManager,
+/// Genset service schedule and exercise runs
+maintenance_planner: MaintenancePlanner,
+/// Coulomb-counting SOC estimator
+soc_estimator: SocEstimator,
+/// Battery SOH and degradation tracker
+battery_health: BatteryHealthTracker,
+/// Time of the last BMS cell status poll
+last_cell_poll: Option<Instant>,
+/// Battery charge/discharge power limiter
+battery_limiter: BatteryPowerLimiter,
+/// Per-cell imbalance analytics
+cell_analytics: CellAnalytics,
+/// Time of the last per-cell data read
+last_cell_detail_poll: Option<Instant>,
+/// Background per-cell reader on its own BMS connection
+cell_detail_reader: Option<CellDetailReader>,
+/// PV curtailment when the surplus cannot be absorbed
+pv_curtailer: PvCurtailer,
+/// Battery contactor sequencing (None: battery treated as always online)
+contactor: Option<ContactorController>,
+/// On-grid/off-grid transition and black start (None: always grid-tied)
+islanding: Option<IslandController>,
+/// Genset-PCS sync check before paralleling (None: genset loaded without checking)
+sync_check: Option<SyncChecker>,
+/// Genset minimum-load protection (None: genset may run at any load)
+genset_min_load: Option<GensetMinLoad>,
+/// Priority-based charger shedding (None: chargers are cut equally)
+load_shedder: Option<LoadShedder>,
+/// PCS reactive power control
+reactive_controller: ReactivePowerController,
+/// Closed-loop site import limit (None: no limit)
+import_limiter: Option<ImportLimiter>,
+/// Closed-loop site export limit (None: export unrestricted)
+export_limiter: Option<ExportLimiter>,
+/// Battery power setpoint last sent in kW (positive: discharging)
+battery_setpoint: f32,
+/// Whether the dispatch commanded the battery in this cycle
+battery_dispatched: bool,
+/// Battery power added after the dispatch by grid limits and grid support in the last cycle, in kW (positive: discharging)
+battery_correction: f32,
+/// Frequency-watt, volt-watt and ride-through monitoring (None: disabled)
+grid_support: Option<GridSupport>,
+/// Total charger power cap applied by the dispatch in this cycle, in kW
+charger_cap: Option<f32>,
 /// Cached system status
 cached_status: std::cell::RefCell<EmsStatus>,
 /// Control loop running flag
@@ -77,15 +188,61 @@ impl EmsController {
     /// Result containing the EMS controller or initialization error
     pub fn with_config(config: EmsConfig) -> Result<Self, String> {
         // Charger devices are added dynamically using add_charger_device()
+        let genset_controller = GensetController::new(GensetControlConfig {
+            start_soc: config.battery_soc_threshold,
+            ..Default::default()
+        });
 
         Ok(Self {
             pv_devices: Vec::new(), // PV devices are added dynamically using add_pv_device()
             battery_device: None, // TODO: Initialize battery device
+            battery_racks: None,
             genset_device: None, // TODO: Initialize genset device
+            genset_fleet: None,
             pcs_device: None, // TODO: Initialize PCS device
+            pcs_group: None,
+            grid_meter: None,
+            grid_measurement: None,
+            bus_measurement: None,
+            genset_reading: None,
+            genset_untracked_hours: 0.0,
+            grid_breaker: None,
             charger_devices: Vec::new(), // Charger devices are added dynamically
             config,
             current_mode: EmsMode::Normal,
+            tou_config: None,
+            tariff_tracker: TariffTracker::default(),
+            last_cycle: None,
+            pv_forecaster: PvForecaster::new(PvForecastConfig::default()),
+            load_forecaster: LoadForecaster::new(LoadForecastConfig::default()),
+            mpc_planner: None,
+            genset_controller,
+            unmet_deficit: 0.0,
+            generator_output: 0.0,
+            genset_request: None,
+            fuel_manager: FuelManager::new(FuelConfig::default()),
+            maintenance_planner: MaintenancePlanner::new(MaintenanceConfig::default()),
+            soc_estimator: SocEstimator::new(SocEstimatorConfig::default()),
+            battery_health: BatteryHealthTracker::new(BatteryHealthConfig::default()),
+            last_cell_poll: None,
+            battery_limiter: BatteryPowerLimiter::new(BatteryLimitConfig::default()),
+            cell_analytics: CellAnalytics::new(CellAnalyticsConfig::default()),
+            last_cell_detail_poll: None,
+            cell_detail_reader: None,
+            pv_curtailer: PvCurtailer::new(CurtailmentConfig::default()),
+            contactor: None,
+            islanding: None,
+            sync_check: None,
+            genset_min_load: None,
+            load_shedder: None,
+            reactive_controller: ReactivePowerController::new(ReactivePowerConfig::default()),
+            import_limiter: None,
+            export_limiter: None,
+            battery_setpoint: 0.0,
+            battery_dispatched: false,
+            battery_correction: 0.0,
+            grid_support: None,
+            charger_cap: None,
             cached_status: std::cell::RefCell::new(EmsStatus::default()),
             running: false,
         })
@@ -111,6 +268,27 @@ impl EmsController {
         self.battery_device = Some(device);
     }
 
+    /// Dispatch over parallel battery racks instead of the single battery and PCS
+    ///
+    /// Per-pack features (cell analytics, health, contactors) stay on the primary battery device;
+    /// the battery limiter is rated at the sum of the rack ratings, which the group SOP refers to.
+    ///
+    /// # Arguments
+    /// * `racks` - Rack group with its BMS and PCS devices
+    pub fn set_battery_racks(&mut self, racks: BatteryRackGroup) {
+        let rated = racks.rated_power();
+        self.battery_limiter.set_rated_power(rated, rated);
+        self.battery_racks = Some(racks);
+    }
+
+    /// Share battery power across parallel PCS units instead of the single PCS
+    ///
+    /// # Arguments
+    /// * `group` - PCS group with its unit devices
+    pub fn set_pcs_group(&mut self, group: PcsGroup) {
+        self.pcs_group = Some(group);
+    }
+
     /// Add generator device to the EMS
     ///
     /// # Arguments
@@ -119,6 +297,35 @@ impl EmsController {
         self.genset_device = Some(device);
     }
 
+    /// Add the grid connection meter to the EMS
+    ///
+    /// # Arguments
+    /// * `device` - Initialized energy meter at the grid connection point
+    pub fn add_grid_meter(&mut self, device: Arc<Mutex<EnergyMeterDevice>>) {
+        self.grid_meter = Some(device);
+    }
+
+    /// Add the grid interconnection breaker used for island operation
+    ///
+    /// # Arguments
+    /// * `device` - Initialized breaker with position feedback
+    pub fn add_grid_breaker(&mut self, device: Arc<Mutex<GridBreakerDevice>>) {
+        self.grid_breaker = Some(device);
+    }
+
+    /// Control several gensets as a lead/lag fleet instead of the single genset
+    ///
+    /// Fuel and maintenance tracking stay on the primary genset device.
+    ///
+    /// # Arguments
+    /// * `fleet` - Genset fleet with its devices
+    pub fn set_genset_fleet(&mut self, mut fleet: GensetFleet) {
+        if let Some(ref sync_check) = self.sync_check {
+            fleet.set_sync_check(sync_check.config().clone());
+        }
+        self.genset_fleet = Some(fleet);
+    }
+
     /// Add PCS device to the EMS
     ///
     /// # Arguments
@@ -172,6 +379,276 @@ impl EmsController {
         Ok(())
     }
 
+    /// Configure time-of-use dispatch
+    ///
+    /// # Arguments
+    /// * `config` - Tariff calendar and dispatch parameters
+    pub fn set_tou_config(&mut self, config: TouDispatchConfig) {
+        self.tou_config = Some(config);
+    }
+
+    /// Configure the charging load forecaster
+    ///
+    /// # Arguments
+    /// * `config` - Holidays, learning rate and profile storage
+    pub fn set_load_forecast_config(&mut self, config: LoadForecastConfig) {
+        self.load_forecaster = LoadForecaster::new(config);
+    }
+
+    /// Configure the PV production forecaster
+    ///
+    /// # Arguments
+    /// * `config` - Initial capacity, horizon, learning thresholds and state storage
+    pub fn set_pv_forecast_config(&mut self, config: PvForecastConfig) {
+        self.pv_forecaster = PvForecaster::new(config);
+    }
+
+    /// Configure the model-predictive dispatch planner
+    ///
+    /// # Arguments
+    /// * `config` - Planner horizon, battery, grid and genset parameters
+    pub fn set_mpc_config(&mut self, config: MpcConfig) {
+        self.mpc_planner = Some(MpcPlanner::new(config));
+    }
+
+    /// Configure genset start/stop thresholds and timings
+    ///
+    /// # Arguments
+    /// * `config` - Genset controller configuration
+    pub fn set_genset_control_config(&mut self, config: GensetControlConfig) {
+        self.genset_controller = GensetController::new(config);
+    }
+
+    /// Require a synchronisation check before the genset is paralleled with the PCS
+    ///
+    /// # Arguments
+    /// * `config` - Voltage, frequency and phase tolerances and dwell time
+    pub fn set_sync_check_config(&mut self, config: SyncCheckConfig) {
+        // A fleet checks every unit on its own
+        if let Some(ref mut fleet) = self.genset_fleet {
+            fleet.set_sync_check(config.clone());
+        }
+        self.sync_check = Some(SyncChecker::new(config));
+    }
+
+    /// Keep a running genset above a minimum load to prevent wet stacking
+    ///
+    /// # Arguments
+    /// * `config` - Minimum load, rating and low-load timeout
+    pub fn set_genset_min_load_config(&mut self, config: MinLoadConfig) {
+        self.genset_min_load = Some(GensetMinLoad::new(config));
+    }
+
+    /// Configure genset fuel tracking
+    ///
+    /// # Arguments
+    /// * `config` - Tank size, consumption curve and alarm levels
+    pub fn set_fuel_config(&mut self, config: FuelConfig) {
+        self.fuel_manager = FuelManager::new(config);
+    }
+
+    /// Configure genset maintenance intervals and exercise runs
+    ///
+    /// # Arguments
+    /// * `config` - Service intervals, alert margins and exercise settings
+    pub fn set_maintenance_config(&mut self, config: MaintenanceConfig) {
+        self.maintenance_planner = MaintenancePlanner::new(config);
+    }
+
+    /// Configure the EMS-side SOC estimator
+    ///
+    /// # Arguments
+    /// * `config` - Capacity, OCV table and correction parameters
+    pub fn set_soc_estimator_config(&mut self, config: SocEstimatorConfig) {
+        self.soc_estimator = SocEstimator::new(config);
+    }
+
+    /// Configure battery health tracking
+    ///
+    /// # Arguments
+    /// * `config` - Capacity, cycle life and measurement parameters
+    pub fn set_battery_health_config(&mut self, config: BatteryHealthConfig) {
+        self.battery_health = BatteryHealthTracker::new(config);
+    }
+
+    /// Configure battery power limits and derating
+    ///
+    /// # Arguments
+    /// * `config` - Ratings, derating curves and ramp rates
+    pub fn set_battery_limit_config(&mut self, config: BatteryLimitConfig) {
+        self.battery_limiter = BatteryPowerLimiter::new(config);
+        if let Some(ref racks) = self.battery_racks {
+            let rated = racks.rated_power();
+            self.battery_limiter.set_rated_power(rated, rated);
+        }
+    }
+
+    /// Configure per-cell data polling and imbalance analytics
+    ///
+    /// # Arguments
+    /// * `config` - Polling, outlier and weak-cell thresholds
+    pub fn set_cell_analytics_config(&mut self, config: CellAnalyticsConfig) {
+        self.cell_analytics = CellAnalytics::new(config);
+    }
+
+    /// Enable per-cell data reads on a dedicated BMS connection
+    ///
+    /// # Arguments
+    /// * `device` - Battery device opened on the BMS interface for cell detail reads only
+    pub fn set_cell_detail_reader(&mut self, device: bms::BatteryDevice) {
+        self.cell_detail_reader = Some(CellDetailReader::spawn(device));
+    }
+
+    /// Configure PV curtailment thresholds
+    ///
+    /// # Arguments
+    /// * `config` - Battery full level, cap margins and ramp rate
+    pub fn set_curtailment_config(&mut self, config: CurtailmentConfig) {
+        self.pv_curtailer = PvCurtailer::new(config);
+    }
+
+    /// Configure PCS reactive power control
+    ///
+    /// # Arguments
+    /// * `config` - Mode, setpoints and volt-var curve
+    pub fn set_reactive_power_config(&mut self, config: ReactivePowerConfig) {
+        self.reactive_controller = ReactivePowerController::new(config);
+    }
+
+    /// Enable grid-support functions
+    ///
+    /// # Arguments
+    /// * `config` - Droop settings, volt-watt curve and ride-through bands
+    pub fn set_grid_support_config(&mut self, config: GridSupportConfig) {
+        self.grid_support = Some(GridSupport::new(config));
+    }
+
+    /// Enable closed-loop site import limiting; requires a grid meter
+    ///
+    /// # Arguments
+    /// * `config` - Import limit, PI gains, ramp rates and fail-safe charger cap
+    pub fn set_import_limit_config(&mut self, config: ImportLimitConfig) {
+        self.import_limiter = Some(ImportLimiter::new(config));
+    }
+
+    /// Enable closed-loop site export limiting (zero export with a limit of 0); requires a grid meter
+    ///
+    /// # Arguments
+    /// * `config` - Export limit, PI gains, ramp rates and fast-trip threshold
+    pub fn set_export_limit_config(&mut self, config: ExportLimitConfig) {
+        self.export_limiter = Some(ExportLimiter::new(config));
+    }
+
+    /// Enable battery contactor sequencing; the battery is requested online immediately
+    ///
+    /// # Arguments
+    /// * `config` - Precharge thresholds and timeouts
+    pub fn set_contactor_config(&mut self, config: ContactorConfig) {
+        let mut contactor = ContactorController::new(config);
+        contactor.request_online(true);
+        self.contactor = Some(contactor);
+    }
+
+    /// Sequence the battery online or offline
+    ///
+    /// # Arguments
+    /// * `online` - True to precharge and close, false to unload and open
+    ///
+    /// # Returns
+    /// Result indicating success or error if contactor control is not configured
+    pub fn set_battery_online(&mut self, online: bool) -> Result<(), String> {
+        match self.contactor {
+            Some(ref mut contactor) => {
+                contactor.request_online(online);
+                Ok(())
+            }
+            None => Err("Battery contactor control is not configured".to_string()),
+        }
+    }
+
+    /// Clear a latched contactor fault
+    pub fn reset_contactor_fault(&mut self) {
+        if let Some(ref mut contactor) = self.contactor {
+            contactor.reset_fault();
+        }
+    }
+
+    /// Enable grid loss detection, island operation and black start
+    ///
+    /// # Arguments
+    /// * `config` - Grid limits, synchronisation tolerances, wait times and critical chargers
+    pub fn set_islanding_config(&mut self, config: IslandingConfig) {
+        self.islanding = Some(IslandController::new(config));
+    }
+
+    /// Shed and restore chargers by priority tier when power is short
+    ///
+    /// # Arguments
+    /// * `config` - Priority classes, restore margin and minimum-off time
+    pub fn set_load_shedding_config(&mut self, config: LoadSheddingConfig) {
+        self.load_shedder = Some(LoadShedder::new(config));
+    }
+
+    /// Clear a latched black start fault
+    pub fn reset_islanding_fault(&mut self) {
+        if let Some(ref mut islanding) = self.islanding {
+            islanding.reset_fault();
+        }
+    }
+
+    /// Record a completed genset service
+    ///
+    /// # Arguments
+    /// * `task` - Service task name
+    /// * `notes` - Optional technician notes
+    ///
+    /// # Returns
+    /// Result indicating success or unknown task error
+    pub fn record_genset_service(&mut self, task: &str, notes: Option<String>) -> Result<(), String> {
+        self.maintenance_planner.record_service(task, Local::now().naive_local(), notes)
+    }
+
+    /// Acknowledge an unexplained genset fuel level drop
+    pub fn acknowledge_fuel_theft(&mut self) {
+        self.fuel_manager.acknowledge_theft();
+    }
+
+    /// Clear a genset start lockout
+    pub fn reset_genset_lockout(&mut self) {
+        self.genset_controller.reset_lockout();
+        if let Some(ref mut fleet) = self.genset_fleet {
+            fleet.reset_lockouts();
+        }
+    }
+
+    /// Update site position used for solar geometry
+    ///
+    /// # Arguments
+    /// * `gps` - Latest GPS fix
+    pub fn set_site_location(&mut self, gps: &GpsData) {
+        if gps.latitude != 0.0 || gps.longitude != 0.0 {
+            self.pv_forecaster.set_location(gps.latitude, gps.longitude, gps.altitude as f64);
+        }
+    }
+
+    /// Switch operational mode
+    ///
+    /// # Arguments
+    /// * `mode` - New operational mode
+    ///
+    /// # Returns
+    /// Result indicating success or error message
+    pub fn set_mode(&mut self, mode: EmsMode) -> Result<(), String> {
+        if mode == EmsMode::TimeOfUse && self.tou_config.is_none() {
+            return Err("Time-of-use mode requires a tariff configuration".to_string());
+        }
+        if mode == EmsMode::Optimized && self.mpc_planner.is_none() {
+            return Err("Optimized mode requires an MPC configuration".to_string());
+        }
+        self.current_mode = mode;
+        Ok(())
+    }
+
     /// Start the EMS control loop
     ///
     /// # Returns
@@ -201,8 +678,15 @@ impl EmsController {
             return Ok(()); // Skip if not running
         }
 
+        let now = Local::now().naive_local();
+        let dt_hours = self.cycle_duration_hours();
+
         // 1. Read all device statuses
         let (pv_power, battery_soc, battery_power, generator_power) = self.read_device_statuses()?;
+        self.generator_output = generator_power;
+
+        self.sequence_contactors(battery_power)?;
+        self.run_islanding()?;
 
         // 2. Calculate total available power
         let available_power = pv_power + generator_power;
@@ -210,15 +694,73 @@ impl EmsController {
         // 3. Calculate current charger demand
         let charger_demand = self.calculate_charger_demand();
 
+        // Learn from measured production and refresh the PV forecast
+        let utc_now = Utc::now();
+        let irradiance = self.read_pv_irradiance();
+        // Curtailed output would be learned as cloud cover
+        if !self.pv_curtailer.is_curtailing() && !self.export_limiter.as_ref().is_some_and(|l| l.is_curtailing()) {
+            self.pv_forecaster.observe(utc_now, pv_power, irradiance);
+        }
+        self.pv_forecaster.refresh(utc_now);
+        // Under last cycle's cap the chargers drew the cap, not the demand, so the slot is not learned
+        let learned_demand = match self.charger_cap {
+            Some(_) => None,
+            None => Some(charger_demand),
+        };
+        self.load_forecaster.observe(now, learned_demand);
+        self.update_genset_tracking(now, dt_hours)?;
+        self.run_sync_check()?;
+        self.update_battery_health(now, battery_soc, battery_power, dt_hours)?;
+        self.battery_limiter.update(Instant::now(), self.battery_health.cell_status());
+
         // 4. Execute power balancing logic
-        self.balance_power(available_power, charger_demand, battery_soc)?;
+        self.battery_dispatched = false;
+        self.charger_cap = None;
+        match self.current_mode {
+            EmsMode::TimeOfUse => self.balance_power_tou(now, available_power, charger_demand, battery_soc)?,
+            EmsMode::Optimized => self.balance_power_mpc(now, available_power, charger_demand, battery_soc)?,
+            _ => self.balance_power(available_power, charger_demand, battery_soc)?,
+        }
+        self.run_genset_control(now, battery_soc)?;
+        self.run_curtailment(now, pv_power, charger_demand, battery_soc, irradiance)?;
+        // The PCS still holds last cycle's correction when the dispatch left the battery alone
+        let base = if self.battery_dispatched { self.battery_setpoint } else { self.battery_setpoint - self.battery_correction };
+        let correction = self.apply_grid_limits(base, pv_power, charger_demand, battery_soc)?;
+        let correction = correction - self.apply_genset_min_load(base + correction, generator_power, battery_soc)?;
+        let target = self.apply_grid_support(base + correction);
+        self.correct_battery_power(base, target)?;
+        self.run_load_shedding()?;
+        let voltage = self.measured_ac_voltage();
+        if let Some(command) = self.reactive_controller.update(Instant::now(), voltage) {
+            match self.apply_reactive(command) {
+                Ok(()) => self.reactive_controller.confirm(command),
+                Err(e) => log::warn!("Reactive power control: {}", e),
+            }
+        }
+
+        // 5. Tariff accounting (metered, otherwise the grid covers whatever local sources and battery do not)
+        if let Some(ref tou) = self.tou_config {
+            let grid_import = self.measured_grid_power().unwrap_or(charger_demand - available_power - battery_power);
+            self.tariff_tracker.record(tou, now, battery_power, grid_import, dt_hours);
+        }
 
-        // 5. Update cached status
+        // 6. Update cached status
         self.update_cached_status(pv_power, battery_power, generator_power, charger_demand);
 
         Ok(())
     }
 
+    /// Time elapsed since the previous control cycle
+    ///
+    /// # Returns
+    /// Elapsed time in hours (0 on the first cycle)
+    fn cycle_duration_hours(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = self.last_cycle.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_cycle = Some(now);
+        elapsed / 3600.0
+    }
+
     /// Read status from all connected devices
     ///
     /// # Returns
@@ -238,32 +780,124 @@ impl EmsController {
             }
         }
 
-        // Read battery status
-        if let Some(ref battery) = self.battery_device {
+        // Read battery status (aggregated over the online racks when racks are configured)
+        let battery_status = if let Some(ref mut racks) = self.battery_racks {
+            racks.read()?
+        } else if let Some(ref battery) = self.battery_device {
             let battery_locked = battery.lock().map_err(|_| "Mutex poisoned".to_string())?;
             match battery_locked.read_status() {
-                Ok(status) => {
-                    battery_soc = status.soc;
-                    battery_power = status.current * status.voltage / 1000.0; // Convert W to kW
+                Ok(status) => Some(status),
+                Err(e) => {
+                    log::warn!("Failed to read battery status: {}", e);
+                    None
                 }
-                Err(e) => log::warn!("Failed to read battery status: {}", e),
             }
+        } else {
+            None
+        };
+        self.bus_measurement = if let Some(ref mut group) = self.pcs_group {
+            group.read()?
+        } else if let Some(ref pcs) = self.pcs_device {
+            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            match pcs_locked.read_status() {
+                Ok(status) => Some((status.voltage_ac, status.frequency)),
+                Err(e) => {
+                    log::warn!("Failed to read PCS status: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(status) = battery_status {
+            self.soc_estimator.update(Instant::now(), &status);
+            self.battery_limiter.observe_bms(&status);
+            battery_soc = self.soc_estimator.dispatch_soc(status.soc);
+            battery_power = status.current * status.voltage / 1000.0; // Convert W to kW
         }
 
-        // Read generator status
+        // Read generator status; the primary genset is read directly unless it is part of the fleet
+        if let Some(ref mut fleet) = self.genset_fleet {
+            generator_power = fleet.read()?;
+        }
+        self.genset_reading = None;
         if let Some(ref genset) = self.genset_device {
             let mut genset_locked = genset.lock().map_err(|_| "Mutex poisoned".to_string())?;
-            match genset_locked.read_status() {
-                Ok(status) => {
-                    generator_power = if status.running { status.power_output } else { 0.0 };
+            match self.genset_fleet {
+                Some(ref fleet) if fleet.contains(&genset_locked.id) => {
+                    self.genset_reading = fleet.reading(&genset_locked.id);
                 }
-                Err(e) => log::warn!("Failed to read generator status: {}", e),
+                _ => match genset_locked.read_status() {
+                    Ok(status) => {
+                        if self.genset_fleet.is_none() {
+                            generator_power = if status.running { status.power_output } else { 0.0 };
+                        }
+                        self.genset_reading = Some(status);
+                    }
+                    Err(e) => log::warn!("Failed to read generator status: {}", e),
+                },
             }
         }
 
+        // Read grid meter
+        if let Some(ref meter) = self.grid_meter {
+            let mut meter_locked = meter.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            self.grid_measurement = match meter_locked.read_status() {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    log::warn!("Failed to read grid meter {}: {}", meter_locked.id, e);
+                    None
+                }
+            };
+        }
+
         Ok((pv_power, battery_soc, battery_power, generator_power))
     }
 
+    /// Metered grid power
+    ///
+    /// # Returns
+    /// Grid power in kW (positive: importing), None without a valid meter reading
+    fn measured_grid_power(&self) -> Option<f32> {
+        self.grid_measurement.as_ref().map(|m| m.active_power)
+    }
+
+    /// Grid frequency at the point of connection
+    ///
+    /// # Returns
+    /// Frequency from the grid meter, otherwise from the PCS, in Hz
+    fn measured_frequency(&self) -> Option<f32> {
+        self.grid_measurement.as_ref()
+            .map(|m| m.frequency)
+            .or_else(|| self.pcs_device.as_ref().and_then(|pcs| pcs.lock().ok().map(|p| p.frequency)))
+            .filter(|f| *f > 0.0)
+    }
+
+    /// AC voltage at the point of connection
+    ///
+    /// # Returns
+    /// Mean phase voltage from the grid meter, otherwise the PCS AC voltage, in V
+    fn measured_ac_voltage(&self) -> Option<f32> {
+        if let Some(ref measurement) = self.grid_measurement {
+            if !measurement.phases.is_empty() {
+                return Some(measurement.phases.iter().map(|p| p.voltage).sum::<f32>() / measurement.phases.len() as f32);
+            }
+        }
+        self.pcs_device.as_ref()
+            .and_then(|pcs| pcs.lock().ok().map(|p| p.voltage_ac))
+            .filter(|v| *v > 0.0)
+    }
+
+    /// Highest irradiance reported by the PV devices
+    ///
+    /// # Returns
+    /// Irradiance in W/m² (0 if no sensor)
+    fn read_pv_irradiance(&self) -> f32 {
+        self.pv_devices.iter()
+            .filter_map(|pv| pv.lock().ok().map(|p| p.irradiance))
+            .fold(0.0, f32::max)
+    }
+
     /// Calculate total power demand from all charging stations
     ///
     /// # Returns
@@ -285,6 +919,13 @@ impl EmsController {
         total_demand
     }
 
+    /// Number of chargers with a vehicle charging
+    fn active_charger_count(&self) -> usize {
+        self.charger_devices.iter()
+            .filter(|c| c.lock().map(|c| c.get_cached_status().charging).unwrap_or(false))
+            .count()
+    }
+
     /// Execute power balancing logic according to priority: PV > Grid > Battery > Generator
     ///
     /// # Arguments
@@ -296,15 +937,20 @@ impl EmsController {
     /// Result indicating success or balancing error
     fn balance_power(&mut self, available_power: f32, charger_demand: f32, battery_soc: f32) -> Result<(), String> {
         let power_deficit = charger_demand - available_power;
+        self.unmet_deficit = 0.0;
 
         if power_deficit <= 0.0 {
             // Surplus power available
             let surplus = -power_deficit;
 
             // Priority 1: Use surplus to charge battery if SOC is low
-            if battery_soc < 90.0 && surplus > 0.0 {
-                self.charge_battery(surplus.min(50.0))?; // Limit charging rate
-            }
+            let absorbed = if battery_soc < self.pv_curtailer.full_soc() && surplus > 0.0 {
+                self.charge_battery(surplus)?
+            } else {
+                0.0
+            };
+            // A running genset backs off by whatever the battery cannot absorb
+            self.load_genset(absorbed - surplus)?;
 
             // Priority 2: Export to grid (not implemented yet)
             // TODO: Implement grid export logic
@@ -315,17 +961,13 @@ impl EmsController {
 
             // Priority 1: Discharge battery if SOC is sufficient
             if battery_soc > self.config.battery_soc_threshold + 5.0 && remaining_deficit > 0.0 {
-                let battery_contribution = remaining_deficit.min(50.0); // Limit discharge rate
-                self.discharge_battery(battery_contribution)?;
+                let battery_contribution = self.discharge_battery(remaining_deficit)?;
                 remaining_deficit -= battery_contribution;
             }
 
-            // Priority 2: Start generator if battery SOC is low and deficit remains
-            if battery_soc <= self.config.battery_soc_threshold && remaining_deficit > 0.0 {
-                self.start_generator()?;
-                // Assume generator can provide remaining deficit
-                // In real implementation, would need to check generator capacity
-            }
+            // Priority 2: Generator; start/stop is decided by the genset controller from SOC and this deficit
+            self.unmet_deficit = remaining_deficit;
+            self.load_genset(remaining_deficit)?;
 
             // Priority 3: Reduce charger power if still insufficient
             if remaining_deficit > 0.0 {
@@ -336,47 +978,311 @@ impl EmsController {
         Ok(())
     }
 
-    /// Charge battery with specified power
+    /// Execute time-of-use dispatch: charge in off-peak windows, discharge in peak windows,
+    /// never discharge below the reserve SOC. The grid covers any remaining deficit.
     ///
     /// # Arguments
-    /// * `power` - Charging power in kW
+    /// * `now` - Local wall-clock time
+    /// * `available_power` - Available power from PV and generator in kW
+    /// * `charger_demand` - Current charger power demand in kW
+    /// * `battery_soc` - Current battery state of charge (0-100%)
     ///
     /// # Returns
-    /// Result indicating success or battery control error
-    fn charge_battery(&mut self, power: f32) -> Result<(), String> {
-        if let Some(ref pcs) = self.pcs_device {
-            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
-            pcs_locked.set_mode(crate::devices::pcs::PcsMode::Charging)
-                .map_err(|e| format!("Failed to set PCS charging mode: {:?}", e))?;
-            pcs_locked.set_power_setpoint(-power) // Negative for charging
-                .map_err(|e| format!("Failed to set PCS charging power: {:?}", e))?;
-        }
-        Ok(())
+    /// Result indicating success or balancing error
+    fn balance_power_tou(&mut self, now: NaiveDateTime, available_power: f32, charger_demand: f32, battery_soc: f32) -> Result<(), String> {
+        let (slot, reserve_soc, target_soc, grid_charge_power, demand_limit) = match self.tou_config {
+            Some(ref tou) => {
+                // Leave room for the PV surplus expected over the next day
+                let pv_credit = self.expected_pv_surplus(now) * tou.pv_forecast_credit;
+                let target_soc = (tou.charge_target_soc - pv_credit / tou.battery_capacity.max(1.0) * 100.0).max(tou.reserve_soc);
+                (tou.calendar.slot_at(now), tou.reserve_soc, target_soc, tou.grid_charge_power, tou.demand_limit)
+            }
+            None => return self.balance_power(available_power, charger_demand, battery_soc),
+        };
+        let net_load = charger_demand - available_power;
+        let can_discharge = battery_soc > reserve_soc;
+        // Grid covers the deficit in TOU mode
+        self.unmet_deficit = 0.0;
+
+        // Band setpoint (positive: discharging)
+        let band_power = match slot.band {
+            PriceBand::Peak if net_load > 0.0 && can_discharge => net_load,
+            // Grid charging on top of any PV surplus
+            PriceBand::OffPeak if battery_soc < target_soc => -(grid_charge_power + (-net_load).max(0.0)),
+            // Store PV surplus, otherwise hold energy for the peak window
+            _ if net_load < 0.0 && battery_soc < target_soc => net_load,
+            _ => 0.0,
+        };
+
+        // Demand shaving applies in every band: discharge at least the excess over the limit
+        // and never charge past it
+        let target = match demand_limit {
+            Some(limit) => {
+                let excess = if can_discharge { net_load - limit } else { (net_load - limit).min(0.0) };
+                band_power.max(excess)
+            }
+            None => band_power,
+        };
+
+        let battery_power = if target > 0.0 {
+            self.discharge_battery(target)?
+        } else if target < 0.0 {
+            -self.charge_battery(-target)?
+        } else {
+            self.idle_battery()?;
+            0.0
+        };
+        // A running genset covers what the battery does not, ahead of the grid
+        self.load_genset(net_load - battery_power)
     }
 
-    /// Discharge battery with specified power
+    /// Execute the first step of the model-predictive plan, re-solving when due.
+    /// Falls back to rule-based balancing when no plan is available.
     ///
     /// # Arguments
-    /// * `power` - Discharging power in kW
+    /// * `now` - Local wall-clock time
+    /// * `available_power` - Available power from PV and generator in kW
+    /// * `charger_demand` - Current charger power demand in kW
+    /// * `battery_soc` - Current battery state of charge (0-100%)
     ///
     /// # Returns
-    /// Result indicating success or battery control error
-    fn discharge_battery(&mut self, power: f32) -> Result<(), String> {
-        if let Some(ref pcs) = self.pcs_device {
-            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
-            pcs_locked.set_mode(crate::devices::pcs::PcsMode::Discharging)
-                .map_err(|e| format!("Failed to set PCS discharging mode: {:?}", e))?;
-            pcs_locked.set_power_setpoint(power)
-                .map_err(|e| format!("Failed to set PCS discharging power: {:?}", e))?;
+    /// Result indicating success or balancing error
+    fn balance_power_mpc(&mut self, now: NaiveDateTime, available_power: f32, charger_demand: f32, battery_soc: f32) -> Result<(), String> {
+        if self.mpc_planner.as_ref().map(|p| p.needs_solve()).unwrap_or(false) {
+            let inputs = self.build_mpc_inputs(now, battery_soc);
+            if let Some(ref mut planner) = self.mpc_planner {
+                if let Err(e) = planner.solve(&inputs) {
+                    log::warn!("{}", e);
+                }
+            }
         }
+
+        let (step, grid_available) = match self.mpc_planner {
+            Some(ref planner) => match planner.current_step() {
+                Some(step) => (step.clone(), planner.config().grid_available),
+                None => return self.balance_power(available_power, charger_demand, battery_soc),
+            },
+            None => return self.balance_power(available_power, charger_demand, battery_soc),
+        };
+
+        // Genset commitment goes through the genset controller; load it once it is available
+        self.genset_request = Some(step.genset_on);
+        self.unmet_deficit = 0.0;
+        if step.genset_on && self.genset_loadable() {
+            self.set_generator_power(step.genset_power)?;
+        }
+
+        // Follow the planned battery power when the grid absorbs forecast errors,
+        // otherwise the battery balances the actual net load
+        let battery_power = if grid_available { step.battery_power } else { charger_demand - available_power };
+        if battery_power > 0.0 {
+            self.discharge_battery(battery_power)?;
+        } else if battery_power < 0.0 {
+            self.charge_battery(-battery_power)?;
+        } else {
+            self.idle_battery()?;
+        }
+
+        // Planned charger curtailment
+        if step.charger_limit < charger_demand {
+            self.reduce_charger_power(step.charger_limit)?;
+        }
+
         Ok(())
     }
 
-    /// Start the generator
+    /// Assemble forecasts, prices and SOC for the MPC planner
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `battery_soc` - Current battery state of charge (0-100%)
     ///
     /// # Returns
-    /// Result indicating success or generator start error
-    fn start_generator(&mut self) -> Result<(), String> {
+    /// Planner inputs aligned to the next 15-minute boundary
+    fn build_mpc_inputs(&self, now: NaiveDateTime, battery_soc: f32) -> MpcInputs {
+        let flat_price = self.mpc_planner.as_ref().map(|p| p.config().flat_price).unwrap_or(0.0);
+        let online = self.battery_online();
+        let start = now - Duration::minutes(now.minute() as i64 % STEP_MINUTES) - Duration::seconds(now.second() as i64)
+            + Duration::minutes(STEP_MINUTES);
+        let load: Vec<f32> = self.load_forecaster.forecast(now).iter().map(|p| p.mean).collect();
+        let pv: Vec<f32> = self.pv_forecaster.forecast().iter().map(|p| p.power).collect();
+        let price = (0..load.len() as i64)
+            .map(|i| {
+                let time = start + Duration::minutes(i * STEP_MINUTES);
+                match self.tou_config {
+                    Some(ref tou) => tou.calendar.slot_at(time).price,
+                    None => flat_price,
+                }
+            })
+            .collect();
+        MpcInputs {
+            start,
+            pv,
+            load,
+            price,
+            soc: battery_soc,
+            max_charge_power: if online { self.battery_limiter.max_charge_power() } else { 0.0 },
+            max_discharge_power: if online { self.battery_limiter.max_discharge_power() } else { 0.0 },
+            genset_cost_factor: self.fuel_manager.genset_cost_factor(),
+        }
+    }
+
+    /// Forecast PV energy in excess of forecast charger demand over the next 24 hours
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    ///
+    /// # Returns
+    /// Surplus energy in kWh
+    fn expected_pv_surplus(&self, now: NaiveDateTime) -> f32 {
+        self.pv_forecaster.forecast().iter()
+            .zip(self.load_forecaster.forecast(now))
+            .map(|(pv, load)| (pv.power - load.mean).max(0.0) * 0.25)
+            .sum()
+    }
+
+    /// Charge battery with specified power, clamped to the live battery charge limit
+    ///
+    /// # Arguments
+    /// * `power` - Requested charging power in kW
+    ///
+    /// # Returns
+    /// Result containing the applied charging power or battery control error
+    fn charge_battery(&mut self, power: f32) -> Result<f32, String> {
+        if !self.battery_online() {
+            return self.idle_battery().map(|_| 0.0);
+        }
+        let power = self.battery_limiter.clamp_charge(power);
+        self.record_battery_setpoint(-power);
+        if self.grid_forming() {
+            return Ok(power);
+        }
+        if let Some(ref mut racks) = self.battery_racks {
+            return racks.dispatch(-power).map(|applied| -applied);
+        }
+        if let Some(ref mut group) = self.pcs_group {
+            return group.dispatch(-power).map(|applied| -applied);
+        }
+        if let Some(ref pcs) = self.pcs_device {
+            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_mode(crate::devices::pcs::PcsMode::Charging)
+                .map_err(|e| format!("Failed to set PCS charging mode: {:?}", e))?;
+            pcs_locked.set_power_setpoint(-power) // Negative for charging
+                .map_err(|e| format!("Failed to set PCS charging power: {:?}", e))?;
+        }
+        Ok(power)
+    }
+
+    /// Discharge battery with specified power, clamped to the live battery discharge limit
+    ///
+    /// # Arguments
+    /// * `power` - Requested discharging power in kW
+    ///
+    /// # Returns
+    /// Result containing the applied discharging power or battery control error
+    fn discharge_battery(&mut self, power: f32) -> Result<f32, String> {
+        if !self.battery_online() {
+            return self.idle_battery().map(|_| 0.0);
+        }
+        let power = self.battery_limiter.clamp_discharge(power);
+        self.record_battery_setpoint(power);
+        if self.grid_forming() {
+            return Ok(power);
+        }
+        if let Some(ref mut racks) = self.battery_racks {
+            return racks.dispatch(power);
+        }
+        if let Some(ref mut group) = self.pcs_group {
+            return group.dispatch(power);
+        }
+        if let Some(ref pcs) = self.pcs_device {
+            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_mode(crate::devices::pcs::PcsMode::Discharging)
+                .map_err(|e| format!("Failed to set PCS discharging mode: {:?}", e))?;
+            pcs_locked.set_power_setpoint(power)
+                .map_err(|e| format!("Failed to set PCS discharging power: {:?}", e))?;
+        }
+        Ok(power)
+    }
+
+    /// Hold the battery idle
+    ///
+    /// # Returns
+    /// Result indicating success or battery control error
+    fn idle_battery(&mut self) -> Result<(), String> {
+        self.record_battery_setpoint(0.0);
+        if self.grid_forming() {
+            return Ok(());
+        }
+        if let Some(ref mut racks) = self.battery_racks {
+            return racks.dispatch(0.0).map(|_| ());
+        }
+        if let Some(ref mut group) = self.pcs_group {
+            return group.dispatch(0.0).map(|_| ());
+        }
+        if let Some(ref pcs) = self.pcs_device {
+            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_power_setpoint(0.0)
+                .map_err(|e| format!("Failed to set PCS idle power: {:?}", e))?;
+            pcs_locked.set_mode(crate::devices::pcs::PcsMode::Standby)
+                .map_err(|e| format!("Failed to set PCS standby mode: {:?}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Remember the battery setpoint sent in this cycle
+    ///
+    /// # Arguments
+    /// * `power` - Battery power setpoint in kW (positive: discharging)
+    fn record_battery_setpoint(&mut self, power: f32) {
+        self.battery_setpoint = power;
+        self.battery_dispatched = true;
+    }
+
+    /// Charge, discharge or idle the battery from a signed setpoint
+    ///
+    /// # Arguments
+    /// * `power` - Battery power setpoint in kW (positive: discharging)
+    ///
+    /// # Returns
+    /// Result indicating success or battery control error
+    fn set_battery_power(&mut self, power: f32) -> Result<(), String> {
+        if power > 0.0 {
+            self.discharge_battery(power).map(|_| ())
+        } else if power < 0.0 {
+            self.charge_battery(-power).map(|_| ())
+        } else {
+            self.idle_battery()
+        }
+    }
+
+    /// Send a reactive control command to the PCS units
+    ///
+    /// # Arguments
+    /// * `command` - Reactive control mode and setpoint
+    ///
+    /// # Returns
+    /// Result indicating success or PCS control error
+    fn apply_reactive(&mut self, command: ReactiveCommand) -> Result<(), String> {
+        if let Some(ref mut racks) = self.battery_racks {
+            return racks.set_reactive(command.mode, command.value);
+        }
+        if let Some(ref mut group) = self.pcs_group {
+            return group.set_reactive(command.mode, command.value);
+        }
+        if let Some(ref pcs) = self.pcs_device {
+            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_reactive_control(command.mode, command.value)
+                .map_err(|e| format!("Failed to set PCS reactive control: {:?}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Start the generator
+    ///
+    /// # Returns
+    /// Result indicating success or generator start error
+    fn start_generator(&mut self) -> Result<(), String> {
         if let Some(ref genset) = self.genset_device {
             let mut genset_locked = genset.lock().map_err(|_| "Mutex poisoned".to_string())?;
             genset_locked.start_engine()
@@ -385,6 +1291,645 @@ impl EmsController {
         Ok(())
     }
 
+    /// Stop the generator
+    ///
+    /// # Returns
+    /// Result indicating success or generator stop error
+    fn stop_generator(&mut self) -> Result<(), String> {
+        if let Some(ref genset) = self.genset_device {
+            let mut genset_locked = genset.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            genset_locked.stop_engine()
+                .map_err(|e| format!("Failed to stop generator: {:?}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Set generator power setpoint
+    ///
+    /// # Arguments
+    /// * `power` - Power setpoint in kW
+    ///
+    /// # Returns
+    /// Result indicating success or generator control error
+    fn set_generator_power(&mut self, power: f32) -> Result<(), String> {
+        if let Some(ref mut fleet) = self.genset_fleet {
+            return fleet.set_power(power);
+        }
+        if let Some(ref genset) = self.genset_device {
+            let mut genset_locked = genset.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            genset_locked.set_power_setpoint(power)
+                .map_err(|e| format!("Failed to set generator power: {:?}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Advance the battery contactor sequence and send the resulting command
+    ///
+    /// # Arguments
+    /// * `battery_power` - Current battery power in kW
+    ///
+    /// # Returns
+    /// Result indicating success or device access error
+    fn sequence_contactors(&mut self, battery_power: f32) -> Result<(), String> {
+        let (contactor, battery) = match (self.contactor.as_mut(), self.battery_device.as_ref()) {
+            (Some(contactor), Some(battery)) => (contactor, battery),
+            _ => return Ok(()),
+        };
+        let now = Instant::now();
+        let battery_locked = battery.lock().map_err(|_| "Mutex poisoned".to_string())?;
+        match battery_locked.read_contactor_status() {
+            Ok(feedback) => {
+                let pack_current = battery_power * 1000.0 / feedback.pack_voltage.max(1.0);
+                contactor.update(now, feedback, pack_current);
+            }
+            Err(e) => {
+                log::warn!("Failed to read battery contactor status: {}", e);
+                contactor.comm_lost(now);
+            }
+        }
+        // Command is repeated every cycle so a lost frame is recovered on the next one
+        if let Err(e) = battery_locked.set_contactors(contactor.command()) {
+            log::warn!("Failed to send battery contactor command: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Run the islanding state machine and carry out its commands
+    ///
+    /// # Returns
+    /// Result indicating success or device control error
+    fn run_islanding(&mut self) -> Result<(), String> {
+        if self.islanding.is_none() {
+            return Ok(());
+        }
+        let (grid_voltage, grid_frequency) = match self.grid_measurement {
+            Some(ref m) if !m.phases.is_empty() => (
+                Some(m.phases.iter().map(|p| p.voltage).sum::<f32>() / m.phases.len() as f32),
+                Some(m.frequency),
+            ),
+            _ => (None, None),
+        };
+        let (site_voltage, site_frequency, pcs_fault) = match self.pcs_device {
+            Some(ref pcs) => {
+                let pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+                (pcs_locked.voltage_ac, pcs_locked.frequency, pcs_locked.fault)
+            }
+            None => (0.0, 0.0, false),
+        };
+        let breaker_closed = match self.grid_breaker {
+            Some(ref breaker) => {
+                let mut breaker_locked = breaker.lock().map_err(|_| "Mutex poisoned".to_string())?;
+                match breaker_locked.read_status() {
+                    Ok(closed) => Some(closed),
+                    Err(e) => {
+                        log::warn!("Failed to read grid breaker {}: {:?}", breaker_locked.id, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let inputs = IslandInputs {
+            grid_voltage,
+            grid_frequency,
+            site_voltage,
+            site_frequency,
+            pcs_fault,
+            battery_online: self.battery_online(),
+            breaker_closed,
+        };
+        let (commands, mode, close) = match self.islanding {
+            Some(ref mut islanding) => {
+                let commands = islanding.update(Instant::now(), inputs);
+                // Mode and breaker position are repeated every cycle so a failed write is recovered on the next one
+                (commands, commands.pcs_mode.or(islanding.pcs_mode()), islanding.breaker_command())
+            }
+            None => return Ok(()),
+        };
+
+        if let Some(ref breaker) = self.grid_breaker {
+            let mut breaker_locked = breaker.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            if let Err(e) = breaker_locked.set_closed(close) {
+                log::warn!("Failed to command grid breaker {}: {:?}", breaker_locked.id, e);
+            }
+        }
+
+        if commands.battery_online {
+            if let Some(ref mut contactor) = self.contactor {
+                contactor.request_online(true);
+            }
+        }
+        if commands.shed.is_some() {
+            self.apply_charger_shed()?;
+        }
+        if let Some(mode) = mode {
+            if let Err(e) = self.set_pcs_operating_mode(mode) {
+                log::warn!("Islanding: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the grid meter currently sees a live grid; sites without a grid meter are treated as off-grid
+    fn grid_present(&self) -> bool {
+        self.grid_measurement.as_ref().is_some_and(|m| m.frequency > 0.0 && m.phases.iter().any(|p| p.voltage > 0.0))
+    }
+
+    /// Whether the PCS forms the site voltage, so battery power follows the load
+    fn grid_forming(&self) -> bool {
+        self.islanding.as_ref().is_some_and(|i| i.grid_forming())
+    }
+
+    /// Whether a charger is shed by the islanding controller or the load shedding tiers
+    fn charger_shed(&self, id: &str) -> bool {
+        self.islanding.as_ref().is_some_and(|i| i.is_shed(id))
+            || self.load_shedder.as_ref().is_some_and(|s| s.is_shed(id))
+    }
+
+    /// Power drawn by the charging, unshed chargers of each priority tier
+    ///
+    /// # Returns
+    /// Result containing the load per tier in kW, indexed by LoadPriority, or device access error
+    fn charger_tier_loads(&self) -> Result<[f32; 3], String> {
+        let mut loads = [0.0; 3];
+        let shedder = match self.load_shedder {
+            Some(ref shedder) => shedder,
+            None => return Ok(loads),
+        };
+        for charger in &self.charger_devices {
+            let charger_locked = charger.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            let status = charger_locked.get_cached_status();
+            if status.charging && !self.charger_shed(&charger_locked.id) {
+                loads[shedder.priority_of(&charger_locked.id) as usize] += status.power;
+            }
+        }
+        Ok(loads)
+    }
+
+    /// Restore a shed tier once power allows and share the charger cap with it
+    ///
+    /// # Returns
+    /// Result indicating success or charger control error
+    fn run_load_shedding(&mut self) -> Result<(), String> {
+        if self.load_shedder.is_none() {
+            return Ok(());
+        }
+        let loads = self.charger_tier_loads()?;
+        let budget = self.charger_cap;
+        let restored = match self.load_shedder {
+            Some(ref mut shedder) => shedder.restore(Instant::now(), budget, loads),
+            None => None,
+        };
+        if restored.is_some() {
+            self.apply_charger_shed()?;
+            if let Some(cap) = budget {
+                self.reduce_charger_power(cap)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Command the PCS units into an operating mode
+    ///
+    /// # Arguments
+    /// * `mode` - PCS operating mode
+    ///
+    /// # Returns
+    /// Result indicating success or PCS control error
+    fn set_pcs_operating_mode(&mut self, mode: crate::devices::pcs::PcsMode) -> Result<(), String> {
+        if let Some(ref mut racks) = self.battery_racks {
+            return racks.set_operating_mode(mode);
+        }
+        if let Some(ref mut group) = self.pcs_group {
+            return group.set_operating_mode(mode);
+        }
+        if let Some(ref pcs) = self.pcs_device {
+            let mut pcs_locked = pcs.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_mode(mode)
+                .map_err(|e| format!("Failed to set PCS {:?} mode: {:?}", mode, e))?;
+        }
+        Ok(())
+    }
+
+    /// Stop shed chargers and return the others to full power, or to an even share of the battery and
+    /// PV supply while the site is islanded
+    ///
+    /// # Returns
+    /// Result indicating success or charger control error
+    fn apply_charger_shed(&mut self) -> Result<(), String> {
+        let unshed = self.charger_devices.iter()
+            .filter(|c| c.lock().map(|c| !self.charger_shed(&c.id)).unwrap_or(false))
+            .count();
+        let share = if self.grid_forming() && unshed > 0 {
+            let battery = if self.battery_online() { self.battery_limiter.max_discharge_power() } else { 0.0 };
+            let pv: f32 = self.pv_devices.iter()
+                .filter_map(|pv| pv.lock().ok().map(|p| p.power / 1000.0))
+                .sum();
+            ((battery + pv) / unshed as f32).min(self.config.max_charger_power)
+        } else {
+            self.config.max_charger_power
+        };
+        for charger in &self.charger_devices {
+            let charger_locked = charger.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            let power = if self.charger_shed(&charger_locked.id) { 0.0 } else { share };
+            charger_locked.set_power_setpoint(power)
+                .map_err(|e| format!("Failed to set charger {} power after shed change: {}", charger_locked.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the battery is connected to the DC link
+    fn battery_online(&self) -> bool {
+        self.contactor.as_ref().map(|c| c.is_online()).unwrap_or(true)
+    }
+
+    /// Poll BMS cell status when due and update battery health tracking
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `battery_soc` - Current battery state of charge (0-100%)
+    /// * `battery_power` - Current battery power in kW (positive = discharging)
+    /// * `dt_hours` - Time since the previous cycle in hours
+    ///
+    /// # Returns
+    /// Result indicating success or device access error
+    fn update_battery_health(&mut self, now: NaiveDateTime, battery_soc: f32, battery_power: f32, dt_hours: f32) -> Result<(), String> {
+        let battery = match self.battery_device {
+            Some(ref battery) => battery,
+            None => return Ok(()),
+        };
+        let poll_due = self.last_cell_poll
+            .map(|t| t.elapsed().as_secs() >= self.battery_health.cell_poll_interval())
+            .unwrap_or(true);
+        if poll_due {
+            self.last_cell_poll = Some(Instant::now());
+            let battery_locked = battery.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            match battery_locked.read_cell_status() {
+                Ok(cells) => self.battery_health.observe_cells(cells),
+                Err(e) => log::warn!("Failed to read battery cell status: {}", e),
+            }
+        }
+
+        // Full per-cell read once the cell count is known; the burst is collected in the background
+        if let Some(reader) = self.cell_detail_reader.as_mut() {
+            match reader.poll() {
+                Some(Ok(details)) => self.cell_analytics.update(now, details),
+                Some(Err(e)) => log::warn!("Failed to read battery cell details: {}", e),
+                None => {}
+            }
+            let detail_due = self.last_cell_detail_poll
+                .map(|t| t.elapsed().as_secs() >= self.cell_analytics.poll_interval())
+                .unwrap_or(true);
+            let cell_count = self.battery_health.cell_status().map(|c| c.cell_count).unwrap_or(0);
+            if detail_due && cell_count > 0 && reader.request(cell_count, self.cell_analytics.cells_per_module()) {
+                self.last_cell_detail_poll = Some(Instant::now());
+            }
+        }
+        self.battery_health.observe(now, battery_soc, battery_power, dt_hours);
+        Ok(())
+    }
+
+    /// Update genset fuel accounting and maintenance tracking from the latest genset status
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `dt_hours` - Time since the previous cycle in hours
+    ///
+    /// # Returns
+    /// Result indicating success or device access error
+    fn update_genset_tracking(&mut self, now: NaiveDateTime, dt_hours: f32) -> Result<(), String> {
+        // Only a successful read this cycle: cached values are zero before the first read and stale after a failure
+        match self.genset_reading {
+            Some(ref status) => {
+                let dt_hours = dt_hours + std::mem::take(&mut self.genset_untracked_hours);
+                self.fuel_manager.update(now, status, dt_hours);
+                self.maintenance_planner.update(now, status);
+            }
+            None => self.genset_untracked_hours += dt_hours,
+        }
+        Ok(())
+    }
+
+    /// Run the genset start/stop controller and execute its action
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `battery_soc` - Current battery state of charge (0-100%)
+    ///
+    /// # Returns
+    /// Result indicating success or generator control error
+    fn run_genset_control(&mut self, now: NaiveDateTime, battery_soc: f32) -> Result<(), String> {
+        if self.genset_fleet.is_none() && self.genset_device.is_none() {
+            return Ok(());
+        }
+        // A due exercise run takes precedence over the dispatch plan and keeps the genset unloaded
+        let demand = GensetDemand {
+            soc: battery_soc,
+            deficit: self.unmet_deficit,
+            request: self.genset_request.take(),
+            fuel_alarm: self.fuel_manager.alarm(),
+            low_load_stop: self.genset_min_load.as_ref().is_some_and(|m| m.stop_requested()),
+            exercise: self.maintenance_planner.exercise_request(now),
+        };
+        if let Some(ref mut fleet) = self.genset_fleet {
+            return fleet.update(Instant::now(), demand);
+        }
+        let status = match self.genset_device {
+            Some(ref genset) => genset.lock().map_err(|_| "Mutex poisoned".to_string())?.get_cached_status(),
+            None => return Ok(()),
+        };
+        match self.genset_controller.update(Instant::now(), demand, &status) {
+            GensetAction::Start => self.start_generator(),
+            GensetAction::Load => self.load_genset(self.unmet_deficit),
+            GensetAction::Unload => self.set_generator_power(0.0),
+            GensetAction::Stop => self.stop_generator(),
+            GensetAction::None => Ok(()),
+        }
+    }
+
+    /// Cap PV to what the load and battery can absorb, and release the cap when it is no longer needed
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `pv_power` - Measured PV power in kW
+    /// * `charger_demand` - Current charger power demand in kW
+    /// * `battery_soc` - Current battery state of charge (0-100%)
+    /// * `irradiance` - Measured irradiance in W/m² (0 if no sensor)
+    ///
+    /// # Returns
+    /// Result indicating success or PV control error
+    fn run_curtailment(&mut self, now: NaiveDateTime, pv_power: f32, charger_demand: f32, battery_soc: f32, irradiance: f32) -> Result<(), String> {
+        if self.pv_devices.is_empty() {
+            return Ok(());
+        }
+        let acceptance = if battery_soc < self.pv_curtailer.full_soc() && self.battery_online() {
+            self.battery_limiter.max_charge_power()
+        } else {
+            0.0
+        };
+        // Uncurtailed output from the irradiance sensor, otherwise from the forecast
+        let potential = if irradiance > 50.0 {
+            Some(self.pv_forecaster.effective_capacity() * irradiance / 1000.0)
+        } else {
+            self.pv_forecaster.forecast().first().map(|p| p.power)
+        };
+        let grid_connected = self.grid_present() && !self.grid_forming();
+        match self.pv_curtailer.update(Instant::now(), now, pv_power, charger_demand + acceptance, potential, grid_connected) {
+            CurtailAction::Cap(cap) => self.cap_pv(cap),
+            CurtailAction::Release => self.release_pv(),
+            CurtailAction::Hold => Ok(()),
+        }
+    }
+
+    /// Limit PV output, shared across the converters in proportion to their present output
+    ///
+    /// # Arguments
+    /// * `cap` - Total PV power limit in kW
+    ///
+    /// # Returns
+    /// Result indicating success or PV control error
+    fn cap_pv(&mut self, cap: f32) -> Result<(), String> {
+        let outputs = self.pv_devices.iter()
+            .map(|pv| pv.lock().map(|p| p.power.max(0.0)).map_err(|_| "Mutex poisoned".to_string()))
+            .collect::<Result<Vec<f32>, String>>()?;
+        let total: f32 = outputs.iter().sum();
+        let count = self.pv_devices.len() as f32;
+        for (pv, output) in self.pv_devices.iter().zip(outputs) {
+            let share = if total > 0.0 { cap * output / total } else { cap / count };
+            let mut pv_locked = pv.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pv_locked.set_mode(crate::devices::pv_dcdc::PvMode::ConstantVoltage)
+                .map_err(|e| format!("Failed to set PV {} constant-voltage mode: {:?}", pv_locked.id, e))?;
+            pv_locked.set_power_setpoint(share * 1000.0) // Convert kW to W
+                .map_err(|e| format!("Failed to set PV {} power limit: {:?}", pv_locked.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Return all PV converters to maximum power point tracking
+    ///
+    /// # Returns
+    /// Result indicating success or PV control error
+    fn release_pv(&mut self) -> Result<(), String> {
+        for pv in &self.pv_devices {
+            let mut pv_locked = pv.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pv_locked.set_mode(crate::devices::pv_dcdc::PvMode::MPPT)
+                .map_err(|e| format!("Failed to set PV {} MPPT mode: {:?}", pv_locked.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Correct the dispatch to hold metered grid import and export within the site limits.
+    /// Import is relieved by battery discharge, then charger curtailment; export is absorbed
+    /// by battery charge, then PV curtailment.
+    ///
+    /// # Arguments
+    /// * `base` - Battery setpoint from the dispatch in kW (positive: discharging)
+    /// * `pv_power` - Measured PV power in kW
+    /// * `charger_demand` - Current charger power demand in kW
+    /// * `battery_soc` - Current battery state of charge (0-100%)
+    ///
+    /// # Returns
+    /// Result containing the battery power to add to the dispatch in kW or device control error
+    fn apply_grid_limits(&mut self, base: f32, pv_power: f32, charger_demand: f32, battery_soc: f32) -> Result<f32, String> {
+        if self.import_limiter.is_none() && self.export_limiter.is_none() {
+            return Ok(0.0);
+        }
+        let now = Instant::now();
+        let measured = self.measured_grid_power();
+        let online = self.battery_online();
+        let mut correction = 0.0;
+
+        let charger_capacity = self.active_charger_count() as f32 * self.config.max_charger_power;
+        let discharge_headroom = if battery_soc > self.config.battery_soc_threshold && online {
+            (self.battery_limiter.max_discharge_power() - base).max(0.0)
+        } else {
+            0.0
+        };
+        let import_action = self.import_limiter.as_mut().map(|limiter| limiter.update(now, ImportLimitInputs {
+            measured,
+            battery_headroom: discharge_headroom,
+            charger_capacity,
+        }));
+
+        let charge_headroom = if battery_soc < self.pv_curtailer.full_soc() && online {
+            (self.battery_limiter.max_charge_power() + base).max(0.0)
+        } else {
+            0.0
+        };
+        let export_action = self.export_limiter.as_mut().map(|limiter| limiter.update(now, ExportLimitInputs {
+            measured,
+            battery_headroom: charge_headroom,
+            pv_power,
+            local_consumption: charger_demand - base,
+        }));
+
+        if let Some(action) = import_action {
+            correction += action.battery_boost;
+        }
+        if let Some(action) = export_action {
+            correction -= action.battery_absorb;
+        }
+        if let Some(action) = import_action {
+            // Never raise a cap the dispatch already applied
+            match (action.charger_cap, self.charger_cap) {
+                (Some(cap), Some(dispatch_cap)) => self.reduce_charger_power(cap.min(dispatch_cap))?,
+                (Some(cap), None) => self.reduce_charger_power(cap)?,
+                (None, None) if action.release_chargers => self.reduce_charger_power(charger_capacity)?,
+                _ => {}
+            }
+        }
+        if let Some(action) = export_action {
+            // The surplus curtailment cap stays in force underneath the export cap
+            match (action.pv_cap, self.pv_curtailer.cap()) {
+                (Some(cap), Some(surplus_cap)) => self.cap_pv(cap.min(surplus_cap))?,
+                (Some(cap), None) => self.cap_pv(cap)?,
+                (None, Some(surplus_cap)) if action.release_pv => self.cap_pv(surplus_cap)?,
+                (None, None) if action.release_pv => self.release_pv()?,
+                _ => {}
+            }
+        }
+        Ok(correction)
+    }
+
+    /// Raise battery charging, then charger allocations, to keep a running genset above its minimum load
+    ///
+    /// # Arguments
+    /// * `setpoint` - Battery setpoint after the grid limits in kW (positive: discharging)
+    /// * `generator_power` - Measured genset output in kW
+    /// * `battery_soc` - Current battery state of charge (0-100%)
+    ///
+    /// # Returns
+    /// Result containing the battery charge power to add in kW or charger control error
+    fn apply_genset_min_load(&mut self, setpoint: f32, generator_power: f32, battery_soc: f32) -> Result<f32, String> {
+        if self.genset_min_load.is_none() {
+            return Ok(0.0);
+        }
+        let (running, rated_power) = match (&self.genset_fleet, &self.genset_device) {
+            (Some(fleet), _) => {
+                let status = fleet.status();
+                (status.running_units > 0, Some(status.loadable_capacity))
+            }
+            (None, Some(genset)) => (genset.lock().map_err(|_| "Mutex poisoned".to_string())?.get_cached_status().running, None),
+            (None, None) => (false, None),
+        };
+        let battery_headroom = if battery_soc < self.pv_curtailer.full_soc() && self.battery_online() {
+            (self.battery_limiter.max_charge_power() + setpoint).max(0.0)
+        } else {
+            0.0
+        };
+        let inputs = MinLoadInputs {
+            running,
+            loaded: self.genset_loadable(),
+            genset_power: generator_power,
+            rated_power,
+            battery_headroom,
+        };
+        let action = match self.genset_min_load {
+            Some(ref mut min_load) => min_load.update(Instant::now(), inputs),
+            None => return Ok(0.0),
+        };
+
+        // A cap held by the import limit stays in force
+        let import_capping = self.import_limiter.as_ref().is_some_and(|l| l.status().charger_curtailment > 0.0);
+        if action.release_chargers && self.charger_cap.is_some() && !import_capping {
+            self.reduce_charger_power(self.active_charger_count() as f32 * self.config.max_charger_power)?;
+        }
+        Ok(action.battery_absorb)
+    }
+
+    /// Apply the frequency-watt and volt-watt responses to a battery setpoint
+    ///
+    /// # Arguments
+    /// * `power` - Battery setpoint in kW (positive: discharging)
+    ///
+    /// # Returns
+    /// Adjusted battery setpoint in kW
+    fn apply_grid_support(&mut self, power: f32) -> f32 {
+        let frequency = self.measured_frequency();
+        let voltage = self.measured_ac_voltage();
+        match self.grid_support {
+            Some(ref mut support) => {
+                let response = support.update(Instant::now(), frequency, voltage);
+                let power = power + response.power_offset;
+                response.power_cap.map(|cap| power.min(cap)).unwrap_or(power)
+            }
+            None => power,
+        }
+    }
+
+    /// Send the corrected battery setpoint when it differs from the dispatch or did last cycle
+    ///
+    /// # Arguments
+    /// * `base` - Battery setpoint from the dispatch in kW
+    /// * `target` - Corrected battery setpoint in kW
+    ///
+    /// # Returns
+    /// Result indicating success or battery control error
+    fn correct_battery_power(&mut self, base: f32, target: f32) -> Result<(), String> {
+        let correction = target - base;
+        if correction.abs() > 0.01 || self.battery_correction.abs() > 0.01 {
+            self.set_battery_power(target)?;
+        }
+        self.battery_correction = correction;
+        Ok(())
+    }
+
+    /// Hold a loadable genset at its present output plus the power it should still cover
+    ///
+    /// # Arguments
+    /// * `residual` - Power left for the genset after PV and battery in kW (negative: back off)
+    ///
+    /// # Returns
+    /// Result indicating success or generator control error
+    fn load_genset(&mut self, residual: f32) -> Result<(), String> {
+        if !self.genset_loadable() {
+            return Ok(());
+        }
+        self.set_generator_power((self.generator_output + residual).max(0.0))
+    }
+
+    /// Whether a genset may carry load
+    fn genset_loadable(&self) -> bool {
+        match self.genset_fleet {
+            Some(ref fleet) => fleet.is_loadable(),
+            None => self.genset_controller.is_loadable()
+                && self.sync_check.as_ref().map(|s| s.is_paralleled()).unwrap_or(true),
+        }
+    }
+
+    /// Compare the genset output with the PCS AC bus ahead of paralleling
+    ///
+    /// # Returns
+    /// Result indicating success or device access error
+    fn run_sync_check(&mut self) -> Result<(), String> {
+        if self.sync_check.is_none() {
+            return Ok(());
+        }
+        // Missing or failed bus reads are passed on as unknown, never as a dead bus
+        let (bus_voltage, bus_frequency) = self.bus_measurement.unzip();
+        if let Some(ref mut fleet) = self.genset_fleet {
+            fleet.update_sync(Instant::now(), bus_voltage, bus_frequency);
+            return Ok(());
+        }
+        let genset = match self.genset_device {
+            Some(ref genset) => genset.lock().map_err(|_| "Mutex poisoned".to_string())?.get_cached_status(),
+            None => return Ok(()),
+        };
+        let inputs = SyncInputs {
+            genset_running: genset.running,
+            genset_voltage: genset.voltage,
+            genset_frequency: genset.frequency,
+            bus_voltage,
+            bus_frequency,
+            // Neither the genset nor the PCS reports a phase angle yet
+            phase_angle: None,
+        };
+        if let Some(ref mut sync_check) = self.sync_check {
+            sync_check.update(Instant::now(), inputs);
+        }
+        Ok(())
+    }
+
     /// Reduce total charger power to match available power
     ///
     /// # Arguments
@@ -393,10 +1938,19 @@ impl EmsController {
     /// # Returns
     /// Result indicating success or charger control error
     fn reduce_charger_power(&mut self, max_power: f32) -> Result<(), String> {
+        // Shed whole tiers, lowest priority first, before cutting the remaining chargers equally
+        if self.load_shedder.is_some() {
+            let loads = self.charger_tier_loads()?;
+            if let Some(ref mut shedder) = self.load_shedder {
+                shedder.shed_for(Instant::now(), max_power, loads);
+            }
+        }
+
+        // Shed chargers stay stopped and take no share
         let active_chargers = self.charger_devices.iter()
             .filter(|c| {
                 if let Ok(charger_locked) = c.lock() {
-                    charger_locked.get_cached_status().charging
+                    charger_locked.get_cached_status().charging && !self.charger_shed(&charger_locked.id)
                 } else {
                     false
                 }
@@ -407,12 +1961,14 @@ impl EmsController {
             return Ok(());
         }
 
+        self.charger_cap = Some(max_power);
         let power_per_charger = (max_power / active_chargers as f32).min(self.config.max_charger_power);
 
         for charger in &self.charger_devices {
             let charger_locked = charger.lock().map_err(|_| "Mutex poisoned".to_string())?;
             if charger_locked.get_cached_status().charging {
-                charger_locked.set_power_setpoint(power_per_charger)
+                let power = if self.charger_shed(&charger_locked.id) { 0.0 } else { power_per_charger };
+                charger_locked.set_power_setpoint(power)
                     .map_err(|e| format!("Failed to set charger power: {}", e))?;
             }
         }
@@ -428,6 +1984,8 @@ impl EmsController {
     /// * `generator_power` - Current generator power output in kW
     /// * `charger_power` - Current charger power consumption in kW
     fn update_cached_status(&self, pv_power: f32, battery_power: f32, generator_power: f32, charger_power: f32) {
+        let now = Local::now().naive_local();
+        let load_forecast = self.load_forecaster.forecast(now);
         let total_generation = pv_power + generator_power;
         let total_consumption = charger_power;
         let power_balance = total_generation - total_consumption;
@@ -442,12 +2000,123 @@ impl EmsController {
             })
             .count();
 
+        let genset_control = self.genset_controller.status();
+        let mut faults = Vec::new();
+        if genset_control.locked_out {
+            faults.push(format!("Genset start locked out after {} failed attempts", genset_control.failed_starts));
+        }
+        let genset_fleet = self.genset_fleet.as_ref().map(|f| f.status()).unwrap_or_default();
+        faults.extend(genset_fleet.units.iter()
+            .filter(|u| u.control.locked_out)
+            .map(|u| format!("Genset {} start locked out after {} failed attempts", u.id, u.control.failed_starts)));
+        // With a fleet each unit is checked on its own; the lead's check is shown here
+        let genset_sync = match self.genset_fleet {
+            Some(_) => genset_fleet.units.first().and_then(|u| u.sync.clone()),
+            None => self.sync_check.as_ref().map(|s| s.status()),
+        }.unwrap_or_else(|| SyncCheckStatus {
+            state: "NotConfigured".to_string(),
+            ..Default::default()
+        });
+        // Report a failed sync only while the genset is still waiting to parallel
+        if self.genset_fleet.is_some() {
+            for unit in &genset_fleet.units {
+                if let Some(failure) = unit.sync.as_ref().filter(|s| s.state == "Checking").and_then(|s| s.last_failure.as_ref()) {
+                    faults.push(format!("Genset {} not synchronised with PCS bus: {}", unit.id, failure));
+                }
+            }
+        } else if genset_sync.state == "Checking" {
+            if let Some(ref failure) = genset_sync.last_failure {
+                faults.push(format!("Genset not synchronised with PCS bus: {}", failure));
+            }
+        }
+        let genset_min_load = self.genset_min_load.as_ref().map(|m| m.status()).unwrap_or_default();
+        if genset_min_load.stop_requested {
+            if let Some(ref event) = genset_min_load.last_event {
+                faults.push(event.clone());
+            }
+        }
+        let fuel = self.fuel_manager.status();
+        match self.fuel_manager.alarm() {
+            FuelAlarm::Critical => faults.push(format!("Genset fuel critically low ({:.0}%)", fuel.level)),
+            FuelAlarm::Warning => faults.push(format!("Genset fuel low ({:.0}%)", fuel.level)),
+            FuelAlarm::None => {}
+        }
+        let maintenance = self.maintenance_planner.status(now);
+        faults.extend(maintenance.items.iter()
+            .filter(|i| i.state == "Overdue")
+            .map(|i| format!("Genset {} overdue", i.task)));
+        let contactor = self.contactor.as_ref().map(|c| c.status()).unwrap_or_else(|| ContactorStatus {
+            state: "NotConfigured".to_string(),
+            ..Default::default()
+        });
+        if let Some(ref fault) = contactor.fault {
+            faults.push(format!("Battery contactor fault: {}", fault));
+        }
+        let battery_racks = self.battery_racks.as_ref().map(|r| r.status()).unwrap_or_default();
+        faults.extend(battery_racks.racks.iter()
+            .filter(|r| !r.online)
+            .map(|r| format!("Battery rack {} offline", r.id)));
+        let pcs_group = self.pcs_group.as_ref().map(|g| g.status()).unwrap_or_default();
+        faults.extend(pcs_group.units.iter()
+            .filter(|u| u.faulted)
+            .map(|u| format!("PCS {} in fault", u.id)));
+        let cell_imbalance = self.cell_analytics.status();
+        if cell_imbalance.spread_alarm {
+            faults.push(format!("Battery cell voltage spread {:.0} mV", cell_imbalance.voltage_spread_mv));
+        }
+        let soc_estimate = self.soc_estimator.status();
+        if soc_estimate.divergence_alarm {
+            faults.push(format!(
+                "Battery SOC mismatch: estimated {:.1}%, BMS {:.1}%",
+                soc_estimate.estimated_soc, soc_estimate.bms_soc
+            ));
+        }
+        if self.grid_meter.is_some() && self.grid_measurement.is_none() {
+            faults.push("Grid meter reading unavailable".to_string());
+        }
+        let import_limit = self.import_limiter.as_ref().map(|l| l.status()).unwrap_or_default();
+        let export_limit = self.export_limiter.as_ref().map(|l| l.status()).unwrap_or_default();
+        if import_limit.failsafe {
+            faults.push("Grid meter stale: chargers held at import limit fail-safe cap".to_string());
+        } else if import_limit.over_limit {
+            faults.push(format!("Grid import above {:.0} kW limit", import_limit.import_limit));
+        }
+        if export_limit.failsafe {
+            faults.push("Grid meter stale: PV held at local consumption by the export limit".to_string());
+        } else if export_limit.over_limit {
+            faults.push(format!("Grid export above {:.0} kW limit", export_limit.export_limit));
+        }
+        let load_shedding = match self.load_shedder {
+            Some(ref shedder) => {
+                let shed_loads = self.charger_devices.iter()
+                    .filter_map(|c| c.lock().ok().map(|c| c.id.clone()))
+                    .filter(|id| shedder.is_shed(id))
+                    .collect();
+                shedder.status(shed_loads)
+            }
+            None => LoadSheddingStatus::default(),
+        };
+        let islanding = self.islanding.as_ref().map(|i| i.status()).unwrap_or_else(|| IslandingStatus {
+            state: "NotConfigured".to_string(),
+            ..Default::default()
+        });
+        if let Some(ref fault) = islanding.fault {
+            faults.push(fault.clone());
+        }
+        let grid_support = self.grid_support.as_ref().map(|g| g.status()).unwrap_or_default();
+        if grid_support.ride_through_exceeded {
+            faults.push("Grid voltage or frequency outside ride-through limits".to_string());
+        }
+        if self.fuel_manager.theft_suspected() {
+            faults.push("Unexplained genset fuel level drop".to_string());
+        }
+
         if let Ok(mut status) = self.cached_status.try_borrow_mut() {
             *status = EmsStatus {
                 total_generation,
                 total_consumption,
                 power_balance,
-                grid_power: 0.0, // TODO: Implement grid power monitoring
+                grid_power: self.measured_grid_power().unwrap_or(0.0),
                 battery_power,
                 generator_power,
                 pv_power,
@@ -455,7 +2124,36 @@ impl EmsController {
                 active_chargers,
                 system_mode: format!("{:?}", self.current_mode),
                 system_healthy: true, // TODO: Implement health monitoring
-                faults: vec![], // TODO: Collect system faults
+                faults,
+                tariff: self.tariff_tracker.status(),
+                forecast: ForecastStatus {
+                    pv_energy_24h: self.pv_forecaster.expected_energy(24),
+                    pv_effective_capacity: self.pv_forecaster.effective_capacity(),
+                    load_energy_24h: load_forecast.iter().map(|p| p.mean * 0.25).sum(),
+                    load_peak_p90_24h: load_forecast.iter().map(|p| p.p90).fold(0.0, f32::max),
+                },
+                mpc: self.mpc_planner.as_ref().map(|p| p.status()).unwrap_or_default(),
+                genset_control,
+                fuel,
+                maintenance,
+                soc_estimate,
+                battery_health: self.battery_health.status(),
+                battery_limits: self.battery_limiter.status(),
+                cell_imbalance,
+                contactor,
+                battery_racks,
+                pcs_group,
+                genset_fleet,
+                genset_sync,
+                genset_min_load,
+                curtailment: self.pv_curtailer.status(),
+                grid_meter: self.grid_measurement.clone(),
+                import_limit,
+                export_limit,
+                reactive_power: self.reactive_controller.status(),
+                grid_support,
+                islanding,
+                load_shedding,
             };
         }
     }
@@ -468,6 +2166,46 @@ impl EmsController {
         self.cached_status.borrow().clone()
     }
 
+    /// Get the latest PV production forecast
+    ///
+    /// # Returns
+    /// Forecast points for the next 48 hours at 15-minute resolution
+    pub fn get_pv_forecast(&self) -> Vec<PvForecastPoint> {
+        self.pv_forecaster.forecast().to_vec()
+    }
+
+    /// Get the charging load forecast
+    ///
+    /// # Returns
+    /// Forecast points for the next 24 hours at 15-minute resolution
+    pub fn get_load_forecast(&self) -> Vec<LoadForecastPoint> {
+        self.load_forecaster.forecast(Local::now().naive_local())
+    }
+
+    /// Get the current dispatch plan
+    ///
+    /// # Returns
+    /// Plan steps from the last successful MPC solve (empty if MPC is not configured)
+    pub fn get_dispatch_plan(&self) -> Vec<DispatchPlanStep> {
+        self.mpc_planner.as_ref().map(|p| p.plan().to_vec()).unwrap_or_default()
+    }
+
+    /// Get per-cell data for the heat-map view
+    ///
+    /// # Returns
+    /// Latest per-cell readings with imbalance analytics
+    pub fn get_cell_heatmap(&self) -> CellHeatmap {
+        self.cell_analytics.heatmap()
+    }
+
+    /// Get the daily battery SOH trend
+    ///
+    /// # Returns
+    /// SOH trend points, oldest first
+    pub fn get_soh_trend(&self) -> Vec<SohTrendPoint> {
+        self.battery_health.trend().to_vec()
+    }
+
     /// Check if EMS is currently running
     ///
     /// # Returns
@@ -0,0 +1,274 @@
+// 发电机燃油管理
+// Genset fuel management: consumption estimate from the load curve, refuel and theft detection,
+// remaining runtime projection and low-fuel alarms
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::types::{FuelEvent, FuelStatus, GensetStatus};
+
+/// Maximum number of refuel/theft events kept in memory
+const MAX_EVENTS: usize = 20;
+
+/// Fuel management configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FuelConfig {
+    /// Tank capacity in litres
+    pub tank_capacity: f32,
+    /// Genset rated power in kW
+    pub rated_power: f32,
+    /// Consumption curve as (load % of rating, litres per hour) points, ascending by load
+    pub consumption_curve: Vec<(f32, f32)>,
+    /// Fuel level for the low-fuel warning (0-100%)
+    pub warning_level: f32,
+    /// Fuel level for the critical low-fuel alarm (0-100%)
+    pub critical_level: f32,
+    /// Level rise above which an alarm clears, in percentage points
+    pub alarm_hysteresis: f32,
+    /// Level rise that counts as a refuel, in percentage points
+    pub refuel_threshold: f32,
+    /// Unexplained level drop that counts as suspected theft, in percentage points
+    pub theft_threshold: f32,
+    /// Smoothing factor for the level sensor (0-1)
+    pub level_filter: f32,
+    /// Filtered level change per cycle below which the level has settled after a step, in percentage points
+    pub settle_step: f32,
+}
+
+impl Default for FuelConfig {
+    fn default() -> Self {
+        // Typical 60 kW diesel genset
+        Self {
+            tank_capacity: 500.0,
+            rated_power: 60.0,
+            consumption_curve: vec![(0.0, 1.8), (25.0, 5.4), (50.0, 9.0), (75.0, 12.6), (100.0, 16.5)],
+            warning_level: 25.0,
+            critical_level: 10.0,
+            alarm_hysteresis: 2.0,
+            refuel_threshold: 5.0,
+            theft_threshold: 3.0,
+            level_filter: 0.05,
+            settle_step: 0.05,
+        }
+    }
+}
+
+/// Low-fuel alarm level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum FuelAlarm {
+    #[default]
+    None,
+    Warning,
+    Critical,
+}
+
+/// Genset fuel manager
+#[derive(Debug, Clone)]
+pub struct FuelManager {
+    config: FuelConfig,
+    /// Filtered fuel level (0-100%)
+    level: Option<f32>,
+    /// Level at the last anchor point and litres burned since then
+    reference_level: f32,
+    burned_since_reference: f32,
+    hours_since_reference: f32,
+    consumption_rate: f32,
+    litres_burned: f32,
+    last_load_rate: f32,
+    alarm: FuelAlarm,
+    events: Vec<FuelEvent>,
+    /// Unexplained drop not yet acknowledged or followed by a refuel
+    theft_suspected: bool,
+}
+
+impl FuelManager {
+    /// Create a new fuel manager
+    ///
+    /// # Arguments
+    /// * `config` - Tank, consumption curve and alarm thresholds
+    pub fn new(config: FuelConfig) -> Self {
+        Self {
+            config,
+            level: None,
+            reference_level: 0.0,
+            burned_since_reference: 0.0,
+            hours_since_reference: 0.0,
+            consumption_rate: 0.0,
+            litres_burned: 0.0,
+            last_load_rate: 0.0,
+            alarm: FuelAlarm::None,
+            events: Vec::new(),
+            theft_suspected: false,
+        }
+    }
+
+    /// Fuel consumption at the given output, interpolated from the consumption curve
+    ///
+    /// # Arguments
+    /// * `power` - Genset output in kW
+    ///
+    /// # Returns
+    /// Consumption in litres per hour
+    pub fn consumption_at(&self, power: f32) -> f32 {
+        let load = power / self.config.rated_power.max(1.0) * 100.0;
+        let curve = &self.config.consumption_curve;
+        match curve.iter().position(|&(l, _)| l >= load) {
+            Some(0) => curve[0].1,
+            Some(i) => {
+                let (l0, f0) = curve[i - 1];
+                let (l1, f1) = curve[i];
+                f0 + (f1 - f0) * (load - l0) / (l1 - l0).max(f32::EPSILON)
+            }
+            None => curve.last().map(|&(_, f)| f).unwrap_or(0.0),
+        }
+    }
+
+    fn record_event(&mut self, time: NaiveDateTime, kind: &str, litres: f32) {
+        log::warn!("Fuel event: {} ({:.1} L)", kind, litres);
+        self.events.push(FuelEvent {
+            timestamp: time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            kind: kind.to_string(),
+            litres,
+        });
+        if self.events.len() > MAX_EVENTS {
+            self.events.remove(0);
+        }
+    }
+
+    fn anchor(&mut self, level: f32) {
+        self.reference_level = level;
+        self.burned_since_reference = 0.0;
+        self.hours_since_reference = 0.0;
+    }
+
+    /// Account for one control cycle
+    ///
+    /// # Arguments
+    /// * `time` - Local wall-clock time
+    /// * `status` - Latest genset status
+    /// * `dt_hours` - Time since the previous cycle in hours
+    pub fn update(&mut self, time: NaiveDateTime, status: &GensetStatus, dt_hours: f32) {
+        // Burn estimate from the load curve
+        self.consumption_rate = if status.running { self.consumption_at(status.power_output) } else { 0.0 };
+        if status.running {
+            self.last_load_rate = self.consumption_rate;
+        }
+        let burned = self.consumption_rate * dt_hours;
+        self.litres_burned += burned;
+        self.burned_since_reference += burned;
+        self.hours_since_reference += dt_hours;
+
+        if !(0.0..=100.0).contains(&status.fuel_level) {
+            return;
+        }
+        let previous = self.level;
+        let level = match previous {
+            Some(l) => l + self.config.level_filter * (status.fuel_level - l),
+            None => {
+                self.anchor(status.fuel_level);
+                status.fuel_level
+            }
+        };
+        self.level = Some(level);
+        // The filter spreads a step over many cycles; wait for it to settle and record the step once
+        let settled = previous.map(|p| (level - p).abs() < self.config.settle_step).unwrap_or(true);
+
+        // Compare the measured level with the level expected from the burn estimate
+        let capacity = self.config.tank_capacity.max(1.0);
+        let expected = self.reference_level - self.burned_since_reference / capacity * 100.0;
+        let deviation = level - expected;
+        if deviation >= self.config.refuel_threshold {
+            if settled {
+                self.record_event(time, "Refuel", deviation * capacity / 100.0);
+                self.theft_suspected = false;
+                self.anchor(level);
+            }
+        } else if -deviation >= self.config.theft_threshold {
+            if settled {
+                let kind = if status.running { "Unexplained drop" } else { "Suspected theft" };
+                self.record_event(time, kind, -deviation * capacity / 100.0);
+                self.theft_suspected = true;
+                self.anchor(level);
+            }
+        } else if status.running && self.hours_since_reference >= 1.0 && deviation.abs() < self.config.theft_threshold / 2.0 {
+            // Re-anchor periodically so the burn model does not drift
+            self.anchor(level);
+        }
+
+        self.update_alarm(level);
+    }
+
+    fn update_alarm(&mut self, level: f32) {
+        let c = &self.config;
+        // Raise immediately; clear only once the level rises past threshold + hysteresis
+        let alarm = if level <= c.critical_level
+            || (self.alarm == FuelAlarm::Critical && level < c.critical_level + c.alarm_hysteresis)
+        {
+            FuelAlarm::Critical
+        } else if level <= c.warning_level
+            || (self.alarm != FuelAlarm::None && level < c.warning_level + c.alarm_hysteresis)
+        {
+            FuelAlarm::Warning
+        } else {
+            FuelAlarm::None
+        };
+        if alarm != self.alarm {
+            log::warn!("Fuel alarm {:?} -> {:?} at {:.1}%", self.alarm, alarm, level);
+            self.alarm = alarm;
+        }
+    }
+
+    /// Current low-fuel alarm
+    pub fn alarm(&self) -> FuelAlarm {
+        self.alarm
+    }
+
+    /// Multiplier applied to genset fuel cost in dispatch planning
+    pub fn genset_cost_factor(&self) -> f32 {
+        match self.alarm {
+            FuelAlarm::None => 1.0,
+            FuelAlarm::Warning => 2.0,
+            FuelAlarm::Critical => 4.0,
+        }
+    }
+
+    /// Whether an unexplained drop is awaiting acknowledgement (cleared by the next refuel)
+    pub fn theft_suspected(&self) -> bool {
+        self.theft_suspected
+    }
+
+    /// Acknowledge an unexplained level drop
+    pub fn acknowledge_theft(&mut self) {
+        if self.theft_suspected {
+            log::info!("Fuel level drop acknowledged");
+            self.theft_suspected = false;
+        }
+    }
+
+    /// Projected runtime left at the current (or last) load
+    ///
+    /// # Returns
+    /// Hours of runtime, or None if no consumption rate is known
+    pub fn runtime_left(&self) -> Option<f32> {
+        let rate = if self.consumption_rate > 0.0 { self.consumption_rate } else { self.last_load_rate };
+        let level = self.level?;
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(level / 100.0 * self.config.tank_capacity / rate)
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> FuelStatus {
+        let level = self.level.unwrap_or(0.0);
+        FuelStatus {
+            level,
+            litres: level / 100.0 * self.config.tank_capacity,
+            consumption_rate: self.consumption_rate,
+            litres_burned: self.litres_burned,
+            runtime_left_hours: self.runtime_left(),
+            alarm: format!("{:?}", self.alarm),
+            events: self.events.clone(),
+        }
+    }
+}
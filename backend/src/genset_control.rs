@@ -0,0 +1,425 @@
+// 发电机启停控制
+// Genset start/stop state machine: SOC and power hysteresis, warm-up, minimum run time,
+// cooldown, start-failure detection and lockout
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::fuel::FuelAlarm;
+use crate::types::{GensetControlStatus, GensetStatus};
+
+/// Genset controller configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GensetControlConfig {
+    /// Start when battery SOC falls to or below this level (0-100%)
+    pub start_soc: f32,
+    /// Allow stopping once battery SOC reaches this level (0-100%)
+    pub stop_soc: f32,
+    /// Start when the power deficit exceeds this level in kW
+    pub start_deficit: f32,
+    /// Allow stopping once the power deficit is below this level in kW
+    pub stop_deficit: f32,
+    /// How long a start condition must persist before starting, in seconds
+    pub start_delay: u64,
+    /// Unloaded warm-up time after a successful start, in seconds
+    pub warmup_time: u64,
+    /// Minimum run time before a stop is allowed, in seconds
+    pub min_run_time: u64,
+    /// Unloaded cooldown time before the engine is stopped, in seconds
+    pub cooldown_time: u64,
+    /// Time allowed for the engine to reach rated frequency, in seconds
+    pub start_timeout: u64,
+    /// Delay between start attempts, in seconds
+    pub retry_delay: u64,
+    /// Consecutive failed starts before lockout
+    pub max_start_attempts: u32,
+    /// Lockout duration after repeated failed starts, in seconds
+    pub lockout_time: u64,
+    /// Nominal output frequency in Hz
+    pub nominal_frequency: f32,
+    /// Allowed frequency deviation for a successful start, in Hz
+    pub frequency_tolerance: f32,
+}
+
+impl Default for GensetControlConfig {
+    fn default() -> Self {
+        Self {
+            start_soc: 20.0,
+            stop_soc: 60.0,
+            start_deficit: 40.0,
+            stop_deficit: 5.0,
+            start_delay: 10,
+            warmup_time: 120,
+            min_run_time: 1800,
+            cooldown_time: 180,
+            start_timeout: 30,
+            retry_delay: 20,
+            max_start_attempts: 3,
+            lockout_time: 1800,
+            nominal_frequency: 50.0,
+            frequency_tolerance: 2.5,
+        }
+    }
+}
+
+/// Genset controller state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GensetState {
+    Stopped,      // Engine off, ready to start
+    Starting,     // Start issued, waiting for rated frequency
+    WarmingUp,    // Running unloaded
+    Running,      // Running and available for load
+    CoolingDown,  // Running unloaded before stop
+    LockedOut,    // Too many failed starts
+}
+
+/// Command for the EMS to send to the genset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GensetAction {
+    None,
+    Start,
+    Load,
+    Unload,
+    Stop,
+}
+
+/// Demand signals evaluated by the controller each cycle
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GensetDemand {
+    /// Battery state of charge (0-100%)
+    pub soc: f32,
+    /// Power deficit not covered by PV and battery in kW
+    pub deficit: f32,
+    /// Explicit run request (dispatch plan); overrides the thresholds when set
+    pub request: Option<bool>,
+    /// Low-fuel alarm; reduces genset reliance when raised
+    pub fuel_alarm: FuelAlarm,
+    /// The genset cannot be kept above its minimum load; stop regardless of run time
+    pub low_load_stop: bool,
+    /// Maintenance exercise run: run the engine unloaded, ignoring the minimum-load stop
+    pub exercise: bool,
+}
+
+/// Genset start/stop controller
+#[derive(Debug, Clone)]
+pub struct GensetController {
+    config: GensetControlConfig,
+    state: GensetState,
+    state_since: Instant,
+    start_condition_since: Option<Instant>,
+    running_since: Option<Instant>,
+    retry_after: Option<Instant>,
+    failed_starts: u32,
+    total_failed_starts: u32,
+    last_event: Option<String>,
+    /// An exercise run is holding the engine unloaded
+    exercising: bool,
+}
+
+impl GensetController {
+    /// Create a new controller
+    ///
+    /// # Arguments
+    /// * `config` - Thresholds and timings
+    pub fn new(config: GensetControlConfig) -> Self {
+        Self {
+            config,
+            state: GensetState::Stopped,
+            state_since: Instant::now(),
+            start_condition_since: None,
+            running_since: None,
+            retry_after: None,
+            failed_starts: 0,
+            total_failed_starts: 0,
+            last_event: None,
+            exercising: false,
+        }
+    }
+
+    fn enter(&mut self, state: GensetState, now: Instant) {
+        log::info!("Genset controller: {:?} -> {:?}", self.state, state);
+        self.state = state;
+        self.state_since = now;
+    }
+
+    fn in_state_for(&self, now: Instant, secs: u64) -> bool {
+        now.duration_since(self.state_since) >= Duration::from_secs(secs)
+    }
+
+    fn at_rated_output(&self, status: &GensetStatus) -> bool {
+        status.running && (status.frequency - self.config.nominal_frequency).abs() <= self.config.frequency_tolerance
+    }
+
+    fn wants_run(&self, demand: &GensetDemand) -> bool {
+        // On low fuel, run only to protect the battery
+        if demand.fuel_alarm != FuelAlarm::None {
+            return demand.soc <= self.config.start_soc;
+        }
+        if demand.exercise {
+            return true;
+        }
+        match demand.request {
+            Some(request) => request,
+            None => demand.soc <= self.config.start_soc || demand.deficit >= self.config.start_deficit,
+        }
+    }
+
+    fn may_stop(&self, demand: &GensetDemand) -> bool {
+        // On critical fuel, stop once the battery is halfway to the normal stop level
+        if demand.fuel_alarm == FuelAlarm::Critical {
+            return demand.soc >= (self.config.start_soc + self.config.stop_soc) / 2.0;
+        }
+        if demand.exercise {
+            return false;
+        }
+        match demand.request {
+            Some(request) => !request,
+            None => demand.soc >= self.config.stop_soc && demand.deficit <= self.config.stop_deficit,
+        }
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `demand` - SOC, deficit and optional explicit request
+    /// * `status` - Latest genset status
+    ///
+    /// # Returns
+    /// Action the EMS should send to the genset
+    pub fn update(&mut self, now: Instant, demand: GensetDemand, status: &GensetStatus) -> GensetAction {
+        let exercise_ended = self.exercising && !demand.exercise;
+        self.exercising = demand.exercise;
+        // An unloaded exercise run never makes the genset too lightly loaded
+        let low_load_stop = demand.low_load_stop && !demand.exercise;
+        match self.state {
+            GensetState::Stopped => {
+                if !self.wants_run(&demand) {
+                    self.start_condition_since = None;
+                    return GensetAction::None;
+                }
+                let since = *self.start_condition_since.get_or_insert(now);
+                let delay_elapsed = demand.request.is_some()
+                    || now.duration_since(since) >= Duration::from_secs(self.config.start_delay);
+                let retry_allowed = self.retry_after.map(|t| now >= t).unwrap_or(true);
+                if delay_elapsed && retry_allowed {
+                    self.start_condition_since = None;
+                    self.enter(GensetState::Starting, now);
+                    GensetAction::Start
+                } else {
+                    GensetAction::None
+                }
+            }
+            GensetState::Starting => {
+                if self.at_rated_output(status) {
+                    self.failed_starts = 0;
+                    self.running_since = Some(now);
+                    self.enter(GensetState::WarmingUp, now);
+                    GensetAction::Unload
+                } else if self.in_state_for(now, self.config.start_timeout) {
+                    self.failed_starts += 1;
+                    self.total_failed_starts += 1;
+                    self.last_event = Some(format!(
+                        "Start attempt {} failed (running={}, frequency={:.1} Hz)",
+                        self.failed_starts, status.running, status.frequency
+                    ));
+                    log::warn!("Genset: {}", self.last_event.as_deref().unwrap_or_default());
+                    if self.failed_starts >= self.config.max_start_attempts {
+                        self.enter(GensetState::LockedOut, now);
+                    } else {
+                        self.retry_after = Some(now + Duration::from_secs(self.config.retry_delay));
+                        self.enter(GensetState::Stopped, now);
+                    }
+                    GensetAction::Stop
+                } else {
+                    GensetAction::None
+                }
+            }
+            GensetState::WarmingUp | GensetState::Running | GensetState::CoolingDown if !status.running => {
+                self.last_event = Some(format!("Unexpected stop while {:?}", self.state));
+                log::warn!("Genset: {}", self.last_event.as_deref().unwrap_or_default());
+                self.running_since = None;
+                self.enter(GensetState::Stopped, now);
+                GensetAction::Stop
+            }
+            GensetState::WarmingUp => {
+                if self.in_state_for(now, self.config.warmup_time) {
+                    self.enter(GensetState::Running, now);
+                    if self.exercising { GensetAction::None } else { GensetAction::Load }
+                } else {
+                    GensetAction::None
+                }
+            }
+            GensetState::Running => {
+                let min_run_elapsed = self.running_since
+                    .map(|t| now.duration_since(t) >= Duration::from_secs(self.config.min_run_time))
+                    .unwrap_or(true);
+                if (min_run_elapsed && self.may_stop(&demand)) || low_load_stop {
+                    self.enter(GensetState::CoolingDown, now);
+                    GensetAction::Unload
+                } else if exercise_ended {
+                    // Still needed after the exercise run: take load
+                    GensetAction::Load
+                } else {
+                    GensetAction::None
+                }
+            }
+            GensetState::CoolingDown => {
+                if self.wants_run(&demand) && !low_load_stop {
+                    // Demand returned during cooldown; take load again
+                    self.enter(GensetState::Running, now);
+                    if self.exercising { GensetAction::None } else { GensetAction::Load }
+                } else if self.in_state_for(now, self.config.cooldown_time) {
+                    self.running_since = None;
+                    self.enter(GensetState::Stopped, now);
+                    GensetAction::Stop
+                } else {
+                    GensetAction::None
+                }
+            }
+            GensetState::LockedOut => {
+                if self.in_state_for(now, self.config.lockout_time) {
+                    self.failed_starts = 0;
+                    self.retry_after = None;
+                    self.enter(GensetState::Stopped, now);
+                }
+                GensetAction::None
+            }
+        }
+    }
+
+    /// Whether the genset may carry load (not while an exercise run holds it unloaded)
+    pub fn is_loadable(&self) -> bool {
+        self.state == GensetState::Running && !self.exercising
+    }
+
+    /// Clear a lockout manually
+    pub fn reset_lockout(&mut self) {
+        if self.state == GensetState::LockedOut {
+            self.failed_starts = 0;
+            self.retry_after = None;
+            self.enter(GensetState::Stopped, Instant::now());
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> GensetControlStatus {
+        GensetControlStatus {
+            state: format!("{:?}", self.state),
+            seconds_in_state: self.state_since.elapsed().as_secs(),
+            run_seconds: self.running_since.map(|t| t.elapsed().as_secs()).unwrap_or(0),
+            failed_starts: self.failed_starts,
+            total_failed_starts: self.total_failed_starts,
+            locked_out: self.state == GensetState::LockedOut,
+            last_event: self.last_event.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> GensetController {
+        GensetController::new(GensetControlConfig {
+            warmup_time: 60,
+            min_run_time: 600,
+            cooldown_time: 120,
+            start_timeout: 30,
+            retry_delay: 20,
+            max_start_attempts: 3,
+            lockout_time: 1800,
+            ..Default::default()
+        })
+    }
+
+    fn request(run: bool) -> GensetDemand {
+        GensetDemand { soc: 50.0, request: Some(run), ..Default::default() }
+    }
+
+    fn running() -> GensetStatus {
+        GensetStatus { running: true, frequency: 50.0, ..Default::default() }
+    }
+
+    fn at(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    /// Start, reach rated output at 1 s and finish warming up at 61 s
+    fn start_running(c: &mut GensetController, start: Instant, demand: GensetDemand) {
+        assert_eq!(c.update(start, demand, &GensetStatus::default()), GensetAction::Start);
+        assert_eq!(c.update(at(start, 1), demand, &running()), GensetAction::Unload);
+        let action = c.update(at(start, 61), demand, &running());
+        assert_eq!(c.state, GensetState::Running);
+        assert_eq!(action, if demand.exercise { GensetAction::None } else { GensetAction::Load });
+    }
+
+    #[test]
+    fn repeated_failed_starts_lock_out_until_the_lockout_time() {
+        let mut c = controller();
+        let start = Instant::now();
+        let stopped = GensetStatus::default();
+        let mut t = 0;
+        for attempt in 1..=3 {
+            assert_eq!(c.update(at(start, t), request(true), &stopped), GensetAction::Start);
+            t += 31;
+            assert_eq!(c.update(at(start, t), request(true), &stopped), GensetAction::Stop);
+            assert_eq!(c.status().failed_starts, attempt);
+            if attempt < 3 {
+                // No new attempt before the retry delay
+                assert_eq!(c.update(at(start, t + 10), request(true), &stopped), GensetAction::None);
+                t += 20;
+            }
+        }
+        assert!(c.status().locked_out);
+        assert_eq!(c.update(at(start, t + 1000), request(true), &stopped), GensetAction::None);
+        c.update(at(start, t + 1800), request(true), &stopped);
+        assert!(!c.status().locked_out);
+        assert_eq!(c.update(at(start, t + 1801), request(true), &stopped), GensetAction::Start);
+    }
+
+    #[test]
+    fn stop_waits_for_minimum_run_unless_load_is_too_low() {
+        let mut c = controller();
+        let start = Instant::now();
+        start_running(&mut c, start, request(true));
+        // Minimum run counts from reaching rated output at 1 s
+        assert_eq!(c.update(at(start, 300), request(false), &running()), GensetAction::None);
+        assert_eq!(c.update(at(start, 601), request(false), &running()), GensetAction::Unload);
+        assert_eq!(c.state, GensetState::CoolingDown);
+
+        let mut c = controller();
+        start_running(&mut c, start, request(true));
+        let low_load = GensetDemand { low_load_stop: true, ..request(true) };
+        assert_eq!(c.update(at(start, 100), low_load, &running()), GensetAction::Unload);
+    }
+
+    #[test]
+    fn cooldown_stops_after_its_time_or_takes_load_again() {
+        let mut c = controller();
+        let start = Instant::now();
+        start_running(&mut c, start, request(true));
+        assert_eq!(c.update(at(start, 700), request(false), &running()), GensetAction::Unload);
+        // Demand returning during cooldown loads the running engine again
+        assert_eq!(c.update(at(start, 750), request(true), &running()), GensetAction::Load);
+        assert_eq!(c.state, GensetState::Running);
+        assert_eq!(c.update(at(start, 800), request(false), &running()), GensetAction::Unload);
+        assert_eq!(c.update(at(start, 900), request(false), &running()), GensetAction::None);
+        assert_eq!(c.update(at(start, 920), request(false), &running()), GensetAction::Stop);
+        assert_eq!(c.state, GensetState::Stopped);
+    }
+
+    #[test]
+    fn exercise_run_stays_unloaded() {
+        let mut c = controller();
+        let start = Instant::now();
+        let exercise = GensetDemand { exercise: true, low_load_stop: true, ..request(false) };
+        start_running(&mut c, start, exercise);
+        assert!(!c.is_loadable());
+        // Neither the plan nor the minimum-load stop ends the run
+        assert_eq!(c.update(at(start, 700), exercise, &running()), GensetAction::None);
+        // Still requested once the exercise run ends: take load
+        assert_eq!(c.update(at(start, 710), request(true), &running()), GensetAction::Load);
+        assert!(c.is_loadable());
+    }
+}
@@ -0,0 +1,418 @@
+// 多机组发电机群控
+// Genset fleet: lead/lag selection, load-dependent start and stop of lag units,
+// lead rotation by engine hours and fallback to a standby unit when the lead fails to start
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::devices::genset::GensetDevice;
+use crate::fuel::FuelAlarm;
+use crate::genset_control::{GensetAction, GensetControlConfig, GensetController, GensetDemand};
+use crate::sync_check::{SyncCheckConfig, SyncChecker, SyncInputs};
+use crate::types::{GensetFleetStatus, GensetStatus, GensetUnitStatus};
+
+/// One genset in the fleet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GensetUnitConfig {
+    /// Genset identifier (a unit with the site genset_id reuses the primary genset)
+    pub id: String,
+    /// Modbus host
+    pub host: String,
+    /// Modbus port
+    pub port: u16,
+    /// Rated power in kW
+    pub rated_power: f32,
+}
+
+/// Genset fleet configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GensetFleetConfig {
+    /// Gensets in the fleet
+    pub units: Vec<GensetUnitConfig>,
+    /// Start the next lag unit when running units exceed this load (% of their rating)
+    pub lag_start_load: f32,
+    /// Stop the last lag unit when the remaining units would run below this load (% of their rating)
+    pub lag_stop_load: f32,
+    /// How long the start load must persist, in seconds
+    pub lag_start_delay: u64,
+    /// How long the stop load must persist, in seconds
+    pub lag_stop_delay: u64,
+    /// Interval between lead rotations by engine hours, in hours
+    pub rotation_interval: f32,
+}
+
+impl Default for GensetFleetConfig {
+    fn default() -> Self {
+        Self {
+            units: Vec::new(),
+            lag_start_load: 80.0,
+            lag_stop_load: 60.0,
+            lag_start_delay: 30,
+            lag_stop_delay: 300,
+            rotation_interval: 168.0,
+        }
+    }
+}
+
+/// Runtime state of one genset
+#[derive(Debug)]
+struct GensetUnit {
+    config: GensetUnitConfig,
+    device: Arc<Mutex<GensetDevice>>,
+    controller: GensetController,
+    status: GensetStatus,
+    /// Whether the last read succeeded, so `status` is current
+    status_valid: bool,
+    failed_starts: u32,
+    setpoint: f32,
+    /// Sync check with the PCS bus (None: loaded without checking)
+    sync: Option<SyncChecker>,
+}
+
+impl GensetUnit {
+    /// Whether the unit may carry load: running and, with a sync check, paralleled
+    fn loadable(&self) -> bool {
+        self.controller.is_loadable() && self.sync.as_ref().map(|s| s.is_paralleled()).unwrap_or(true)
+    }
+
+    /// Send a controller action to the genset
+    fn execute(&mut self, action: GensetAction) -> Result<(), String> {
+        let mut genset_locked = self.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+        match action {
+            GensetAction::Start => genset_locked.start_engine()
+                .map_err(|e| format!("Failed to start genset {}: {:?}", self.config.id, e)),
+            // The EMS shares the fleet setpoint across the loadable units every cycle
+            GensetAction::Load => Ok(()),
+            GensetAction::Unload => {
+                self.setpoint = 0.0;
+                genset_locked.set_power_setpoint(0.0)
+                    .map_err(|e| format!("Failed to unload genset {}: {:?}", self.config.id, e))
+            }
+            GensetAction::Stop => {
+                self.setpoint = 0.0;
+                genset_locked.stop_engine()
+                    .map_err(|e| format!("Failed to stop genset {}: {:?}", self.config.id, e))
+            }
+            GensetAction::None => Ok(()),
+        }
+    }
+}
+
+/// Genset fleet controller
+#[derive(Debug)]
+pub struct GensetFleet {
+    config: GensetFleetConfig,
+    control: GensetControlConfig,
+    units: Vec<GensetUnit>,
+    /// Unit indices in lead/lag order (lead first)
+    order: Vec<usize>,
+    /// Units requested to run (lead plus lags)
+    staged: usize,
+    stage_condition_since: Option<Instant>,
+    last_rotation: Option<Instant>,
+    last_event: Option<String>,
+    /// Sync check applied to every unit (None: units loaded without checking)
+    sync_check: Option<SyncCheckConfig>,
+}
+
+impl GensetFleet {
+    /// Create an empty fleet
+    ///
+    /// # Arguments
+    /// * `config` - Lag staging and rotation parameters
+    /// * `control` - Start/stop thresholds and timings applied to every unit
+    pub fn new(config: GensetFleetConfig, control: GensetControlConfig) -> Self {
+        Self {
+            config,
+            control,
+            units: Vec::new(),
+            order: Vec::new(),
+            staged: 1,
+            stage_condition_since: None,
+            last_rotation: None,
+            last_event: None,
+            sync_check: None,
+        }
+    }
+
+    /// Check each unit's synchronisation with the PCS bus before it takes load
+    ///
+    /// # Arguments
+    /// * `config` - Voltage, frequency and phase tolerances and dwell time
+    pub fn set_sync_check(&mut self, config: SyncCheckConfig) {
+        for unit in &mut self.units {
+            unit.sync = Some(SyncChecker::new(config.clone()));
+        }
+        self.sync_check = Some(config);
+    }
+
+    /// Compare every unit's output with the PCS AC bus
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `bus_voltage` - PCS AC bus voltage in V, None without a fresh reading
+    /// * `bus_frequency` - PCS AC bus frequency in Hz, None without a fresh reading
+    pub fn update_sync(&mut self, now: Instant, bus_voltage: Option<f32>, bus_frequency: Option<f32>) {
+        for unit in &mut self.units {
+            let status = &unit.status;
+            if let Some(ref mut sync) = unit.sync {
+                sync.update(now, SyncInputs {
+                    genset_running: status.running,
+                    genset_voltage: status.voltage,
+                    genset_frequency: status.frequency,
+                    bus_voltage,
+                    bus_frequency,
+                    phase_angle: None,
+                });
+            }
+        }
+    }
+
+    /// Add a genset to the end of the lead/lag order
+    ///
+    /// # Arguments
+    /// * `config` - Unit rating
+    /// * `device` - Genset device
+    pub fn add_unit(&mut self, config: GensetUnitConfig, device: Arc<Mutex<GensetDevice>>) {
+        self.order.push(self.units.len());
+        self.units.push(GensetUnit {
+            config,
+            device,
+            controller: GensetController::new(self.control.clone()),
+            status: GensetStatus::default(),
+            status_valid: false,
+            failed_starts: 0,
+            setpoint: 0.0,
+            sync: self.sync_check.clone().map(SyncChecker::new),
+        });
+    }
+
+    /// Read every genset
+    ///
+    /// # Returns
+    /// Result containing the summed output of the running gensets in kW or device access error
+    pub fn read(&mut self) -> Result<f32, String> {
+        let mut power = 0.0;
+        for unit in &mut self.units {
+            let mut genset_locked = unit.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            match genset_locked.read_status() {
+                Ok(status) => {
+                    unit.status = status;
+                    unit.status_valid = true;
+                }
+                Err(e) => {
+                    log::warn!("Failed to read genset {} status: {}", unit.config.id, e);
+                    unit.status = genset_locked.get_cached_status();
+                    unit.status_valid = false;
+                }
+            }
+            if unit.status.running {
+                power += unit.status.power_output;
+            }
+        }
+        Ok(power)
+    }
+
+    /// Whether a genset is part of the fleet
+    ///
+    /// # Arguments
+    /// * `id` - Genset identifier
+    pub fn contains(&self, id: &str) -> bool {
+        self.units.iter().any(|u| u.config.id == id)
+    }
+
+    /// Status of a genset from this cycle's read
+    ///
+    /// # Arguments
+    /// * `id` - Genset identifier
+    ///
+    /// # Returns
+    /// The status, or None if the genset is not in the fleet or its read failed
+    pub fn reading(&self, id: &str) -> Option<GensetStatus> {
+        self.units.iter()
+            .find(|u| u.config.id == id && u.status_valid)
+            .map(|u| u.status.clone())
+    }
+
+    /// Whether any genset may carry load
+    pub fn is_loadable(&self) -> bool {
+        self.units.iter().any(|u| u.loadable())
+    }
+
+    /// Move a requested unit that failed to start to the back of the order so a standby unit takes its place
+    fn fall_back(&mut self) {
+        for position in 0..self.staged.min(self.order.len()) {
+            let i = self.order[position];
+            let status = self.units[i].controller.status();
+            let failed = status.failed_starts > self.units[i].failed_starts || status.locked_out;
+            self.units[i].failed_starts = status.failed_starts;
+            let unreachable = !self.units[i].status_valid;
+            if (failed || unreachable) && self.order.len() > self.staged && !self.units[i].controller.is_loadable() {
+                self.order.remove(position);
+                self.order.push(i);
+                let role = if position == 0 { "Lead" } else { "Lag" };
+                let reason = if unreachable { "is unreachable" } else { "failed to start" };
+                let event = format!(
+                    "{} genset {} {}, falling back to {}",
+                    role, self.units[i].config.id, reason, self.units[self.order[position]].config.id
+                );
+                log::warn!("{}", event);
+                self.last_event = Some(event);
+                return;
+            }
+        }
+    }
+
+    /// Rotate the lead to the unit with the fewest engine hours while the fleet is stopped
+    fn rotate(&mut self, now: Instant) {
+        let due = self.last_rotation
+            .map(|t| now.duration_since(t).as_secs_f32() / 3600.0 >= self.config.rotation_interval)
+            .unwrap_or(true);
+        if !due || self.units.iter().any(|u| u.status.running) {
+            return;
+        }
+        self.last_rotation = Some(now);
+        let units = &self.units;
+        let previous = self.order.first().copied();
+        self.order.sort_by_key(|&i| units[i].status.engine_hours);
+        if previous.is_some() && self.order.first().copied() != previous {
+            let event = format!("Lead genset rotated to {}", self.units[self.order[0]].config.id);
+            log::info!("{}", event);
+            self.last_event = Some(event);
+        }
+    }
+
+    /// Stage lag units up or down from the fleet load
+    fn stage(&mut self, now: Instant, deficit: f32) {
+        if !self.units[self.order[0]].loadable() {
+            self.staged = 1;
+            self.stage_condition_since = None;
+            return;
+        }
+        let running: Vec<usize> = self.order.iter().copied().filter(|&i| self.units[i].loadable()).collect();
+        let capacity: f32 = running.iter().map(|&i| self.units[i].config.rated_power).sum::<f32>().max(f32::EPSILON);
+        let load = running.iter().map(|&i| self.units[i].status.power_output).sum::<f32>() + deficit.max(0.0);
+        let last_lag = (self.staged > 1)
+            .then(|| self.order[self.staged - 1])
+            .filter(|&i| self.units[i].loadable());
+        let reduced = capacity - last_lag.map(|i| self.units[i].config.rated_power).unwrap_or(capacity);
+
+        // Stage further only once every requested unit is carrying load
+        let step_up = self.staged < self.units.len()
+            && running.len() >= self.staged
+            && load / capacity * 100.0 > self.config.lag_start_load;
+        let step_down = last_lag.is_some() && reduced > 0.0 && load / reduced * 100.0 < self.config.lag_stop_load;
+        let delay = if step_up { self.config.lag_start_delay } else { self.config.lag_stop_delay };
+        if !(step_up || step_down) {
+            self.stage_condition_since = None;
+            return;
+        }
+        let since = *self.stage_condition_since.get_or_insert(now);
+        if now.duration_since(since) >= Duration::from_secs(delay) {
+            self.stage_condition_since = None;
+            self.staged = if step_up { self.staged + 1 } else { self.staged - 1 };
+            log::info!("Genset fleet load {:.0} kW: {} unit(s) requested", load, self.staged);
+        }
+    }
+
+    /// Evaluate one control cycle and send the resulting commands
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `demand` - Site demand applied to the lead genset
+    ///
+    /// # Returns
+    /// Result indicating success or generator control error
+    pub fn update(&mut self, now: Instant, demand: GensetDemand) -> Result<(), String> {
+        if self.units.is_empty() {
+            return Ok(());
+        }
+        self.fall_back();
+        self.rotate(now);
+        self.stage(now, demand.deficit);
+
+        for position in 0..self.order.len() {
+            let unit = &mut self.units[self.order[position]];
+            // Lags follow the staging decision rather than the site thresholds
+            let unit_demand = if position == 0 {
+                demand
+            } else {
+                GensetDemand {
+                    request: Some(position < self.staged),
+                    fuel_alarm: FuelAlarm::None,
+                    exercise: false,
+                    ..demand
+                }
+            };
+            let action = unit.controller.update(now, unit_demand, &unit.status);
+            // One unit's command failure must not stop the others from being commanded
+            if let Err(e) = unit.execute(action) {
+                log::warn!("{}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Split a fleet power setpoint across the loadable gensets by rating
+    ///
+    /// # Arguments
+    /// * `power` - Fleet power setpoint in kW
+    ///
+    /// # Returns
+    /// Result indicating success or generator control error
+    pub fn set_power(&mut self, power: f32) -> Result<(), String> {
+        let rating: f32 = self.units.iter()
+            .filter(|u| u.loadable())
+            .map(|u| u.config.rated_power)
+            .sum();
+        if rating <= 0.0 {
+            return Ok(());
+        }
+        for unit in self.units.iter_mut().filter(|u| u.loadable()) {
+            let share = power.clamp(0.0, rating) * unit.config.rated_power / rating;
+            let mut genset_locked = unit.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            genset_locked.set_power_setpoint(share)
+                .map_err(|e| format!("Failed to set genset {} power: {:?}", unit.config.id, e))?;
+            unit.setpoint = share;
+        }
+        Ok(())
+    }
+
+    /// Clear start lockouts on every genset
+    pub fn reset_lockouts(&mut self) {
+        for unit in &mut self.units {
+            unit.controller.reset_lockout();
+            unit.failed_starts = 0;
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> GensetFleetStatus {
+        GensetFleetStatus {
+            lead: self.order.first().map(|&i| self.units[i].config.id.clone()),
+            requested_units: self.staged,
+            running_units: self.units.iter().filter(|u| u.status.running).count(),
+            loadable_capacity: self.units.iter()
+                .filter(|u| u.loadable())
+                .map(|u| u.config.rated_power)
+                .sum(),
+            last_event: self.last_event.clone(),
+            units: self.order.iter().enumerate()
+                .map(|(position, &i)| {
+                    let unit = &self.units[i];
+                    GensetUnitStatus {
+                        id: unit.config.id.clone(),
+                        role: if position == 0 { "Lead" } else { "Lag" }.to_string(),
+                        rated_power: unit.config.rated_power,
+                        power: if unit.status.running { unit.status.power_output } else { 0.0 },
+                        setpoint: unit.setpoint,
+                        engine_hours: unit.status.engine_hours,
+                        control: unit.controller.status(),
+                        sync: unit.sync.as_ref().map(|s| s.status()),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
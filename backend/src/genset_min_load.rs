@@ -0,0 +1,177 @@
+// 发电机最低负载保护
+// Genset minimum-load protection against wet stacking: raise battery charging and charger
+// allocations to keep the genset above a minimum load, track low-load time and stop the
+// genset when it cannot be kept loaded
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::types::GensetMinLoadStatus;
+
+/// Minimum-load protection configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MinLoadConfig {
+    /// Minimum genset load in % of rating
+    pub min_load_pct: f32,
+    /// Genset rating in kW (a fleet uses the rating of its loadable units instead)
+    pub rated_power: f32,
+    /// Load held above the minimum in kW
+    pub margin: f32,
+    /// Fraction of the shortfall added to the battery charge each cycle
+    pub gain: f32,
+    /// Continuous time below the minimum before the genset is stopped, in seconds
+    pub low_load_timeout: u64,
+}
+
+impl Default for MinLoadConfig {
+    fn default() -> Self {
+        Self {
+            min_load_pct: 30.0,
+            rated_power: 100.0,
+            margin: 2.0,
+            gain: 0.5,
+            low_load_timeout: 900,
+        }
+    }
+}
+
+/// Measurements and headroom evaluated each cycle
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinLoadInputs {
+    /// Whether a genset engine is running
+    pub running: bool,
+    /// Whether the genset is carrying load
+    pub loaded: bool,
+    /// Genset output in kW
+    pub genset_power: f32,
+    /// Rating of the gensets carrying load in kW (None: configured rating)
+    pub rated_power: Option<f32>,
+    /// Additional battery charge power available in kW
+    pub battery_headroom: f32,
+}
+
+/// Correction for the EMS to apply
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MinLoadAction {
+    /// Battery charge power to add in kW
+    pub battery_absorb: f32,
+    /// Restore full charger allocations because the battery cannot absorb the shortfall
+    pub release_chargers: bool,
+}
+
+/// Genset minimum-load controller
+#[derive(Debug, Clone)]
+pub struct GensetMinLoad {
+    config: MinLoadConfig,
+    last_update: Option<Instant>,
+    min_load: f32,
+    load_pct: f32,
+    battery_absorb: f32,
+    below_since: Option<Instant>,
+    seconds_below_min: f32,
+    low_load_events: u32,
+    stop_requested: bool,
+    last_event: Option<String>,
+}
+
+impl GensetMinLoad {
+    /// Create a new controller
+    ///
+    /// # Arguments
+    /// * `config` - Minimum load, rating and timeout
+    pub fn new(config: MinLoadConfig) -> Self {
+        Self {
+            config,
+            last_update: None,
+            min_load: 0.0,
+            load_pct: 0.0,
+            battery_absorb: 0.0,
+            below_since: None,
+            seconds_below_min: 0.0,
+            low_load_events: 0,
+            stop_requested: false,
+            last_event: None,
+        }
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `inputs` - Genset load and battery headroom
+    ///
+    /// # Returns
+    /// Battery and charger correction to raise the genset load
+    pub fn update(&mut self, now: Instant, inputs: MinLoadInputs) -> MinLoadAction {
+        let dt = self.last_update.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+
+        if !inputs.running {
+            // A pending stop has taken effect
+            self.stop_requested = false;
+        }
+        if !inputs.loaded {
+            // Stopped, warming up or cooling down: nothing to protect
+            self.battery_absorb = 0.0;
+            self.load_pct = 0.0;
+            self.below_since = None;
+            return MinLoadAction::default();
+        }
+
+        let rating = inputs.rated_power.unwrap_or(self.config.rated_power).max(f32::EPSILON);
+        self.min_load = self.config.min_load_pct / 100.0 * rating;
+        self.load_pct = inputs.genset_power / rating * 100.0;
+        let headroom = inputs.battery_headroom.max(0.0);
+
+        // Integrate towards the minimum plus margin; back off once the load is above it
+        let shortfall = self.min_load + self.config.margin - inputs.genset_power;
+        self.battery_absorb = (self.battery_absorb + shortfall * self.config.gain).clamp(0.0, headroom);
+        let battery_exhausted = self.battery_absorb >= headroom - 0.01;
+
+        if inputs.genset_power < self.min_load {
+            self.seconds_below_min += dt;
+            let since = match self.below_since {
+                Some(since) => since,
+                None => {
+                    self.low_load_events += 1;
+                    log::info!("Genset load {:.1}% below minimum {:.0}%", self.load_pct, self.config.min_load_pct);
+                    *self.below_since.insert(now)
+                }
+            };
+            if !self.stop_requested && now.duration_since(since) >= Duration::from_secs(self.config.low_load_timeout) {
+                self.stop_requested = true;
+                self.last_event = Some(format!(
+                    "Genset held below {:.0}% load for {} s (load {:.1}%, battery absorbing {:.1} kW); stopping",
+                    self.config.min_load_pct, self.config.low_load_timeout, self.load_pct, self.battery_absorb
+                ));
+                log::warn!("Genset: {}", self.last_event.as_deref().unwrap_or_default());
+            }
+        } else {
+            self.below_since = None;
+        }
+
+        MinLoadAction {
+            battery_absorb: self.battery_absorb,
+            release_chargers: shortfall > 0.0 && battery_exhausted,
+        }
+    }
+
+    /// Whether the genset should be stopped because it cannot be kept loaded
+    pub fn stop_requested(&self) -> bool {
+        self.stop_requested
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> GensetMinLoadStatus {
+        GensetMinLoadStatus {
+            min_load: self.min_load,
+            load_pct: self.load_pct,
+            battery_absorb: self.battery_absorb,
+            below_min_load: self.below_since.is_some(),
+            seconds_below_min_load: self.seconds_below_min,
+            low_load_events: self.low_load_events,
+            stop_requested: self.stop_requested,
+            last_event: self.last_event.clone(),
+        }
+    }
+}
@@ -0,0 +1,565 @@
+// 电网功率限制
+// Closed-loop site import and export limits on grid meter feedback: PI controllers with anti-windup,
+// battery correction first then charger or PV curtailment, ramp limits, meter fail-safe and violation counters
+
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::types::{ExportLimitStatus, ImportLimitStatus};
+
+/// PI controller with output clamping and conditional-integration anti-windup
+#[derive(Debug, Clone)]
+pub struct PiController {
+    kp: f32,
+    ki: f32,
+    integral: f32,
+}
+
+impl PiController {
+    /// Create a new controller
+    ///
+    /// # Arguments
+    /// * `kp` - Proportional gain
+    /// * `ki` - Integral gain per second
+    pub fn new(kp: f32, ki: f32) -> Self {
+        Self { kp, ki, integral: 0.0 }
+    }
+
+    /// Advance the controller one step
+    ///
+    /// # Arguments
+    /// * `error` - Control error
+    /// * `dt` - Time step in seconds
+    /// * `min` - Lower output limit
+    /// * `max` - Upper output limit
+    ///
+    /// # Returns
+    /// Clamped controller output
+    pub fn update(&mut self, error: f32, dt: f32, min: f32, max: f32) -> f32 {
+        let unclamped = self.kp * error + self.integral + self.ki * error * dt;
+        // Only integrate while the output is not saturated in the direction of the error
+        let saturated = (unclamped > max && error > 0.0) || (unclamped < min && error < 0.0);
+        if !saturated {
+            self.integral += self.ki * error * dt;
+        }
+        self.integral = self.integral.clamp(min, max);
+        (self.kp * error + self.integral).clamp(min, max)
+    }
+
+    /// Clear the integral term
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+    }
+
+    /// Load the integral term so the output continues from an externally imposed value
+    ///
+    /// # Arguments
+    /// * `output` - Output the controller should continue from
+    pub fn preset(&mut self, output: f32) {
+        self.integral = output;
+    }
+}
+
+/// Move a value toward a target at a limited rate
+fn ramp(current: f32, target: f32, rate: f32, dt: f32) -> f32 {
+    let step = rate * dt;
+    current + (target - current).clamp(-step, step)
+}
+
+/// Whether the last valid meter reading is older than the timeout
+fn meter_stale(last_measurement: Option<Instant>, now: Instant, timeout: u64) -> bool {
+    last_measurement
+        .map(|t| now.duration_since(t).as_secs() > timeout)
+        .unwrap_or(true)
+}
+
+/// Site import limit configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportLimitConfig {
+    /// Contracted maximum grid import in kW
+    pub import_limit: f32,
+    /// Control target below the limit, in kW
+    pub margin: f32,
+    /// Proportional gain (kW correction per kW error)
+    pub kp: f32,
+    /// Integral gain (kW correction per kW error per second)
+    pub ki: f32,
+    /// Maximum rate of change of the battery correction in kW/s
+    pub battery_ramp_rate: f32,
+    /// Maximum rate of change of the charger curtailment in kW/s
+    pub charger_ramp_rate: f32,
+    /// Meter data older than this triggers the fail-safe, in seconds (several control cycles)
+    pub meter_timeout: u64,
+    /// Total charger power cap while the meter is stale, in kW
+    pub failsafe_charger_cap: f32,
+}
+
+impl Default for ImportLimitConfig {
+    fn default() -> Self {
+        Self {
+            import_limit: 100.0,
+            margin: 2.0,
+            kp: 0.5,
+            ki: 0.2,
+            battery_ramp_rate: 20.0,
+            charger_ramp_rate: 10.0,
+            meter_timeout: 15,
+            failsafe_charger_cap: 44.0,
+        }
+    }
+}
+
+/// Measurements and headroom for one control step
+#[derive(Debug, Clone, Copy)]
+pub struct ImportLimitInputs {
+    /// Metered grid power in kW (positive: importing), None if the read failed
+    pub measured: Option<f32>,
+    /// Battery discharge available on top of the dispatched setpoint, in kW
+    pub battery_headroom: f32,
+    /// Power the active chargers could draw uncapped, in kW
+    pub charger_capacity: f32,
+}
+
+/// Corrections for the EMS to apply on top of the dispatch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImportLimitAction {
+    /// Additional battery discharge in kW
+    pub battery_boost: f32,
+    /// Total charger power cap in kW (None: uncapped)
+    pub charger_cap: Option<f32>,
+    /// Chargers were capped last step and may return to full power
+    pub release_chargers: bool,
+}
+
+/// Site import limiter
+#[derive(Debug, Clone)]
+pub struct ImportLimiter {
+    config: ImportLimitConfig,
+    pi: PiController,
+    last_update: Option<Instant>,
+    last_measurement: Option<Instant>,
+    battery_boost: f32,
+    charger_cut: f32,
+    charger_capped: bool,
+    failsafe: bool,
+    over_limit: bool,
+    violations: u32,
+    seconds_over_limit: f32,
+    peak_import: f32,
+}
+
+impl ImportLimiter {
+    /// Create a new limiter
+    ///
+    /// # Arguments
+    /// * `config` - Limit, gains, ramp rates and fail-safe cap
+    pub fn new(config: ImportLimitConfig) -> Self {
+        let pi = PiController::new(config.kp, config.ki);
+        Self {
+            config,
+            pi,
+            last_update: None,
+            last_measurement: None,
+            battery_boost: 0.0,
+            charger_cut: 0.0,
+            charger_capped: false,
+            failsafe: false,
+            over_limit: false,
+            violations: 0,
+            seconds_over_limit: 0.0,
+            peak_import: 0.0,
+        }
+    }
+
+    /// Evaluate one control step
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `inputs` - Metered import and available headroom
+    ///
+    /// # Returns
+    /// Battery and charger corrections
+    pub fn update(&mut self, now: Instant, inputs: ImportLimitInputs) -> ImportLimitAction {
+        let dt = self.last_update.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+
+        let measured = match inputs.measured {
+            Some(measured) => {
+                self.last_measurement = Some(now);
+                if self.failsafe {
+                    // Start from the fail-safe cap and ramp out rather than releasing the chargers at once
+                    log::info!("Grid meter restored, import limit back in closed loop");
+                    self.failsafe = false;
+                    self.charger_cut = (inputs.charger_capacity - self.config.failsafe_charger_cap).max(0.0);
+                    self.pi.preset(self.battery_boost + self.charger_cut);
+                }
+                measured
+            }
+            None => {
+                if !meter_stale(self.last_measurement, now, self.config.meter_timeout) {
+                    // Hold the last correction through missed reads until the meter times out
+                    return self.action(inputs.charger_capacity);
+                }
+                if !self.failsafe {
+                    log::warn!("Grid meter stale, capping chargers at {:.0} kW", self.config.failsafe_charger_cap);
+                    self.failsafe = true;
+                }
+                self.pi.reset();
+                self.battery_boost = ramp(self.battery_boost, 0.0, self.config.battery_ramp_rate, dt);
+                self.charger_cut = 0.0;
+                self.charger_capped = true;
+                return ImportLimitAction {
+                    battery_boost: self.battery_boost,
+                    charger_cap: Some(self.config.failsafe_charger_cap),
+                    release_chargers: false,
+                };
+            }
+        };
+
+        // Violation accounting against the contracted limit
+        let over = measured > self.config.import_limit;
+        if over {
+            if !self.over_limit {
+                self.violations += 1;
+                log::warn!("Grid import {:.1} kW exceeds limit {:.1} kW", measured, self.config.import_limit);
+            }
+            self.seconds_over_limit += dt;
+        }
+        self.over_limit = over;
+        self.peak_import = self.peak_import.max(measured);
+
+        // Total relief needed, served by the battery first and the chargers second
+        let max_relief = inputs.battery_headroom + inputs.charger_capacity;
+        let error = measured - (self.config.import_limit - self.config.margin);
+        let relief = self.pi.update(error, dt, 0.0, max_relief.max(0.0));
+        let battery_target = relief.min(inputs.battery_headroom);
+        let charger_target = (relief - battery_target).min(inputs.charger_capacity);
+        self.battery_boost = ramp(self.battery_boost, battery_target, self.config.battery_ramp_rate, dt);
+        self.charger_cut = ramp(self.charger_cut, charger_target, self.config.charger_ramp_rate, dt);
+        self.action(inputs.charger_capacity)
+    }
+
+    fn action(&mut self, charger_capacity: f32) -> ImportLimitAction {
+        let capped = self.charger_cut > 0.01;
+        let release_chargers = self.charger_capped && !capped;
+        self.charger_capped = capped;
+        ImportLimitAction {
+            battery_boost: self.battery_boost,
+            charger_cap: capped.then(|| (charger_capacity - self.charger_cut).max(0.0)),
+            release_chargers,
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> ImportLimitStatus {
+        ImportLimitStatus {
+            import_limit: self.config.import_limit,
+            battery_boost: self.battery_boost,
+            charger_curtailment: self.charger_cut,
+            failsafe: self.failsafe,
+            over_limit: self.over_limit,
+            violations: self.violations,
+            seconds_over_limit: self.seconds_over_limit,
+            peak_import: self.peak_import,
+        }
+    }
+}
+
+/// Site export limit configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportLimitConfig {
+    /// Maximum grid export in kW (0 for zero export)
+    pub export_limit: f32,
+    /// Control target below the limit, in kW
+    pub margin: f32,
+    /// Proportional gain (kW correction per kW error)
+    pub kp: f32,
+    /// Integral gain (kW correction per kW error per second)
+    pub ki: f32,
+    /// Maximum rate of change of the battery correction in kW/s
+    pub battery_ramp_rate: f32,
+    /// Rate at which the PV cap is raised again, in kW/s (cuts apply immediately)
+    pub pv_release_rate: f32,
+    /// Export this far above the limit cuts PV by the full excess in one step, in kW
+    pub fast_trip: f32,
+    /// Meter data older than this triggers the fail-safe, in seconds (several control cycles)
+    pub meter_timeout: u64,
+}
+
+impl Default for ExportLimitConfig {
+    fn default() -> Self {
+        Self {
+            export_limit: 0.0,
+            margin: 1.0,
+            kp: 0.8,
+            ki: 0.5,
+            battery_ramp_rate: 20.0,
+            pv_release_rate: 2.0,
+            fast_trip: 5.0,
+            meter_timeout: 15,
+        }
+    }
+}
+
+/// Measurements and headroom for one control step
+#[derive(Debug, Clone, Copy)]
+pub struct ExportLimitInputs {
+    /// Metered grid power in kW (positive: importing), None if the read failed
+    pub measured: Option<f32>,
+    /// Battery charge available on top of the dispatched setpoint, in kW
+    pub battery_headroom: f32,
+    /// Measured PV power in kW
+    pub pv_power: f32,
+    /// Power taken on site by the chargers and the dispatched battery, in kW
+    pub local_consumption: f32,
+}
+
+/// Corrections for the EMS to apply on top of the dispatch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportLimitAction {
+    /// Additional battery charge in kW
+    pub battery_absorb: f32,
+    /// Total PV power cap in kW (None: uncapped)
+    pub pv_cap: Option<f32>,
+    /// PV was capped last step and may return to MPPT
+    pub release_pv: bool,
+}
+
+/// Site export limiter. Chargers soak up excess on their own since this limiter never caps them;
+/// the battery absorbs the rest before PV is curtailed.
+#[derive(Debug, Clone)]
+pub struct ExportLimiter {
+    config: ExportLimitConfig,
+    pi: PiController,
+    last_update: Option<Instant>,
+    last_measurement: Option<Instant>,
+    battery_absorb: f32,
+    pv_cut: f32,
+    /// PV output when the cap engaged, the reference the cut is taken from
+    pv_reference: f32,
+    pv_cap: Option<f32>,
+    failsafe: bool,
+    over_limit: bool,
+    violations: u32,
+    seconds_over_limit: f32,
+    peak_export: f32,
+}
+
+impl ExportLimiter {
+    /// Create a new limiter
+    ///
+    /// # Arguments
+    /// * `config` - Limit, gains and ramp rates
+    pub fn new(config: ExportLimitConfig) -> Self {
+        let pi = PiController::new(config.kp, config.ki);
+        Self {
+            config,
+            pi,
+            last_update: None,
+            last_measurement: None,
+            battery_absorb: 0.0,
+            pv_cut: 0.0,
+            pv_reference: 0.0,
+            pv_cap: None,
+            failsafe: false,
+            over_limit: false,
+            violations: 0,
+            seconds_over_limit: 0.0,
+            peak_export: 0.0,
+        }
+    }
+
+    /// Whether PV is currently capped by the export limit
+    pub fn is_curtailing(&self) -> bool {
+        self.pv_cap.is_some()
+    }
+
+    /// Evaluate one control step
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `inputs` - Metered export, PV output and available headroom
+    ///
+    /// # Returns
+    /// Battery and PV corrections
+    pub fn update(&mut self, now: Instant, inputs: ExportLimitInputs) -> ExportLimitAction {
+        let dt = self.last_update.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+
+        let export = match inputs.measured {
+            Some(measured) => {
+                self.last_measurement = Some(now);
+                if self.failsafe {
+                    log::info!("Grid meter restored, export limit back in closed loop");
+                    self.failsafe = false;
+                    self.pi.preset(self.battery_absorb + self.pv_cut);
+                }
+                -measured
+            }
+            None => {
+                if !meter_stale(self.last_measurement, now, self.config.meter_timeout) {
+                    // Hold the last correction through missed reads until the meter times out
+                    let cap = self.pv_cap;
+                    return self.action(inputs.pv_power, cap);
+                }
+                if !self.failsafe {
+                    log::warn!("Grid meter stale, capping PV at local consumption");
+                    self.failsafe = true;
+                }
+                // Without feedback only what is consumed on site may be produced
+                self.pi.reset();
+                self.battery_absorb = ramp(self.battery_absorb, 0.0, self.config.battery_ramp_rate, dt);
+                let cap = (inputs.local_consumption + self.config.export_limit - self.config.margin).max(0.0);
+                let pv_available = self.pv_cap.map(|_| self.pv_reference).unwrap_or(inputs.pv_power);
+                self.pv_cut = (pv_available - cap).max(0.0);
+                return self.action(inputs.pv_power, Some(cap));
+            }
+        };
+
+        // Violation accounting against the export limit
+        let over = export > self.config.export_limit;
+        if over {
+            if !self.over_limit {
+                self.violations += 1;
+                log::warn!("Grid export {:.1} kW exceeds limit {:.1} kW", export, self.config.export_limit);
+            }
+            self.seconds_over_limit += dt;
+        }
+        self.over_limit = over;
+        self.peak_export = self.peak_export.max(export);
+
+        // Total excess to absorb, taken by the battery first and PV curtailment second
+        let pv_available = self.pv_cap.map(|_| self.pv_reference).unwrap_or(inputs.pv_power);
+        let max_relief = inputs.battery_headroom + pv_available;
+        let error = export - (self.config.export_limit - self.config.margin);
+        let mut relief = self.pi.update(error, dt, 0.0, max_relief.max(0.0));
+        if export - self.config.export_limit > self.config.fast_trip {
+            // Sudden load drop: take the whole excess at once and let the PI continue from there
+            relief = relief.max(self.battery_absorb + self.pv_cut + error).min(max_relief);
+            self.pi.preset(relief);
+        }
+        let battery_target = relief.min(inputs.battery_headroom);
+        self.battery_absorb = ramp(self.battery_absorb, battery_target, self.config.battery_ramp_rate, dt);
+        // PV takes whatever the battery does not, including while the battery ramps
+        let pv_target = (relief - self.battery_absorb).clamp(0.0, pv_available);
+        self.pv_cut = if pv_target > self.pv_cut {
+            pv_target
+        } else {
+            ramp(self.pv_cut, pv_target, self.config.pv_release_rate, dt)
+        };
+        let cap = (self.pv_cut > 0.01).then(|| {
+            let reference = if self.pv_cap.is_some() { self.pv_reference } else { inputs.pv_power };
+            (reference - self.pv_cut).max(0.0)
+        });
+        self.action(inputs.pv_power, cap)
+    }
+
+    fn action(&mut self, pv_power: f32, cap: Option<f32>) -> ExportLimitAction {
+        match (self.pv_cap, cap) {
+            (None, Some(cap)) => {
+                self.pv_reference = pv_power;
+                log::info!("PV capped at {:.1} kW to hold the export limit", cap);
+            }
+            (Some(_), None) => log::info!("PV export cap released"),
+            _ => {}
+        }
+        let release_pv = self.pv_cap.is_some() && cap.is_none();
+        self.pv_cap = cap;
+        ExportLimitAction {
+            battery_absorb: self.battery_absorb,
+            pv_cap: cap,
+            release_pv,
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> ExportLimitStatus {
+        ExportLimitStatus {
+            export_limit: self.config.export_limit,
+            battery_absorb: self.battery_absorb,
+            pv_curtailment: self.pv_cut,
+            pv_cap: self.pv_cap,
+            failsafe: self.failsafe,
+            over_limit: self.over_limit,
+            violations: self.violations,
+            seconds_over_limit: self.seconds_over_limit,
+            peak_export: self.peak_export,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn pi_integral_stays_within_output_limits() {
+        let mut pi = PiController::new(0.0, 1.0);
+        for _ in 0..100 {
+            assert!(pi.update(5.0, 1.0, -10.0, 10.0) <= 10.0);
+        }
+        // A wound-up integral would hold the output at the limit long after the error reverses
+        let output = pi.update(-1.0, 1.0, -10.0, 10.0);
+        assert!((output - 9.0).abs() < 1e-4, "output {}", output);
+    }
+
+    #[test]
+    fn pi_does_not_integrate_while_saturated() {
+        let mut pi = PiController::new(1.0, 1.0);
+        for _ in 0..10 {
+            assert_eq!(pi.update(100.0, 1.0, -10.0, 10.0), 10.0);
+        }
+        let output = pi.update(-1.0, 1.0, -10.0, 10.0);
+        assert!(output < 0.0, "output {}", output);
+    }
+
+    #[test]
+    fn import_limiter_enters_failsafe_on_stale_meter_and_recovers() {
+        let config = ImportLimitConfig::default();
+        let cap = config.failsafe_charger_cap;
+        let timeout = config.meter_timeout;
+        let mut limiter = ImportLimiter::new(config);
+        let inputs = |measured| ImportLimitInputs { measured, battery_headroom: 20.0, charger_capacity: 60.0 };
+        let t0 = Instant::now();
+
+        let action = limiter.update(t0, inputs(Some(50.0)));
+        assert_eq!(action.charger_cap, None);
+
+        // Missed reads within the timeout hold the last correction
+        let action = limiter.update(t0 + Duration::from_secs(timeout), inputs(None));
+        assert_eq!(action.charger_cap, None);
+        assert!(!limiter.status().failsafe);
+
+        let action = limiter.update(t0 + Duration::from_secs(timeout + 5), inputs(None));
+        assert_eq!(action.charger_cap, Some(cap));
+        assert!(limiter.status().failsafe);
+
+        // Back in closed loop, the cap ramps out from the fail-safe value at the charger ramp rate
+        let action = limiter.update(t0 + Duration::from_secs(timeout + 6), inputs(Some(50.0)));
+        assert!(!limiter.status().failsafe);
+        let ramped = action.charger_cap.expect("chargers should still be capped");
+        assert!(ramped > cap && ramped < 60.0, "cap {}", ramped);
+        assert!(!action.release_chargers);
+
+        let action = limiter.update(t0 + Duration::from_secs(timeout + 8), inputs(Some(50.0)));
+        assert_eq!(action.charger_cap, None);
+        assert!(action.release_chargers);
+    }
+
+    #[test]
+    fn export_limiter_fast_trip_cuts_full_excess() {
+        let mut limiter = ExportLimiter::new(ExportLimitConfig { kp: 0.1, ki: 0.05, ..Default::default() });
+        let inputs = |measured| ExportLimitInputs { measured, battery_headroom: 0.0, pv_power: 50.0, local_consumption: 20.0 };
+        let t0 = Instant::now();
+
+        let action = limiter.update(t0, inputs(Some(5.0)));
+        assert_eq!(action.pv_cap, None);
+
+        // 30 kW export against a zero-export limit: the slow PI alone would cut under 5 kW
+        let action = limiter.update(t0 + Duration::from_secs(1), inputs(Some(-30.0)));
+        let cap = action.pv_cap.expect("PV should be capped");
+        assert!((cap - 19.0).abs() < 1e-3, "cap {}", cap);
+    }
+}
@@ -0,0 +1,350 @@
+// 电网支撑功能
+// Grid-support functions for the PCS: frequency-watt droop, volt-watt curve and
+// voltage/frequency ride-through monitoring, with every response logged as an event
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::types::{GridSupportEvent, GridSupportStatus};
+
+/// Maximum number of retained events
+const MAX_EVENTS: usize = 100;
+
+/// Ride-through band: the measurement may stay outside [min, max] for at most `duration` seconds
+///
+/// Measurements arrive once per control cycle, so a band is only evaluated to within a cycle;
+/// sub-second limits belong to the PCS's own protection and are not configured here.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RideThroughBand {
+    /// Lower bound (V as % of nominal, or Hz)
+    pub min: f32,
+    /// Upper bound (V as % of nominal, or Hz)
+    pub max: f32,
+    /// Permitted time outside the band in seconds
+    pub duration: f32,
+}
+
+/// Grid-support configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridSupportConfig {
+    /// Nominal grid frequency in Hz
+    pub nominal_frequency: f32,
+    /// Nominal AC voltage in V
+    pub nominal_voltage: f32,
+    /// PCS active power rating the responses scale with, in kW
+    pub rated_power: f32,
+    /// Enable the frequency-watt response
+    pub frequency_watt: bool,
+    /// Frequency above which output is reduced, in Hz
+    pub over_frequency_start: f32,
+    /// Over-frequency droop in % (frequency change in % of nominal for a full-rating change)
+    pub over_frequency_droop: f32,
+    /// Frequency below which output is increased, in Hz
+    pub under_frequency_start: f32,
+    /// Under-frequency droop in %
+    pub under_frequency_droop: f32,
+    /// Enable the volt-watt response
+    pub volt_watt: bool,
+    /// Volt-watt curve as (voltage % of nominal, maximum output % of rating) points, ascending by voltage
+    pub volt_watt_curve: Vec<(f32, f32)>,
+    /// Voltage ride-through bands (% of nominal)
+    pub voltage_ride_through: Vec<RideThroughBand>,
+    /// Frequency ride-through bands (Hz)
+    pub frequency_ride_through: Vec<RideThroughBand>,
+}
+
+impl Default for GridSupportConfig {
+    fn default() -> Self {
+        Self {
+            nominal_frequency: 50.0,
+            nominal_voltage: 230.0,
+            rated_power: 100.0,
+            frequency_watt: true,
+            over_frequency_start: 50.2,
+            over_frequency_droop: 5.0,
+            under_frequency_start: 49.8,
+            under_frequency_droop: 5.0,
+            volt_watt: true,
+            volt_watt_curve: vec![(106.0, 100.0), (110.0, 20.0)],
+            voltage_ride_through: vec![
+                RideThroughBand { min: 88.0, max: 110.0, duration: 21.0 },
+            ],
+            frequency_ride_through: vec![
+                RideThroughBand { min: 49.0, max: 51.0, duration: 180.0 },
+            ],
+        }
+    }
+}
+
+/// Adjustment for the EMS to apply to the battery setpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSupportResponse {
+    /// Power added to the setpoint in kW (positive: more output)
+    pub power_offset: f32,
+    /// Maximum output in kW (None: unrestricted)
+    pub power_cap: Option<f32>,
+}
+
+/// Kinds of grid-support event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventKind {
+    OverFrequency,
+    UnderFrequency,
+    VoltWatt,
+    VoltageExcursion,
+    FrequencyExcursion,
+}
+
+impl EventKind {
+    const ALL: [EventKind; 5] = [
+        EventKind::OverFrequency,
+        EventKind::UnderFrequency,
+        EventKind::VoltWatt,
+        EventKind::VoltageExcursion,
+        EventKind::FrequencyExcursion,
+    ];
+}
+
+/// An excursion or response in progress
+#[derive(Debug, Clone)]
+struct ActiveEvent {
+    started: Instant,
+    timestamp: String,
+    extreme: f32,
+    max_response: f32,
+}
+
+/// Grid-support controller
+#[derive(Debug, Clone)]
+pub struct GridSupport {
+    config: GridSupportConfig,
+    frequency: Option<f32>,
+    voltage: Option<f32>,
+    response: GridSupportResponse,
+    /// Events in progress, indexed by EventKind
+    active: [Option<ActiveEvent>; 5],
+    /// Start of the current excursion outside each voltage / frequency band
+    voltage_band_since: Vec<Option<Instant>>,
+    frequency_band_since: Vec<Option<Instant>>,
+    ride_through_exceeded: bool,
+    events: Vec<GridSupportEvent>,
+}
+
+impl GridSupport {
+    /// Create a new controller
+    ///
+    /// # Arguments
+    /// * `config` - Droop settings, volt-watt curve and ride-through bands
+    pub fn new(config: GridSupportConfig) -> Self {
+        Self {
+            config,
+            frequency: None,
+            voltage: None,
+            response: GridSupportResponse { power_offset: 0.0, power_cap: None },
+            active: Default::default(),
+            voltage_band_since: Vec::new(),
+            frequency_band_since: Vec::new(),
+            ride_through_exceeded: false,
+            events: Vec::new(),
+        }
+    }
+
+    /// Maximum output from the volt-watt curve, held flat beyond the end points
+    ///
+    /// # Arguments
+    /// * `voltage_pct` - Voltage in % of nominal
+    ///
+    /// # Returns
+    /// Maximum output in kW, None below the first curve point
+    fn volt_watt_cap(&self, voltage_pct: f32) -> Option<f32> {
+        let curve = &self.config.volt_watt_curve;
+        let first = curve.first()?;
+        if voltage_pct <= first.0 {
+            return None;
+        }
+        let pct = match curve.windows(2).find(|w| voltage_pct >= w[0].0 && voltage_pct <= w[1].0) {
+            Some(w) => {
+                let span = (w[1].0 - w[0].0).max(f32::EPSILON);
+                w[0].1 + (w[1].1 - w[0].1) * (voltage_pct - w[0].0) / span
+            }
+            None => curve.last().map(|p| p.1).unwrap_or(100.0),
+        };
+        Some(pct / 100.0 * self.config.rated_power)
+    }
+
+    /// Start, extend or close an event
+    ///
+    /// # Arguments
+    /// * `kind` - Event kind
+    /// * `now` - Current time
+    /// * `active` - Whether the condition holds
+    /// * `deviation` - Measurement deviation from nominal (sign kept)
+    /// * `response` - Response magnitude in kW
+    fn track(&mut self, kind: EventKind, now: Instant, active: bool, deviation: f32, response: f32) {
+        let slot = &mut self.active[kind as usize];
+        match (slot.as_mut(), active) {
+            (None, true) => {
+                log::warn!("Grid-support event {:?} started (deviation {:.3}, response {:.1} kW)", kind, deviation, response);
+                *slot = Some(ActiveEvent {
+                    started: now,
+                    timestamp: Local::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    extreme: deviation,
+                    max_response: response,
+                });
+            }
+            (Some(event), true) => {
+                if deviation.abs() > event.extreme.abs() {
+                    event.extreme = deviation;
+                }
+                event.max_response = event.max_response.max(response);
+            }
+            (Some(_), false) => {
+                if let Some(event) = slot.take() {
+                    let duration = now.duration_since(event.started).as_secs_f32();
+                    log::info!("Grid-support event {:?} ended after {:.1} s", kind, duration);
+                    self.events.push(GridSupportEvent {
+                        timestamp: event.timestamp,
+                        kind: format!("{:?}", kind),
+                        duration,
+                        extreme_deviation: event.extreme,
+                        max_response: event.max_response,
+                    });
+                    if self.events.len() > MAX_EVENTS {
+                        self.events.remove(0);
+                    }
+                }
+            }
+            (None, false) => {}
+        }
+    }
+
+    /// Whether the measurement has stayed outside any band for longer than the band permits
+    ///
+    /// Each band times its own excursion: time spent outside a wide band only counts
+    /// against a narrower band from when the measurement actually left it.
+    fn outside_too_long(bands: &[RideThroughBand], since: &mut Vec<Option<Instant>>, now: Instant, value: f32) -> bool {
+        since.resize(bands.len(), None);
+        bands.iter().zip(since.iter_mut()).fold(false, |exceeded, (band, started)| {
+            if value < band.min || value > band.max {
+                let started = *started.get_or_insert(now);
+                exceeded || now.duration_since(started).as_secs_f32() > band.duration
+            } else {
+                *started = None;
+                exceeded
+            }
+        })
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `frequency` - Measured grid frequency in Hz, if available
+    /// * `voltage` - Measured AC voltage in V, if available
+    ///
+    /// # Returns
+    /// Adjustment to the battery setpoint
+    pub fn update(&mut self, now: Instant, frequency: Option<f32>, voltage: Option<f32>) -> GridSupportResponse {
+        self.frequency = frequency.filter(|f| *f > 0.0);
+        self.voltage = voltage.filter(|v| *v > 0.0);
+        // A gap in the measurements ends the timed excursions
+        if self.frequency.is_none() {
+            self.frequency_band_since.clear();
+        }
+        if self.voltage.is_none() {
+            self.voltage_band_since.clear();
+        }
+        let nominal_frequency = self.config.nominal_frequency.max(f32::EPSILON);
+        let rated = self.config.rated_power;
+        let mut response = GridSupportResponse { power_offset: 0.0, power_cap: None };
+
+        if let Some(f) = self.frequency {
+            let deviation = f - nominal_frequency;
+            let over = self.config.frequency_watt && f > self.config.over_frequency_start;
+            let under = self.config.frequency_watt && f < self.config.under_frequency_start;
+            if over {
+                let droop = (self.config.over_frequency_droop / 100.0).max(f32::EPSILON);
+                response.power_offset = -(rated * (f - self.config.over_frequency_start) / (nominal_frequency * droop)).min(rated);
+            } else if under {
+                let droop = (self.config.under_frequency_droop / 100.0).max(f32::EPSILON);
+                response.power_offset = (rated * (self.config.under_frequency_start - f) / (nominal_frequency * droop)).min(rated);
+            }
+            let offset = response.power_offset.abs();
+            self.track(EventKind::OverFrequency, now, over, deviation, offset);
+            self.track(EventKind::UnderFrequency, now, under, deviation, offset);
+
+            let bands = self.config.frequency_ride_through.clone();
+            let outside = bands.iter().any(|b| f < b.min || f > b.max);
+            self.track(EventKind::FrequencyExcursion, now, outside, deviation, 0.0);
+            if Self::outside_too_long(&bands, &mut self.frequency_band_since, now, f) {
+                self.flag_ride_through("frequency", f);
+            }
+        }
+
+        if let Some(v) = self.voltage {
+            let pct = v / self.config.nominal_voltage.max(f32::EPSILON) * 100.0;
+            let cap = if self.config.volt_watt { self.volt_watt_cap(pct).filter(|c| *c < rated) } else { None };
+            response.power_cap = cap;
+            self.track(EventKind::VoltWatt, now, cap.is_some(), pct - 100.0, cap.map(|c| rated - c).unwrap_or(0.0));
+
+            let bands = self.config.voltage_ride_through.clone();
+            let outside = bands.iter().any(|b| pct < b.min || pct > b.max);
+            self.track(EventKind::VoltageExcursion, now, outside, pct - 100.0, 0.0);
+            if Self::outside_too_long(&bands, &mut self.voltage_band_since, now, pct) {
+                self.flag_ride_through("voltage", pct);
+            }
+        }
+        if self.active[EventKind::VoltageExcursion as usize].is_none() && self.active[EventKind::FrequencyExcursion as usize].is_none() {
+            self.ride_through_exceeded = false;
+        }
+
+        self.response = response;
+        response
+    }
+
+    /// Latch a ride-through limit violation for the current excursion
+    fn flag_ride_through(&mut self, measurand: &str, value: f32) {
+        if !self.ride_through_exceeded {
+            log::error!("Grid {} {:.2} outside ride-through limits for longer than permitted", measurand, value);
+            self.ride_through_exceeded = true;
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> GridSupportStatus {
+        GridSupportStatus {
+            frequency: self.frequency,
+            voltage: self.voltage,
+            power_offset: self.response.power_offset,
+            power_cap: self.response.power_cap,
+            active_events: EventKind::ALL.iter()
+                .filter(|&&k| self.active[k as usize].is_some())
+                .map(|k| format!("{:?}", k))
+                .collect(),
+            ride_through_exceeded: self.ride_through_exceeded,
+            events: self.events.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn each_band_times_its_own_excursion() {
+        let bands = [
+            RideThroughBand { min: 88.0, max: 110.0, duration: 60.0 },
+            RideThroughBand { min: 50.0, max: 115.0, duration: 10.0 },
+        ];
+        let mut since = Vec::new();
+        let start = Instant::now();
+        // Leaving the outer band 12 s into the excursion starts its 10 s timer then
+        assert!(!GridSupport::outside_too_long(&bands, &mut since, start, 112.0));
+        assert!(!GridSupport::outside_too_long(&bands, &mut since, start + Duration::from_secs(12), 116.0));
+        assert!(!GridSupport::outside_too_long(&bands, &mut since, start + Duration::from_secs(19), 116.0));
+        assert!(GridSupport::outside_too_long(&bands, &mut since, start + Duration::from_secs(23), 116.0));
+    }
+}
@@ -0,0 +1,688 @@
+// 并离网切换与黑启动
+// Islanding: grid loss detection, opening the grid breaker before the PCS goes grid-forming with charger
+// shedding, black start from the battery when the site bus is dead, resynchronisation across the open
+// breaker and reconnection
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::devices::pcs::PcsMode;
+use crate::types::IslandingStatus;
+
+/// Islanding configuration
+///
+/// The controller runs once per EMS control cycle (5 s by default), so every timing below is rounded
+/// up to whole cycles. Fast loss-of-mains protection is left to the PCS and the interface relay; this
+/// controller carries out the transfer once the grid stays out of range. The grid meter must sit on
+/// the grid side of the breaker so that the grid can be compared with the site bus while islanded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IslandingConfig {
+    /// Nominal AC voltage in V
+    pub nominal_voltage: f32,
+    /// Lowest healthy grid voltage in % of nominal
+    pub grid_voltage_min: f32,
+    /// Highest healthy grid voltage in % of nominal
+    pub grid_voltage_max: f32,
+    /// Lowest healthy grid frequency in Hz
+    pub grid_frequency_min: f32,
+    /// Highest healthy grid frequency in Hz
+    pub grid_frequency_max: f32,
+    /// How long the grid must be out of range before islanding, in milliseconds
+    pub grid_loss_delay_ms: u64,
+    /// Time allowed for the breaker feedback to follow a command, in milliseconds
+    pub breaker_timeout_ms: u64,
+    /// How long the grid must be healthy before resynchronising, in seconds
+    pub grid_stable_time: u64,
+    /// Maximum site/grid voltage difference for reconnection in % of nominal
+    pub sync_voltage_tolerance: f32,
+    /// Maximum site/grid frequency difference for reconnection in Hz
+    pub sync_frequency_tolerance: f32,
+    /// How long the site must stay in sync before reconnecting, in seconds
+    pub sync_time: u64,
+    /// Site bus voltage below this is dead, in % of nominal
+    pub dead_bus_voltage: f32,
+    /// Site bus voltage above this is energised, in % of nominal
+    pub live_bus_voltage: f32,
+    /// How long the bus must stay dead while islanded before a black start, in milliseconds
+    pub dead_bus_delay_ms: u64,
+    /// Time allowed for one black start attempt, in seconds
+    pub black_start_timeout: u64,
+    /// Wait between black start attempts, in seconds
+    pub black_start_retry_delay: u64,
+    /// Black start attempts before giving up
+    pub max_black_start_attempts: u32,
+    /// Wait after reconnection or black start before restoring shed chargers, in seconds
+    pub load_restore_delay: u64,
+    /// Chargers kept in service while islanded
+    pub critical_chargers: Vec<String>,
+}
+
+impl Default for IslandingConfig {
+    fn default() -> Self {
+        Self {
+            nominal_voltage: 230.0,
+            grid_voltage_min: 85.0,
+            grid_voltage_max: 115.0,
+            grid_frequency_min: 47.5,
+            grid_frequency_max: 51.5,
+            grid_loss_delay_ms: 5000,
+            breaker_timeout_ms: 10000,
+            grid_stable_time: 300,
+            sync_voltage_tolerance: 5.0,
+            sync_frequency_tolerance: 0.1,
+            sync_time: 2,
+            dead_bus_voltage: 20.0,
+            live_bus_voltage: 90.0,
+            dead_bus_delay_ms: 10000,
+            black_start_timeout: 60,
+            black_start_retry_delay: 30,
+            max_black_start_attempts: 3,
+            load_restore_delay: 30,
+            critical_chargers: Vec::new(),
+        }
+    }
+}
+
+/// Islanding state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IslandState {
+    OnGrid,          // Breaker closed, PCS grid-following
+    Disconnecting,   // Grid lost, breaker commanded open, PCS in standby
+    Islanded,        // Breaker open, PCS grid-forming
+    BlackStart,      // Energising the dead site bus from the battery
+    Resynchronising, // Grid back, matching the site bus to the grid across the open breaker
+    Reconnecting,    // Breaker commanded closed, waiting for its feedback
+    Fault,           // Breaker or black start failed, PCS in standby until the grid returns or a reset
+}
+
+/// Steps of a black start attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlackStartStep {
+    BatteryOnline, // Waiting for the battery contactors
+    Energising,    // PCS commanded OffGrid, waiting for bus voltage
+    Retrying,      // Attempt failed, waiting before the next one
+}
+
+/// Which chargers are shed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedLevel {
+    None,        // All chargers in service
+    NonCritical, // Only critical chargers in service
+    All,         // No chargers in service
+}
+
+/// Measurements for one control cycle
+#[derive(Debug, Clone, Copy)]
+pub struct IslandInputs {
+    /// Grid-side voltage in V, None without a valid meter reading (the PCS reading is used while on grid)
+    pub grid_voltage: Option<f32>,
+    /// Grid-side frequency in Hz, None without a valid meter reading (the PCS reading is used while on grid)
+    pub grid_frequency: Option<f32>,
+    /// Site bus voltage at the PCS in V
+    pub site_voltage: f32,
+    /// Site bus frequency at the PCS in Hz
+    pub site_frequency: f32,
+    /// Whether the PCS reports a fault
+    pub pcs_fault: bool,
+    /// Whether the battery contactors are closed
+    pub battery_online: bool,
+    /// Whether the grid breaker reports closed, None without valid feedback
+    pub breaker_closed: Option<bool>,
+}
+
+/// Commands for the EMS to carry out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IslandCommands {
+    /// PCS operating mode to command
+    pub pcs_mode: Option<PcsMode>,
+    /// Request the battery contactors to close
+    pub battery_online: bool,
+    /// New charger shed level
+    pub shed: Option<ShedLevel>,
+}
+
+/// Islanding and black start controller
+#[derive(Debug, Clone)]
+pub struct IslandController {
+    config: IslandingConfig,
+    state: IslandState,
+    state_since: Instant,
+    step: BlackStartStep,
+    step_since: Instant,
+    attempt_since: Instant,
+    attempts: u32,
+    shed: ShedLevel,
+    restore_at: Option<Instant>,
+    grid_bad_since: Option<Instant>,
+    grid_good_since: Option<Instant>,
+    dead_bus_since: Option<Instant>,
+    in_sync_since: Option<Instant>,
+    grid_healthy: Option<bool>,
+    breaker_closed: Option<bool>,
+    /// Reconnecting a dead bus, so the PCS stays in standby until the breaker closes
+    dead_bus_reconnect: bool,
+    transitions: u32,
+    last_event: Option<String>,
+    fault: Option<String>,
+}
+
+impl IslandController {
+    /// Create a new controller, assuming the site starts on grid
+    ///
+    /// # Arguments
+    /// * `config` - Grid limits, synchronisation tolerances and wait times
+    pub fn new(config: IslandingConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            config,
+            state: IslandState::OnGrid,
+            state_since: now,
+            step: BlackStartStep::BatteryOnline,
+            step_since: now,
+            attempt_since: now,
+            attempts: 0,
+            shed: ShedLevel::None,
+            restore_at: None,
+            grid_bad_since: None,
+            grid_good_since: None,
+            dead_bus_since: None,
+            in_sync_since: None,
+            grid_healthy: None,
+            breaker_closed: None,
+            dead_bus_reconnect: false,
+            transitions: 0,
+            last_event: None,
+            fault: None,
+        }
+    }
+
+    /// Whether the PCS forms the site voltage, so battery power follows the load rather than a setpoint
+    pub fn grid_forming(&self) -> bool {
+        self.pcs_mode() == Some(PcsMode::OffGrid)
+    }
+
+    /// Breaker position the current state requires, re-issued every cycle
+    ///
+    /// # Returns
+    /// True to hold the breaker closed
+    pub fn breaker_command(&self) -> bool {
+        matches!(self.state, IslandState::OnGrid | IslandState::Reconnecting)
+    }
+
+    /// PCS operating mode the current state requires, re-issued every cycle so a failed write is recovered
+    ///
+    /// # Returns
+    /// None while on grid, where the dispatch chooses the mode
+    pub fn pcs_mode(&self) -> Option<PcsMode> {
+        match self.state {
+            IslandState::OnGrid => None,
+            IslandState::Islanded | IslandState::Resynchronising => Some(PcsMode::OffGrid),
+            IslandState::Reconnecting if !self.dead_bus_reconnect => Some(PcsMode::OffGrid),
+            IslandState::BlackStart if self.step == BlackStartStep::Energising => Some(PcsMode::OffGrid),
+            IslandState::Disconnecting | IslandState::Reconnecting | IslandState::BlackStart | IslandState::Fault => {
+                Some(PcsMode::Standby)
+            }
+        }
+    }
+
+    /// Whether a charger is currently shed
+    ///
+    /// # Arguments
+    /// * `id` - Charger identifier
+    pub fn is_shed(&self, id: &str) -> bool {
+        match self.shed {
+            ShedLevel::None => false,
+            ShedLevel::NonCritical => !self.config.critical_chargers.iter().any(|c| c == id),
+            ShedLevel::All => true,
+        }
+    }
+
+    /// Clear a breaker or black start fault; the breaker must confirm open again before the PCS
+    /// goes grid-forming, and a black start follows if the bus is still dead
+    pub fn reset_fault(&mut self) {
+        if self.state == IslandState::Fault {
+            log::info!("Islanding fault reset");
+            self.fault = None;
+            self.attempts = 0;
+            self.set_state(IslandState::Disconnecting, Instant::now());
+        }
+    }
+
+    fn set_fault(&mut self, fault: String, now: Instant, commands: &mut IslandCommands) {
+        log::error!("{}", fault);
+        self.fault = Some(fault);
+        commands.pcs_mode = Some(PcsMode::Standby);
+        self.set_state(IslandState::Fault, now);
+    }
+
+    fn set_state(&mut self, state: IslandState, now: Instant) {
+        if state != self.state {
+            let event = format!("{:?} -> {:?}", self.state, state);
+            log::info!("Islanding: {}", event);
+            self.last_event = Some(event);
+            self.transitions += 1;
+            self.state = state;
+            self.state_since = now;
+            self.in_sync_since = None;
+        }
+    }
+
+    fn set_step(&mut self, step: BlackStartStep, now: Instant) {
+        self.step = step;
+        self.step_since = now;
+    }
+
+    fn set_shed(&mut self, level: ShedLevel, commands: &mut IslandCommands) {
+        if level != self.shed {
+            log::info!("Charger shed level {:?}", level);
+            self.shed = level;
+            commands.shed = Some(level);
+        }
+    }
+
+    /// Whether a condition has held for at least the given time, tracking its start
+    fn held(since: &mut Option<Instant>, condition: bool, now: Instant, delay: Duration) -> bool {
+        if !condition {
+            *since = None;
+            return false;
+        }
+        now.duration_since(*since.get_or_insert(now)) >= delay
+    }
+
+    /// Start a black start attempt
+    fn begin_black_start(&mut self, now: Instant, commands: &mut IslandCommands) {
+        self.attempts += 1;
+        self.attempt_since = now;
+        log::warn!("Site bus dead, black start attempt {}", self.attempts);
+        self.set_state(IslandState::BlackStart, now);
+        self.set_step(BlackStartStep::BatteryOnline, now);
+        self.set_shed(ShedLevel::All, commands);
+        self.restore_at = None;
+        commands.pcs_mode = Some(PcsMode::Standby);
+        commands.battery_online = true;
+    }
+
+    /// Open the breaker; the PCS waits in standby until the breaker confirms open
+    fn disconnect(&mut self, now: Instant, commands: &mut IslandCommands) {
+        commands.pcs_mode = Some(PcsMode::Standby);
+        commands.battery_online = true;
+        self.restore_at = None;
+        self.set_shed(ShedLevel::NonCritical, commands);
+        self.set_state(IslandState::Disconnecting, now);
+    }
+
+    /// Close the breaker onto the grid
+    ///
+    /// # Arguments
+    /// * `dead_bus` - The site bus is dead, so the PCS stays in standby and no synchronisation is needed
+    fn begin_reconnect(&mut self, now: Instant, dead_bus: bool, commands: &mut IslandCommands) {
+        self.dead_bus_reconnect = dead_bus;
+        if dead_bus {
+            commands.pcs_mode = Some(PcsMode::Standby);
+        }
+        self.set_state(IslandState::Reconnecting, now);
+    }
+
+    /// Breaker closed: follow the grid and schedule charger restoration
+    fn reconnected(&mut self, now: Instant, commands: &mut IslandCommands) {
+        commands.pcs_mode = Some(PcsMode::GridTie);
+        self.attempts = 0;
+        self.restore_at = Some(now + Duration::from_secs(self.config.load_restore_delay));
+        self.set_state(IslandState::OnGrid, now);
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `inputs` - Grid and site measurements
+    ///
+    /// # Returns
+    /// Commands for the PCS, contactors and chargers
+    pub fn update(&mut self, now: Instant, inputs: IslandInputs) -> IslandCommands {
+        let mut commands = IslandCommands::default();
+        self.breaker_closed = inputs.breaker_closed;
+        let nominal_voltage = self.config.nominal_voltage.max(f32::EPSILON);
+        // Without a meter reading the grid-following PCS still sees the grid at its terminals;
+        // while islanded the PCS forms the bus itself and the grid state is unknown
+        let grid = match (inputs.grid_voltage, inputs.grid_frequency) {
+            (Some(v), Some(f)) => Some((v, f)),
+            _ if self.state == IslandState::OnGrid => Some((inputs.site_voltage, inputs.site_frequency)),
+            _ => None,
+        };
+        self.grid_healthy = match grid {
+            Some((v, f)) => {
+                let pct = v / nominal_voltage * 100.0;
+                Some(pct >= self.config.grid_voltage_min && pct <= self.config.grid_voltage_max
+                    && f >= self.config.grid_frequency_min && f <= self.config.grid_frequency_max)
+            }
+            _ => None,
+        };
+        let grid_lost = Self::held(
+            &mut self.grid_bad_since,
+            self.grid_healthy == Some(false),
+            now,
+            Duration::from_millis(self.config.grid_loss_delay_ms),
+        );
+        let grid_stable = Self::held(
+            &mut self.grid_good_since,
+            self.grid_healthy == Some(true),
+            now,
+            Duration::from_secs(self.config.grid_stable_time),
+        );
+        let site_pct = inputs.site_voltage / nominal_voltage * 100.0;
+        let bus_dead = Self::held(
+            &mut self.dead_bus_since,
+            site_pct < self.config.dead_bus_voltage || inputs.pcs_fault,
+            now,
+            Duration::from_millis(self.config.dead_bus_delay_ms),
+        );
+
+        if let Some(at) = self.restore_at {
+            if self.state == IslandState::OnGrid && now >= at {
+                self.restore_at = None;
+                self.set_shed(ShedLevel::None, &mut commands);
+            }
+        }
+
+        match self.state {
+            IslandState::OnGrid => {
+                if grid_lost {
+                    log::warn!("Grid lost, opening the grid breaker");
+                    self.disconnect(now, &mut commands);
+                } else if inputs.breaker_closed == Some(false) {
+                    // Opened by the interface protection
+                    log::warn!("Grid breaker opened while on grid, transferring the site to island operation");
+                    self.disconnect(now, &mut commands);
+                }
+            }
+            IslandState::Disconnecting => {
+                let elapsed = now.duration_since(self.state_since);
+                if inputs.breaker_closed == Some(false) {
+                    log::info!("Grid breaker open, PCS forming the site bus");
+                    commands.pcs_mode = Some(PcsMode::OffGrid);
+                    self.set_state(IslandState::Islanded, now);
+                } else if elapsed >= Duration::from_millis(self.config.breaker_timeout_ms) {
+                    self.set_fault("Grid breaker failed to open".to_string(), now, &mut commands);
+                }
+            }
+            IslandState::Islanded => {
+                if grid_stable {
+                    self.set_state(IslandState::Resynchronising, now);
+                } else if bus_dead {
+                    self.begin_black_start(now, &mut commands);
+                }
+            }
+            IslandState::Resynchronising => {
+                if self.grid_healthy == Some(false) {
+                    log::warn!("Grid disturbed during resynchronisation, staying islanded");
+                    self.set_state(IslandState::Islanded, now);
+                } else if bus_dead {
+                    // Nothing to synchronise: connect the dead bus straight to the grid
+                    self.begin_reconnect(now, true, &mut commands);
+                } else if let (Some(v), Some(f)) = (inputs.grid_voltage, inputs.grid_frequency) {
+                    let voltage_match = (v - inputs.site_voltage).abs() / nominal_voltage * 100.0 <= self.config.sync_voltage_tolerance;
+                    let frequency_match = (f - inputs.site_frequency).abs() <= self.config.sync_frequency_tolerance;
+                    let in_sync = Self::held(
+                        &mut self.in_sync_since,
+                        voltage_match && frequency_match,
+                        now,
+                        Duration::from_secs(self.config.sync_time),
+                    );
+                    if in_sync {
+                        log::info!("Site in sync with the grid, closing the grid breaker");
+                        self.begin_reconnect(now, false, &mut commands);
+                    }
+                }
+            }
+            IslandState::Reconnecting => {
+                let elapsed = now.duration_since(self.state_since);
+                if inputs.breaker_closed == Some(true) {
+                    log::info!("Grid breaker closed, PCS following the grid");
+                    self.reconnected(now, &mut commands);
+                } else if self.grid_healthy == Some(false) || elapsed >= Duration::from_millis(self.config.breaker_timeout_ms) {
+                    log::warn!("Grid breaker did not close, staying islanded");
+                    if self.dead_bus_reconnect {
+                        self.dead_bus_since = None;
+                    }
+                    commands.pcs_mode = Some(PcsMode::OffGrid);
+                    self.set_state(IslandState::Islanded, now);
+                }
+            }
+            IslandState::BlackStart => {
+                if grid_stable {
+                    if self.step == BlackStartStep::Energising && site_pct >= self.config.live_bus_voltage {
+                        self.set_state(IslandState::Resynchronising, now);
+                    } else {
+                        // Grid came back while the bus is dead; no synchronisation needed
+                        log::info!("Grid restored during black start, reconnecting");
+                        self.begin_reconnect(now, true, &mut commands);
+                    }
+                    return commands;
+                }
+                let elapsed = now.duration_since(self.step_since);
+                match self.step {
+                    BlackStartStep::BatteryOnline if inputs.battery_online => {
+                        commands.pcs_mode = Some(PcsMode::OffGrid);
+                        self.set_step(BlackStartStep::Energising, now);
+                    }
+                    BlackStartStep::Energising if site_pct >= self.config.live_bus_voltage && !inputs.pcs_fault => {
+                        log::info!("Site bus energised after black start attempt {}", self.attempts);
+                        self.attempts = 0;
+                        self.dead_bus_since = None;
+                        self.set_shed(ShedLevel::NonCritical, &mut commands);
+                        self.set_state(IslandState::Islanded, now);
+                    }
+                    BlackStartStep::Retrying if elapsed >= Duration::from_secs(self.config.black_start_retry_delay) => {
+                        self.begin_black_start(now, &mut commands);
+                    }
+                    BlackStartStep::BatteryOnline | BlackStartStep::Energising
+                        if now.duration_since(self.attempt_since) >= Duration::from_secs(self.config.black_start_timeout) =>
+                    {
+                        commands.pcs_mode = Some(PcsMode::Standby);
+                        if self.attempts >= self.config.max_black_start_attempts {
+                            let fault = format!("Black start failed after {} attempts", self.attempts);
+                            self.set_fault(fault, now, &mut commands);
+                        } else {
+                            log::warn!("Black start attempt {} timed out", self.attempts);
+                            self.set_step(BlackStartStep::Retrying, now);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            IslandState::Fault => {
+                if grid_stable {
+                    // The PCS is in standby, so the bus is dead or already on the grid
+                    log::info!("Grid restored, clearing islanding fault");
+                    self.fault = None;
+                    self.begin_reconnect(now, true, &mut commands);
+                }
+            }
+        }
+        commands
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> IslandingStatus {
+        IslandingStatus {
+            state: format!("{:?}", self.state),
+            seconds_in_state: self.state_since.elapsed().as_secs(),
+            grid_healthy: self.grid_healthy,
+            breaker_closed: self.breaker_closed,
+            shed_level: format!("{:?}", self.shed),
+            black_start_attempts: self.attempts,
+            transitions: self.transitions,
+            last_event: self.last_event.clone(),
+            fault: self.fault.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_up(breaker_closed: bool) -> IslandInputs {
+        IslandInputs {
+            grid_voltage: Some(230.0),
+            grid_frequency: Some(50.0),
+            site_voltage: 230.0,
+            site_frequency: 50.0,
+            pcs_fault: false,
+            battery_online: true,
+            breaker_closed: Some(breaker_closed),
+        }
+    }
+
+    fn grid_down(breaker_closed: bool) -> IslandInputs {
+        IslandInputs {
+            grid_voltage: Some(0.0),
+            grid_frequency: Some(0.0),
+            ..grid_up(breaker_closed)
+        }
+    }
+
+    fn at(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    /// Drive a controller from on grid to islanded
+    fn islanded(start: Instant) -> IslandController {
+        let mut controller = IslandController::new(IslandingConfig::default());
+        controller.update(at(start, 0), grid_down(true));
+        controller.update(at(start, 5), grid_down(true));
+        controller.update(at(start, 10), grid_down(false));
+        assert_eq!(controller.state, IslandState::Islanded);
+        controller
+    }
+
+    #[test]
+    fn grid_loss_opens_the_breaker_before_forming_the_bus() {
+        let start = Instant::now();
+        let mut controller = IslandController::new(IslandingConfig::default());
+
+        controller.update(at(start, 0), grid_down(true));
+        assert_eq!(controller.state, IslandState::OnGrid);
+        assert!(controller.breaker_command());
+
+        let commands = controller.update(at(start, 5), grid_down(true));
+        assert_eq!(controller.state, IslandState::Disconnecting);
+        assert!(!controller.breaker_command());
+        assert_eq!(commands.pcs_mode, Some(PcsMode::Standby));
+        assert_eq!(commands.shed, Some(ShedLevel::NonCritical));
+        assert!(!controller.grid_forming());
+
+        // Still closed: the PCS must not form the bus yet
+        controller.update(at(start, 7), grid_down(true));
+        assert_eq!(controller.state, IslandState::Disconnecting);
+
+        let commands = controller.update(at(start, 10), grid_down(false));
+        assert_eq!(controller.state, IslandState::Islanded);
+        assert_eq!(commands.pcs_mode, Some(PcsMode::OffGrid));
+        assert!(controller.grid_forming());
+    }
+
+    #[test]
+    fn breaker_that_fails_to_open_latches_a_fault() {
+        let start = Instant::now();
+        let mut controller = IslandController::new(IslandingConfig::default());
+        controller.update(at(start, 0), grid_down(true));
+        controller.update(at(start, 5), grid_down(true));
+
+        let commands = controller.update(at(start, 15), grid_down(true));
+        assert_eq!(controller.state, IslandState::Fault);
+        assert_eq!(commands.pcs_mode, Some(PcsMode::Standby));
+        assert!(controller.status().fault.is_some());
+
+        controller.reset_fault();
+        assert_eq!(controller.state, IslandState::Disconnecting);
+    }
+
+    #[test]
+    fn protection_trip_while_on_grid_transfers_to_island() {
+        let start = Instant::now();
+        let mut controller = IslandController::new(IslandingConfig::default());
+
+        controller.update(at(start, 0), grid_up(false));
+        assert_eq!(controller.state, IslandState::Disconnecting);
+        controller.update(at(start, 5), grid_up(false));
+        assert_eq!(controller.state, IslandState::Islanded);
+    }
+
+    #[test]
+    fn reconnects_after_sync_and_breaker_close_feedback() {
+        let start = Instant::now();
+        let mut controller = islanded(start);
+
+        controller.update(at(start, 20), grid_up(false));
+        controller.update(at(start, 320), grid_up(false));
+        assert_eq!(controller.state, IslandState::Resynchronising);
+        assert!(!controller.breaker_command());
+
+        controller.update(at(start, 325), grid_up(false));
+        let commands = controller.update(at(start, 330), grid_up(false));
+        assert_eq!(controller.state, IslandState::Reconnecting);
+        assert!(controller.breaker_command());
+        // The PCS keeps forming the bus until the breaker confirms closed
+        assert_eq!(commands.pcs_mode, None);
+        assert_eq!(controller.pcs_mode(), Some(PcsMode::OffGrid));
+
+        let commands = controller.update(at(start, 335), grid_up(true));
+        assert_eq!(controller.state, IslandState::OnGrid);
+        assert_eq!(commands.pcs_mode, Some(PcsMode::GridTie));
+
+        // Shed chargers return after the restore delay
+        let commands = controller.update(at(start, 365), grid_up(true));
+        assert_eq!(commands.shed, Some(ShedLevel::None));
+    }
+
+    #[test]
+    fn breaker_that_fails_to_close_stays_islanded() {
+        let start = Instant::now();
+        let mut controller = islanded(start);
+        controller.update(at(start, 20), grid_up(false));
+        controller.update(at(start, 320), grid_up(false));
+        controller.update(at(start, 325), grid_up(false));
+        controller.update(at(start, 330), grid_up(false));
+        assert_eq!(controller.state, IslandState::Reconnecting);
+
+        let commands = controller.update(at(start, 340), grid_up(false));
+        assert_eq!(controller.state, IslandState::Islanded);
+        assert_eq!(commands.pcs_mode, Some(PcsMode::OffGrid));
+    }
+
+    #[test]
+    fn dead_bus_black_start_energises_the_site() {
+        let start = Instant::now();
+        let mut controller = islanded(start);
+        let dead = IslandInputs { site_voltage: 0.0, site_frequency: 0.0, ..grid_down(false) };
+
+        controller.update(at(start, 15), dead);
+        let commands = controller.update(at(start, 25), dead);
+        assert_eq!(controller.state, IslandState::BlackStart);
+        assert_eq!(commands.pcs_mode, Some(PcsMode::Standby));
+        assert_eq!(commands.shed, Some(ShedLevel::All));
+
+        let commands = controller.update(at(start, 30), dead);
+        assert_eq!(commands.pcs_mode, Some(PcsMode::OffGrid));
+
+        let commands = controller.update(at(start, 35), grid_down(false));
+        assert_eq!(controller.state, IslandState::Islanded);
+        assert_eq!(commands.shed, Some(ShedLevel::NonCritical));
+    }
+
+    #[test]
+    fn meter_loss_falls_back_to_the_pcs_only_on_grid() {
+        let start = Instant::now();
+        let mut controller = IslandController::new(IslandingConfig::default());
+        let no_meter = IslandInputs { grid_voltage: None, grid_frequency: None, ..grid_up(true) };
+
+        controller.update(at(start, 0), no_meter);
+        assert_eq!(controller.status().grid_healthy, Some(true));
+
+        let mut controller = islanded(start);
+        controller.update(at(start, 20), IslandInputs { breaker_closed: Some(false), ..no_meter });
+        assert_eq!(controller.status().grid_healthy, None);
+    }
+}
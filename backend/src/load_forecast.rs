@@ -0,0 +1,205 @@
+// 充电负荷预测
+// Charging load forecast learned from historical charger power by time of day and day of week,
+// with holiday overrides and probabilistic (P10/P50/P90) output
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use crate::types::LoadForecastPoint;
+
+/// Number of 15-minute slots per day
+const SLOTS_PER_DAY: usize = 96;
+/// Seven weekday profiles plus one holiday profile
+const PROFILE_COUNT: usize = 8;
+const HOLIDAY_PROFILE: usize = 7;
+/// z-scores for the 10th/90th percentiles of a normal distribution
+const Z_P90: f32 = 1.2816;
+
+/// Load forecaster configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadForecastConfig {
+    /// Holidays ("YYYY-MM-DD") learned and forecast with the holiday profile
+    pub holidays: Vec<String>,
+    /// Weight of a new day in the learned profile (0-1)
+    pub learning_rate: f32,
+    /// File the learned profile is persisted to
+    pub profile_path: Option<String>,
+}
+
+impl Default for LoadForecastConfig {
+    fn default() -> Self {
+        Self {
+            holidays: Vec::new(),
+            learning_rate: 0.2,
+            profile_path: Some("data_cache/load_profile.json".to_string()),
+        }
+    }
+}
+
+/// Learned statistics for one 15-minute slot
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+struct SlotStats {
+    mean: f32,
+    variance: f32,
+    samples: u32,
+}
+
+/// Charging load forecaster
+#[derive(Debug, Clone)]
+pub struct LoadForecaster {
+    config: LoadForecastConfig,
+    holidays: Vec<NaiveDate>,
+    /// Learned statistics indexed by [profile][slot]
+    profiles: Vec<Vec<SlotStats>>,
+    /// Running average of the slot currently being observed
+    current: Option<(NaiveDate, usize)>,
+    accumulated: f32,
+    count: u32,
+    /// The current slot had samples where the demand was unknown
+    censored: bool,
+}
+
+impl LoadForecaster {
+    /// Create a new forecaster, restoring a persisted profile if available
+    ///
+    /// # Arguments
+    /// * `config` - Forecaster configuration
+    pub fn new(config: LoadForecastConfig) -> Self {
+        let holidays = config.holidays.iter()
+            .filter_map(|h| NaiveDate::parse_from_str(h, "%Y-%m-%d").ok())
+            .collect();
+        let profiles = config.profile_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str::<Vec<Vec<SlotStats>>>(&s).ok())
+            .filter(|p| p.len() == PROFILE_COUNT && p.iter().all(|d| d.len() == SLOTS_PER_DAY))
+            .unwrap_or_else(|| vec![vec![SlotStats::default(); SLOTS_PER_DAY]; PROFILE_COUNT]);
+        Self {
+            config,
+            holidays,
+            profiles,
+            current: None,
+            accumulated: 0.0,
+            count: 0,
+            censored: false,
+        }
+    }
+
+    fn slot_of(time: NaiveDateTime) -> usize {
+        (time.hour() * 4 + time.minute() / 15) as usize
+    }
+
+    fn profile_of(&self, date: NaiveDate) -> usize {
+        if self.holidays.contains(&date) {
+            HOLIDAY_PROFILE
+        } else {
+            date.weekday().num_days_from_monday() as usize
+        }
+    }
+
+    /// Record one charger demand sample
+    ///
+    /// A slot containing any unknown sample is left out of the profile rather than learned from
+    /// the part of it that was observed.
+    ///
+    /// # Arguments
+    /// * `time` - Local wall-clock time
+    /// * `charger_power` - Total charger power in kW, or None when the demand is unknown
+    ///   (e.g. the chargers were capped and drew the cap instead)
+    pub fn observe(&mut self, time: NaiveDateTime, charger_power: Option<f32>) {
+        let key = (time.date(), Self::slot_of(time));
+        match self.current {
+            Some(current) if current == key => {}
+            Some((date, slot)) => {
+                self.commit(date, slot);
+                if date != key.0 {
+                    self.save();
+                }
+                self.current = Some(key);
+            }
+            None => self.current = Some(key),
+        }
+        match charger_power {
+            Some(power) => {
+                self.accumulated += power;
+                self.count += 1;
+            }
+            None => self.censored = true,
+        }
+    }
+
+    /// Fold the finished slot average into the learned profile
+    fn commit(&mut self, date: NaiveDate, slot: usize) {
+        let censored = std::mem::take(&mut self.censored);
+        if self.count == 0 || censored {
+            self.accumulated = 0.0;
+            self.count = 0;
+            return;
+        }
+        let value = self.accumulated / self.count as f32;
+        self.accumulated = 0.0;
+        self.count = 0;
+
+        let profile = self.profile_of(date);
+        let stats = &mut self.profiles[profile][slot];
+        if stats.samples == 0 {
+            stats.mean = value;
+            stats.variance = 0.0;
+        } else {
+            let alpha = self.config.learning_rate.max(1.0 / (stats.samples + 1) as f32);
+            let delta = value - stats.mean;
+            stats.mean += alpha * delta;
+            stats.variance = (1.0 - alpha) * (stats.variance + alpha * delta * delta);
+        }
+        stats.samples = stats.samples.saturating_add(1);
+    }
+
+    /// Persist the learned profile
+    fn save(&self) {
+        if let Some(ref path) = self.config.profile_path {
+            match serde_json::to_string(&self.profiles) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save load profile to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize load profile: {}", e),
+            }
+        }
+    }
+
+    /// Statistics used to forecast a slot; holidays without history fall back to Sunday
+    fn stats_for(&self, date: NaiveDate, slot: usize) -> SlotStats {
+        let profile = self.profile_of(date);
+        let stats = self.profiles[profile][slot];
+        if profile == HOLIDAY_PROFILE && stats.samples == 0 {
+            self.profiles[6][slot]
+        } else {
+            stats
+        }
+    }
+
+    /// Forecast charger demand over the next 24 hours
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    ///
+    /// # Returns
+    /// Forecast points at 15-minute resolution starting at the next slot boundary
+    pub fn forecast(&self, now: NaiveDateTime) -> Vec<LoadForecastPoint> {
+        let start = now - Duration::minutes(now.minute() as i64 % 15) - Duration::seconds(now.second() as i64);
+        (1..=SLOTS_PER_DAY as i64)
+            .map(|i| {
+                let time = start + Duration::minutes(i * 15);
+                let stats = self.stats_for(time.date(), Self::slot_of(time));
+                let spread = Z_P90 * stats.variance.sqrt();
+                LoadForecastPoint {
+                    timestamp: time.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    mean: stats.mean,
+                    p10: (stats.mean - spread).max(0.0),
+                    p90: stats.mean + spread,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,203 @@
+// 分级负荷切除
+// Priority-based load shedding: chargers (and later auxiliary loads) belong to a priority
+// class and are shed lowest priority first when power is short, then restored in reverse
+// order with hysteresis and minimum-off times
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::types::{LoadSheddingStatus, LoadTierStatus};
+
+/// Load priority class, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LoadPriority {
+    /// Kept in service; cut equally with the rest only when nothing else is left to shed
+    Critical,
+    /// Depot or fleet vehicles
+    Fleet,
+    /// Public charging, shed first
+    #[default]
+    Public,
+}
+
+impl LoadPriority {
+    /// Highest priority first
+    const ALL: [LoadPriority; 3] = [LoadPriority::Critical, LoadPriority::Fleet, LoadPriority::Public];
+}
+
+/// Load shedding configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadSheddingConfig {
+    /// Priority class by load id; unlisted loads use the default priority
+    pub priorities: HashMap<String, LoadPriority>,
+    /// Priority class of unlisted loads
+    pub default_priority: LoadPriority,
+    /// Allow the critical tier to be shed as well
+    pub shed_critical: bool,
+    /// Spare power required above a tier's load before it is restored, in kW
+    pub restore_margin: f32,
+    /// Minimum time a tier stays shed, in seconds
+    pub min_off_time: u64,
+    /// Time without any charger cap before a tier is restored, in seconds
+    pub restore_delay: u64,
+}
+
+impl Default for LoadSheddingConfig {
+    fn default() -> Self {
+        Self {
+            priorities: HashMap::new(),
+            default_priority: LoadPriority::Public,
+            shed_critical: false,
+            restore_margin: 5.0,
+            min_off_time: 300,
+            restore_delay: 60,
+        }
+    }
+}
+
+/// Per-tier shed state
+#[derive(Debug, Clone, Default)]
+struct TierState {
+    shed_since: Option<Instant>,
+    /// Load the tier was drawing when shed, in kW
+    shed_load: f32,
+    shed_count: u32,
+}
+
+/// Load shedding controller
+#[derive(Debug, Clone)]
+pub struct LoadShedder {
+    config: LoadSheddingConfig,
+    /// Tier state, indexed by LoadPriority
+    tiers: [TierState; 3],
+    /// Last time a charger cap was requested
+    last_cap: Option<Instant>,
+    last_event: Option<String>,
+}
+
+impl LoadShedder {
+    /// Create a new controller
+    ///
+    /// # Arguments
+    /// * `config` - Priority classes, margins and timings
+    pub fn new(config: LoadSheddingConfig) -> Self {
+        Self {
+            config,
+            tiers: Default::default(),
+            last_cap: None,
+            last_event: None,
+        }
+    }
+
+    /// Priority class of a load
+    pub fn priority_of(&self, id: &str) -> LoadPriority {
+        self.config.priorities.get(id).copied().unwrap_or(self.config.default_priority)
+    }
+
+    /// Whether a load is currently shed
+    pub fn is_shed(&self, id: &str) -> bool {
+        self.tiers[self.priority_of(id) as usize].shed_since.is_some()
+    }
+
+    fn sheddable(&self, priority: LoadPriority) -> bool {
+        priority != LoadPriority::Critical || self.config.shed_critical
+    }
+
+    /// Shed tiers, lowest priority first, until the remaining load fits the budget
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `budget` - Power allowed for the loads in kW
+    /// * `loads` - Load drawn by the unshed loads of each tier in kW, indexed by LoadPriority
+    ///
+    /// # Returns
+    /// Tiers shed by this call
+    pub fn shed_for(&mut self, now: Instant, budget: f32, loads: [f32; 3]) -> Vec<LoadPriority> {
+        self.last_cap = Some(now);
+        let mut remaining: f32 = LoadPriority::ALL.iter()
+            .filter(|&&p| self.tiers[p as usize].shed_since.is_none())
+            .map(|&p| loads[p as usize])
+            .sum();
+        let mut shed = Vec::new();
+        for &priority in LoadPriority::ALL.iter().rev() {
+            if remaining <= budget {
+                break;
+            }
+            let load = loads[priority as usize];
+            if self.tiers[priority as usize].shed_since.is_some() || load <= 0.0 || !self.sheddable(priority) {
+                continue;
+            }
+            let tier = &mut self.tiers[priority as usize];
+            tier.shed_since = Some(now);
+            tier.shed_load = load;
+            tier.shed_count += 1;
+            remaining -= load;
+            shed.push(priority);
+            self.last_event = Some(format!("Shed {:?} tier ({:.1} kW) for a {:.1} kW budget", priority, load, budget));
+            log::warn!("Load shedding: {}", self.last_event.as_deref().unwrap_or_default());
+        }
+        shed
+    }
+
+    /// Restore the most recently shed tier once it has been off long enough and fits the budget
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `budget` - Charger cap in force this cycle in kW (None: unconstrained)
+    /// * `loads` - Load drawn by the unshed loads of each tier in kW, indexed by LoadPriority
+    ///
+    /// # Returns
+    /// Tier restored by this call
+    pub fn restore(&mut self, now: Instant, budget: Option<f32>, loads: [f32; 3]) -> Option<LoadPriority> {
+        // Highest shed priority comes back first
+        let priority = LoadPriority::ALL.iter().copied().find(|&p| self.tiers[p as usize].shed_since.is_some())?;
+        let tier = &self.tiers[priority as usize];
+        let off_long_enough = tier.shed_since
+            .map(|t| now.duration_since(t) >= Duration::from_secs(self.config.min_off_time))
+            .unwrap_or(false);
+        let fits = match budget {
+            Some(budget) => {
+                let unshed: f32 = LoadPriority::ALL.iter()
+                    .filter(|&&p| self.tiers[p as usize].shed_since.is_none())
+                    .map(|&p| loads[p as usize])
+                    .sum();
+                budget >= unshed + tier.shed_load + self.config.restore_margin
+            }
+            None => self.last_cap
+                .map(|t| now.duration_since(t) >= Duration::from_secs(self.config.restore_delay))
+                .unwrap_or(true),
+        };
+        if !off_long_enough || !fits {
+            return None;
+        }
+        let off = tier.shed_since.map(|t| now.duration_since(t).as_secs()).unwrap_or(0);
+        self.tiers[priority as usize].shed_since = None;
+        self.last_event = Some(format!("Restored {:?} tier after {} s", priority, off));
+        log::info!("Load shedding: {}", self.last_event.as_deref().unwrap_or_default());
+        Some(priority)
+    }
+
+    /// Snapshot for EmsStatus
+    ///
+    /// # Arguments
+    /// * `shed_loads` - Ids of the loads currently shed
+    pub fn status(&self, shed_loads: Vec<String>) -> LoadSheddingStatus {
+        LoadSheddingStatus {
+            tiers: LoadPriority::ALL.iter()
+                .map(|&p| {
+                    let tier = &self.tiers[p as usize];
+                    LoadTierStatus {
+                        priority: format!("{:?}", p),
+                        shed: tier.shed_since.is_some(),
+                        seconds_shed: tier.shed_since.map(|t| t.elapsed().as_secs()).unwrap_or(0),
+                        shed_load: tier.shed_load,
+                        shed_count: tier.shed_count,
+                    }
+                })
+                .collect(),
+            shed_loads,
+            last_event: self.last_event.clone(),
+        }
+    }
+}
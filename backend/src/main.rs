@@ -1,19 +1,42 @@
 // 纯 Rust 控制核心 (可独立运行)
 // Placeholder: EMS 系统主入口
 
+mod battery_health;
+mod battery_limits;
+mod battery_racks;
+mod cell_analytics;
+mod contactor;
+mod curtailment;
 mod ems_core;
 mod devices;
 mod drivers;
+mod fuel;
+mod genset_control;
+mod genset_fleet;
+mod genset_min_load;
+mod grid_limit;
+mod grid_support;
+mod islanding;
+mod load_forecast;
+mod load_shedding;
+mod maintenance;
+mod mpc;
+mod pcs_group;
+mod pv_forecast;
+mod reactive_power;
+mod soc_estimator;
+mod sync_check;
+mod tariff;
 mod types;
 
-use crate::ems_core::EmsController;
+use crate::ems_core::{EmsController, EmsMode};
 
 use std::time::Duration;
 use std::thread;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc;
-use crate::types::{EmsStatus, GpsData};
-use crate::devices::{charger, bms, pcs, pv_dcdc, genset};
+use crate::types::{CellHeatmap, DispatchPlanStep, EmsStatus, GpsData, LoadForecastPoint, PvForecastPoint, SohTrendPoint};
+use crate::devices::{charger, bms, pcs, pv_dcdc, genset, meter, breaker};
 use crate::drivers::{can, modbus, gps_4g, cloud};
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -32,6 +55,8 @@ struct SystemState {
     pcs: Arc<Mutex<pcs::PcsDevice>>,
     pv_dcdc: Arc<Mutex<pv_dcdc::PvDcdcDevice>>,
     genset: Arc<Mutex<genset::GensetDevice>>,
+    grid_meter: Option<Arc<Mutex<meter::EnergyMeterDevice>>>,
+    grid_breaker: Option<Arc<Mutex<breaker::GridBreakerDevice>>>,
     // Drivers
     can_driver: Arc<Mutex<can::CanDriver>>,
     modbus_driver: Arc<Mutex<modbus::ModbusDriver>>,
@@ -63,6 +88,87 @@ struct Config {
     genset_host: String,
     genset_port: u16,
     can_interface: String,
+    #[serde(default)]
+    grid_meter: Option<GridMeterConfig>,
+    #[serde(default)]
+    grid_breaker: Option<GridBreakerConfig>,
+    #[serde(default)]
+    tou_dispatch: Option<tariff::TouDispatchConfig>,
+    #[serde(default)]
+    load_forecast: Option<load_forecast::LoadForecastConfig>,
+    #[serde(default)]
+    pv_forecast: Option<pv_forecast::PvForecastConfig>,
+    #[serde(default)]
+    mpc: Option<mpc::MpcConfig>,
+    #[serde(default)]
+    genset_control: Option<genset_control::GensetControlConfig>,
+    #[serde(default)]
+    genset_fleet: Option<genset_fleet::GensetFleetConfig>,
+    #[serde(default)]
+    sync_check: Option<sync_check::SyncCheckConfig>,
+    #[serde(default)]
+    genset_min_load: Option<genset_min_load::MinLoadConfig>,
+    #[serde(default)]
+    fuel: Option<fuel::FuelConfig>,
+    #[serde(default)]
+    maintenance: Option<maintenance::MaintenanceConfig>,
+    #[serde(default)]
+    soc_estimator: Option<soc_estimator::SocEstimatorConfig>,
+    #[serde(default)]
+    battery_health: Option<battery_health::BatteryHealthConfig>,
+    #[serde(default)]
+    battery_limits: Option<battery_limits::BatteryLimitConfig>,
+    #[serde(default)]
+    battery_racks: Option<battery_racks::BatteryRackConfig>,
+    #[serde(default)]
+    pcs_group: Option<pcs_group::PcsGroupConfig>,
+    #[serde(default)]
+    cell_analytics: Option<cell_analytics::CellAnalyticsConfig>,
+    #[serde(default)]
+    curtailment: Option<curtailment::CurtailmentConfig>,
+    #[serde(default)]
+    contactor: Option<contactor::ContactorConfig>,
+    #[serde(default)]
+    import_limit: Option<grid_limit::ImportLimitConfig>,
+    #[serde(default)]
+    export_limit: Option<grid_limit::ExportLimitConfig>,
+    #[serde(default)]
+    reactive_power: Option<reactive_power::ReactivePowerConfig>,
+    #[serde(default)]
+    grid_support: Option<grid_support::GridSupportConfig>,
+    #[serde(default)]
+    islanding: Option<islanding::IslandingConfig>,
+    #[serde(default)]
+    load_shedding: Option<load_shedding::LoadSheddingConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GridMeterConfig {
+    id: String,
+    host: String,
+    port: u16,
+    #[serde(default = "default_meter_unit_id")]
+    unit_id: u8,
+    /// Meter CTs face the load side, so import reads as negative power
+    #[serde(default)]
+    reversed: bool,
+}
+
+fn default_meter_unit_id() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GridBreakerConfig {
+    id: String,
+    host: String,
+    port: u16,
+    #[serde(default = "default_meter_unit_id")]
+    unit_id: u8,
+    /// Coil commanding the breaker closed
+    command_coil: u16,
+    /// Discrete input reporting the breaker closed
+    feedback_input: u16,
 }
 
 // Tauri commands for data interface
@@ -86,6 +192,36 @@ fn get_current_timestamp(state: State<'_, Arc<SystemState>>) -> String {
     state.current_timestamp.lock().expect("Failed to lock current_timestamp").clone()
 }
 
+#[command]
+fn get_pv_forecast(state: State<'_, Arc<SystemState>>) -> Vec<PvForecastPoint> {
+    state.ems_controller.lock().expect("Failed to lock ems_controller").get_pv_forecast()
+}
+
+#[command]
+fn get_load_forecast(state: State<'_, Arc<SystemState>>) -> Vec<LoadForecastPoint> {
+    state.ems_controller.lock().expect("Failed to lock ems_controller").get_load_forecast()
+}
+
+#[command]
+fn get_dispatch_plan(state: State<'_, Arc<SystemState>>) -> Vec<DispatchPlanStep> {
+    state.ems_controller.lock().expect("Failed to lock ems_controller").get_dispatch_plan()
+}
+
+#[command]
+fn get_cell_heatmap(state: State<'_, Arc<SystemState>>) -> CellHeatmap {
+    state.ems_controller.lock().expect("Failed to lock ems_controller").get_cell_heatmap()
+}
+
+#[command]
+fn get_soh_trend(state: State<'_, Arc<SystemState>>) -> Vec<SohTrendPoint> {
+    state.ems_controller.lock().expect("Failed to lock ems_controller").get_soh_trend()
+}
+
+#[command]
+fn record_genset_service(state: State<'_, Arc<SystemState>>, task: String, notes: Option<String>) -> Result<(), String> {
+    state.ems_controller.lock().expect("Failed to lock ems_controller").record_genset_service(&task, notes)
+}
+
 #[command]
 fn get_device_statuses(state: State<'_, Arc<SystemState>>) -> serde_json::Value {
     let charger = state.charger.lock().expect("Failed to lock charger").get_cached_status();
@@ -175,6 +311,29 @@ fn send_control_command(state: State<'_, Arc<SystemState>>, command: String) ->
                         "Genset stopped".to_string()
                     }
                 }
+                "reset_genset_lockout" => {
+                    state.ems_controller.lock().expect("Failed to lock ems_controller").reset_genset_lockout();
+                    "Genset lockout cleared".to_string()
+                }
+                "acknowledge_fuel_theft" => {
+                    state.ems_controller.lock().expect("Failed to lock ems_controller").acknowledge_fuel_theft();
+                    "Fuel level drop acknowledged".to_string()
+                }
+                "battery_online" | "battery_offline" => {
+                    let online = action == "battery_online";
+                    match state.ems_controller.lock().expect("Failed to lock ems_controller").set_battery_online(online) {
+                        Ok(()) => format!("Battery {} requested", if online { "online" } else { "offline" }),
+                        Err(e) => e,
+                    }
+                }
+                "reset_contactor_fault" => {
+                    state.ems_controller.lock().expect("Failed to lock ems_controller").reset_contactor_fault();
+                    "Contactor fault cleared".to_string()
+                }
+                "reset_islanding_fault" => {
+                    state.ems_controller.lock().expect("Failed to lock ems_controller").reset_islanding_fault();
+                    "Islanding fault cleared".to_string()
+                }
                 "set_charger_power" => {
                     if let Some(power) = cmd.get("power").and_then(|v| v.as_f64()) {
                         let charger = state.charger.lock().expect("Failed to lock charger");
@@ -187,6 +346,24 @@ fn send_control_command(state: State<'_, Arc<SystemState>>, command: String) ->
                         "Missing power parameter".to_string()
                     }
                 }
+                "set_ems_mode" => {
+                    if let Some(mode_str) = cmd.get("mode").and_then(|v| v.as_str()) {
+                        let mode = match mode_str {
+                            "normal" => EmsMode::Normal,
+                            "peak_shaving" => EmsMode::PeakShaving,
+                            "time_of_use" => EmsMode::TimeOfUse,
+                            "optimized" => EmsMode::Optimized,
+                            _ => return "Invalid mode".to_string(),
+                        };
+                        let mut ems_controller = state.ems_controller.lock().expect("Failed to lock ems_controller");
+                        match ems_controller.set_mode(mode.clone()) {
+                            Ok(()) => format!("EMS mode set to {:?}", mode),
+                            Err(e) => format!("Failed to set EMS mode: {}", e),
+                        }
+                    } else {
+                        "Missing mode parameter".to_string()
+                    }
+                }
                 "set_threshold" => {
                     if let Some(threshold) = cmd.get("soc_threshold").and_then(|v| v.as_f64()) {
                         let mut status = state.ems_status.lock().expect("Failed to lock ems_status");
@@ -270,6 +447,12 @@ fn main() {
             get_system_health,
             get_current_timestamp,
             get_device_statuses,
+            get_pv_forecast,
+            get_load_forecast,
+            get_dispatch_plan,
+            get_soh_trend,
+            get_cell_heatmap,
+            record_genset_service,
             send_control_command
         ])
         ;
@@ -282,6 +465,15 @@ fn initialize_system(config: &Config) -> SystemState {
     let pcs = Arc::new(Mutex::new(pcs::PcsDevice::new(config.pcs_id.clone(), &config.pcs_host, config.pcs_port).expect("Failed to initialize PCS device")));
     let pv_dcdc = Arc::new(Mutex::new(pv_dcdc::PvDcdcDevice::new(config.pv_dcdc_id.clone(), &config.pv_dcdc_host, config.pv_dcdc_port).expect("Failed to initialize PV DCDC device")));
     let genset = Arc::new(Mutex::new(genset::GensetDevice::new(config.genset_id.clone(), &config.genset_host, config.genset_port).expect("Failed to initialize genset device")));
+    let grid_meter = config.grid_meter.as_ref().map(|m| {
+        let mut device = meter::EnergyMeterDevice::new(m.id.clone(), &m.host, m.port, m.unit_id).expect("Failed to initialize grid meter");
+        device.reversed = m.reversed;
+        Arc::new(Mutex::new(device))
+    });
+    let grid_breaker = config.grid_breaker.as_ref().map(|b| {
+        Arc::new(Mutex::new(breaker::GridBreakerDevice::new(b.id.clone(), &b.host, b.port, b.unit_id, b.command_coil, b.feedback_input)
+            .expect("Failed to initialize grid breaker")))
+    });
     let can_driver = Arc::new(Mutex::new(can::CanDriver::new(&config.can_interface)));
     let modbus_driver = Arc::new(Mutex::new(modbus::ModbusDriver::new()));
     let gps_4g_driver = Arc::new(Mutex::new(gps_4g::Gps4gDriver::new().expect("Failed to initialize GPS 4G driver")));
@@ -298,8 +490,153 @@ fn initialize_system(config: &Config) -> SystemState {
     ems_controller.add_battery_device(battery.clone());
     ems_controller.add_pcs_device(pcs.clone());
     ems_controller.add_genset_device(genset.clone());
+    if let Some(ref grid_meter) = grid_meter {
+        ems_controller.add_grid_meter(grid_meter.clone());
+    }
+    if let Some(ref grid_breaker) = grid_breaker {
+        ems_controller.add_grid_breaker(grid_breaker.clone());
+    }
     ems_controller.add_charger_device(charger.clone());
+    if let Some(load_forecast) = config.load_forecast.clone() {
+        ems_controller.set_load_forecast_config(load_forecast);
+    }
+    if let Some(pv_forecast) = config.pv_forecast.clone() {
+        ems_controller.set_pv_forecast_config(pv_forecast);
+    }
+    if let Some(mpc) = config.mpc.clone() {
+        ems_controller.set_mpc_config(mpc);
+    }
+    if let Some(genset_control) = config.genset_control.clone() {
+        ems_controller.set_genset_control_config(genset_control);
+    }
+    if let Some(fleet_config) = config.genset_fleet.clone() {
+        let control = config.genset_control.clone().unwrap_or_default();
+        let mut fleet = genset_fleet::GensetFleet::new(fleet_config.clone(), control);
+        for unit in fleet_config.units {
+            // The primary genset is shared rather than connected twice
+            let device = if unit.id == config.genset_id {
+                genset.clone()
+            } else {
+                // An unreachable unit joins unconnected: its reads fail and the fleet stages around it
+                let device = genset::GensetDevice::new(unit.id.clone(), &unit.host, unit.port).unwrap_or_else(|e| {
+                    log::warn!("Fleet genset {} unreachable, added as unavailable: {}", unit.id, e);
+                    genset::GensetDevice::unconnected(unit.id.clone())
+                });
+                Arc::new(Mutex::new(device))
+            };
+            fleet.add_unit(unit, device);
+        }
+        ems_controller.set_genset_fleet(fleet);
+    }
+    if let Some(sync_check) = config.sync_check.clone() {
+        ems_controller.set_sync_check_config(sync_check);
+    }
+    if let Some(min_load) = config.genset_min_load.clone() {
+        ems_controller.set_genset_min_load_config(min_load);
+    }
+    if let Some(fuel) = config.fuel.clone() {
+        ems_controller.set_fuel_config(fuel);
+    }
+    if let Some(maintenance) = config.maintenance.clone() {
+        ems_controller.set_maintenance_config(maintenance);
+    }
+    if let Some(soc_estimator) = config.soc_estimator.clone() {
+        ems_controller.set_soc_estimator_config(soc_estimator);
+    }
+    if let Some(battery_health) = config.battery_health.clone() {
+        ems_controller.set_battery_health_config(battery_health);
+    }
+    if let Some(battery_limits) = config.battery_limits.clone() {
+        ems_controller.set_battery_limit_config(battery_limits);
+    }
+    if let Some(rack_config) = config.battery_racks.clone() {
+        let mut racks = battery_racks::BatteryRackGroup::new(rack_config.clone());
+        for rack in rack_config.racks {
+            // The primary battery and PCS are shared rather than opened twice
+            let (rack_battery, rack_pcs) = if rack.id == config.battery_id {
+                (battery.clone(), pcs.clone())
+            } else {
+                (
+                    Arc::new(Mutex::new(bms::BatteryDevice::new(rack.id.clone(), &rack.battery_interface).expect("Failed to initialize battery rack"))),
+                    Arc::new(Mutex::new(pcs::PcsDevice::new(rack.pcs_id.clone(), &rack.pcs_host, rack.pcs_port).expect("Failed to initialize rack PCS"))),
+                )
+            };
+            racks.add_rack(rack, rack_battery, rack_pcs);
+        }
+        ems_controller.set_battery_racks(racks);
+    }
+    if let Some(group_config) = config.pcs_group.clone() {
+        if config.battery_racks.is_some() {
+            log::warn!("pcs_group ignored: battery racks drive their own PCS units");
+        } else {
+            let mut group = pcs_group::PcsGroup::new(group_config.clone());
+            for unit in group_config.units {
+                // The primary PCS is shared rather than connected twice
+                let device = if unit.id == config.pcs_id {
+                    pcs.clone()
+                } else {
+                    // An unreachable unit joins unconnected: its reads fail and it is held faulted
+                    let device = pcs::PcsDevice::new(unit.id.clone(), &unit.host, unit.port).unwrap_or_else(|e| {
+                        log::warn!("PCS unit {} unreachable, added as unavailable: {}", unit.id, e);
+                        pcs::PcsDevice::unconnected(unit.id.clone())
+                    });
+                    Arc::new(Mutex::new(device))
+                };
+                group.add_unit(unit, device);
+            }
+            ems_controller.set_pcs_group(group);
+        }
+    }
+    if let Some(cell_analytics) = config.cell_analytics.clone() {
+        ems_controller.set_cell_analytics_config(cell_analytics);
+    }
+    // Per-cell bursts are read on a second connection so they never stall the control cycle
+    match bms::BatteryDevice::new(format!("{}-cells", config.battery_id), &config.battery_interface) {
+        Ok(device) => ems_controller.set_cell_detail_reader(device),
+        Err(e) => log::warn!("Per-cell battery data unavailable: {}", e),
+    }
+    if let Some(curtailment) = config.curtailment.clone() {
+        ems_controller.set_curtailment_config(curtailment);
+    }
+    if let Some(contactor) = config.contactor.clone() {
+        ems_controller.set_contactor_config(contactor);
+    }
+    if let Some(load_shedding) = config.load_shedding.clone() {
+        ems_controller.set_load_shedding_config(load_shedding);
+    }
+    if let Some(islanding) = config.islanding.clone() {
+        if grid_breaker.is_none() {
+            log::error!("Islanding configured without a grid breaker; island operation disabled");
+        } else {
+            if grid_meter.is_none() {
+                log::warn!("Islanding configured without a grid meter; grid loss cannot be detected");
+            }
+            ems_controller.set_islanding_config(islanding);
+        }
+    }
+    if let Some(grid_support) = config.grid_support.clone() {
+        ems_controller.set_grid_support_config(grid_support);
+    }
+    if let Some(reactive_power) = config.reactive_power.clone() {
+        ems_controller.set_reactive_power_config(reactive_power);
+    }
+    if let Some(import_limit) = config.import_limit.clone() {
+        if grid_meter.is_none() {
+            log::warn!("Import limit configured without a grid meter; chargers will stay at the fail-safe cap");
+        }
+        ems_controller.set_import_limit_config(import_limit);
+    }
+    if let Some(export_limit) = config.export_limit.clone() {
+        if grid_meter.is_none() {
+            log::warn!("Export limit configured without a grid meter; PV will stay capped at local consumption");
+        }
+        ems_controller.set_export_limit_config(export_limit);
+    }
     ems_controller.start().expect("Failed to start EMS controller");
+    if let Some(tou) = config.tou_dispatch.clone() {
+        ems_controller.set_tou_config(tou);
+        ems_controller.set_mode(EmsMode::TimeOfUse).expect("Failed to enable time-of-use dispatch");
+    }
     let ems_controller = Arc::new(Mutex::new(ems_controller));
 
     SystemState {
@@ -308,6 +645,8 @@ fn initialize_system(config: &Config) -> SystemState {
         pcs,
         pv_dcdc,
         genset,
+        grid_meter,
+        grid_breaker,
         can_driver,
         modbus_driver,
         gps_4g_driver,
@@ -326,7 +665,7 @@ fn perform_health_checks(state: &Arc<SystemState>) -> bool {
 
     // Define a list of health checks for drivers and devices
     // Each check is a tuple of (name, check_function)
-    let checks: Vec<(&str, Box<dyn Fn() -> bool>)> = vec![
+    let mut checks: Vec<(&str, Box<dyn Fn() -> bool>)> = vec![
         ("CAN Driver", Box::new(|| state.can_driver.lock().expect("Failed to lock can_driver").is_connected())),
         ("Modbus Driver", Box::new(|| state.modbus_driver.lock().expect("Failed to lock modbus_driver").is_connected())),
         ("Cloud Driver", Box::new(|| state.cloud_driver.lock().expect("Failed to lock cloud_driver").is_connected())),
@@ -340,6 +679,12 @@ fn perform_health_checks(state: &Arc<SystemState>) -> bool {
         ("PV DCDC Device", Box::new(|| state.pv_dcdc.lock().expect("Failed to lock pv_dcdc").is_connected())),
         ("Genset Device", Box::new(|| state.genset.lock().expect("Failed to lock genset").is_connected())),
     ];
+    if let Some(ref grid_meter) = state.grid_meter {
+        checks.push(("Grid Meter", Box::new(move || grid_meter.lock().expect("Failed to lock grid_meter").is_connected())));
+    }
+    if let Some(ref grid_breaker) = state.grid_breaker {
+        checks.push(("Grid Breaker", Box::new(move || grid_breaker.lock().expect("Failed to lock grid_breaker").is_connected())));
+    }
 
     let mut all_healthy = true;
     let mut failed_checks = Vec::new();
@@ -441,9 +786,11 @@ async fn data_collection_thread_async(state: Arc<SystemState>, data_tx: mpsc::Se
             let state = state.clone();
             async move {
                 tokio::task::spawn_blocking(move || {
+                    // Take the health snapshot before locking the battery; the control cycle locks them in the other order
+                    let health = state.ems_controller.lock().expect("Failed to lock ems_controller").get_status().battery_health;
                     let battery = state.battery.lock().expect("Failed to lock battery");
                     let status = battery.read_status().unwrap_or_default();
-                    serde_json::json!({"status": status})
+                    serde_json::json!({"status": status, "health": health})
                 }).await.unwrap_or(serde_json::Value::Null)
             }
         });
@@ -602,7 +949,9 @@ fn power_control_thread(state: Arc<SystemState>) {
     loop {
         // Run EMS control cycle for power balancing
         {
+            let gps_data = state.gps_data.lock().expect("Failed to lock gps_data").clone();
             let mut ems_controller = state.ems_controller.lock().expect("Failed to lock ems_controller");
+            ems_controller.set_site_location(&gps_data);
             if let Err(e) = ems_controller.run_control_cycle() {
                 log::error!("EMS control cycle failed: {}", e);
             }
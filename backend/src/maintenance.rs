@@ -0,0 +1,292 @@
+// 发电机预防性维护计划
+// Genset preventive maintenance planner: service intervals by engine hours and calendar time,
+// completed service records, upcoming/overdue alerts and automatic exercise runs
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use crate::types::{GensetStatus, MaintenanceItem, MaintenanceStatus};
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// One recurring service task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceInterval {
+    /// Task name used when recording completed service
+    pub name: String,
+    /// Engine hours between services (None if not hour-based)
+    pub interval_hours: Option<u32>,
+    /// Days between services (None if not calendar-based)
+    pub interval_days: Option<u32>,
+}
+
+/// Maintenance planner configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Service tasks; whichever interval expires first makes a task due
+    pub intervals: Vec<ServiceInterval>,
+    /// Engine hours before due at which a task is reported as upcoming
+    pub warning_hours: u32,
+    /// Days before due at which a task is reported as upcoming
+    pub warning_days: u32,
+    /// Run the genset unloaded after this many idle days (0 disables exercise runs)
+    pub exercise_idle_days: u32,
+    /// Exercise run duration in minutes
+    pub exercise_minutes: u32,
+    /// Local hour at which exercise runs may start
+    pub exercise_hour: u32,
+    /// File service records are persisted to
+    pub record_path: Option<String>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            intervals: vec![
+                ServiceInterval { name: "oil_change".to_string(), interval_hours: Some(250), interval_days: Some(180) },
+                ServiceInterval { name: "filters".to_string(), interval_hours: Some(500), interval_days: Some(365) },
+                ServiceInterval { name: "major_service".to_string(), interval_hours: Some(2000), interval_days: Some(730) },
+            ],
+            warning_hours: 25,
+            warning_days: 14,
+            exercise_idle_days: 7,
+            exercise_minutes: 30,
+            exercise_hour: 10,
+            record_path: Some("data_cache/maintenance.json".to_string()),
+        }
+    }
+}
+
+/// Completed service entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ServiceRecord {
+    timestamp: String,
+    engine_hours: u32,
+    notes: Option<String>,
+}
+
+/// Planner start, the due-date baseline for tasks never serviced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MaintenanceBaseline {
+    timestamp: String,
+    /// First engine hours reading (None until the genset reports one)
+    engine_hours: Option<u32>,
+}
+
+/// Persisted planner state
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct MaintenanceRecords {
+    services: HashMap<String, ServiceRecord>,
+    last_run: Option<String>,
+    #[serde(default)]
+    baseline: Option<MaintenanceBaseline>,
+    /// Start of the last exercise run attempt, successful or not
+    #[serde(default)]
+    last_exercise: Option<String>,
+}
+
+/// Genset maintenance planner
+#[derive(Debug, Clone)]
+pub struct MaintenancePlanner {
+    config: MaintenanceConfig,
+    records: MaintenanceRecords,
+    engine_hours: u32,
+    was_running: bool,
+    /// Local time at which the current exercise run ends
+    exercise_until: Option<NaiveDateTime>,
+}
+
+impl MaintenancePlanner {
+    /// Create a new planner, restoring persisted service records if available
+    ///
+    /// # Arguments
+    /// * `config` - Service intervals, alert margins and exercise settings
+    pub fn new(config: MaintenanceConfig) -> Self {
+        let records = config.record_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            config,
+            records,
+            engine_hours: 0,
+            was_running: false,
+            exercise_until: None,
+        }
+    }
+
+    fn parse_time(s: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(s, TIME_FORMAT).ok()
+    }
+
+    /// Persist service records
+    fn save(&self) {
+        if let Some(ref path) = self.config.record_path {
+            match serde_json::to_string_pretty(&self.records) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save maintenance records to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize maintenance records: {}", e),
+            }
+        }
+    }
+
+    /// Track engine hours and run history
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    /// * `status` - Latest genset status
+    pub fn update(&mut self, now: NaiveDateTime, status: &GensetStatus) {
+        if status.engine_hours > 0 {
+            self.engine_hours = status.engine_hours;
+        }
+        // Record the baseline once, so due dates of never-serviced tasks survive restarts
+        let mut new_baseline = self.records.baseline.is_none();
+        let baseline = self.records.baseline.get_or_insert_with(|| MaintenanceBaseline {
+            timestamp: now.format(TIME_FORMAT).to_string(),
+            engine_hours: None,
+        });
+        if baseline.engine_hours.is_none() && self.engine_hours > 0 {
+            baseline.engine_hours = Some(self.engine_hours);
+            new_baseline = true;
+            log::info!("Genset maintenance baseline set at {} engine hours", self.engine_hours);
+        }
+        if status.running {
+            self.records.last_run = Some(now.format(TIME_FORMAT).to_string());
+        }
+        // Persist the run end so idle time survives restarts
+        if new_baseline || (!status.running && self.was_running) {
+            self.save();
+        }
+        self.was_running = status.running;
+
+        if self.exercise_until.map(|t| now >= t).unwrap_or(false) {
+            log::info!("Genset exercise run complete");
+            self.exercise_until = None;
+        }
+    }
+
+    /// Whether an exercise run should be requested now
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    ///
+    /// # Returns
+    /// True while an exercise run is in progress
+    pub fn exercise_request(&mut self, now: NaiveDateTime) -> bool {
+        if self.exercise_until.is_some() {
+            return true;
+        }
+        if self.config.exercise_idle_days == 0 || now.hour() != self.config.exercise_hour {
+            return false;
+        }
+        // One attempt per day: a failed start does not re-trigger for the rest of the hour
+        let attempted_today = self.records.last_exercise.as_deref()
+            .and_then(Self::parse_time)
+            .is_some_and(|t| t.date() == now.date());
+        if !attempted_today && now - self.idle_since() >= Duration::days(self.config.exercise_idle_days as i64) {
+            log::info!("Genset idle for {} days, starting exercise run", self.config.exercise_idle_days);
+            self.exercise_until = Some(now + Duration::minutes(self.config.exercise_minutes as i64));
+            self.records.last_exercise = Some(now.format(TIME_FORMAT).to_string());
+            self.save();
+            return true;
+        }
+        false
+    }
+
+    /// Baseline time for tasks never serviced
+    fn baseline_time(&self) -> Option<NaiveDateTime> {
+        self.records.baseline.as_ref().and_then(|b| Self::parse_time(&b.timestamp))
+    }
+
+    /// Last run end, or planner start if the genset has not run
+    fn idle_since(&self) -> NaiveDateTime {
+        self.records.last_run.as_deref()
+            .and_then(Self::parse_time)
+            .or_else(|| self.baseline_time())
+            .unwrap_or_default()
+    }
+
+    /// Record a completed service
+    ///
+    /// # Arguments
+    /// * `task` - Service task name
+    /// * `now` - Local wall-clock time of the service
+    /// * `notes` - Optional technician notes
+    ///
+    /// # Returns
+    /// Result indicating success or unknown task error
+    pub fn record_service(&mut self, task: &str, now: NaiveDateTime, notes: Option<String>) -> Result<(), String> {
+        if !self.config.intervals.iter().any(|i| i.name == task) {
+            return Err(format!("Unknown maintenance task: {}", task));
+        }
+        log::info!("Genset service '{}' recorded at {} engine hours", task, self.engine_hours);
+        self.records.services.insert(task.to_string(), ServiceRecord {
+            timestamp: now.format(TIME_FORMAT).to_string(),
+            engine_hours: self.engine_hours,
+            notes,
+        });
+        self.save();
+        Ok(())
+    }
+
+    fn item(&self, interval: &ServiceInterval, now: NaiveDateTime) -> MaintenanceItem {
+        let record = self.records.services.get(&interval.name);
+        // Never serviced: count from the baseline, or from now until one is recorded
+        let last_hours = record.map(|r| r.engine_hours)
+            .or_else(|| self.records.baseline.as_ref().and_then(|b| b.engine_hours))
+            .unwrap_or(self.engine_hours);
+        let last_time = record
+            .and_then(|r| Self::parse_time(&r.timestamp))
+            .or_else(|| self.baseline_time())
+            .unwrap_or(now);
+
+        let hours_remaining = interval.interval_hours
+            .map(|h| (last_hours + h) as i64 - self.engine_hours as i64);
+        let days_remaining = interval.interval_days
+            .map(|d| (last_time + Duration::days(d as i64) - now).num_days());
+
+        let overdue = hours_remaining.map(|h| h <= 0).unwrap_or(false) || days_remaining.map(|d| d <= 0).unwrap_or(false);
+        let upcoming = hours_remaining.map(|h| h <= self.config.warning_hours as i64).unwrap_or(false)
+            || days_remaining.map(|d| d <= self.config.warning_days as i64).unwrap_or(false);
+        MaintenanceItem {
+            task: interval.name.clone(),
+            state: if overdue { "Overdue" } else if upcoming { "Upcoming" } else { "Ok" }.to_string(),
+            last_service: record.map(|r| r.timestamp.clone()),
+            last_service_hours: record.map(|r| r.engine_hours),
+            hours_remaining,
+            days_remaining,
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    ///
+    /// # Arguments
+    /// * `now` - Local wall-clock time
+    pub fn status(&self, now: NaiveDateTime) -> MaintenanceStatus {
+        let items: Vec<MaintenanceItem> = self.config.intervals.iter().map(|i| self.item(i, now)).collect();
+        let alerts = items.iter()
+            .filter(|i| i.state != "Ok")
+            .map(|i| {
+                let remaining = match (i.hours_remaining, i.days_remaining) {
+                    (Some(h), Some(d)) => format!("{} h / {} days", h, d),
+                    (Some(h), None) => format!("{} h", h),
+                    (None, Some(d)) => format!("{} days", d),
+                    (None, None) => String::new(),
+                };
+                format!("Genset {} {} ({} remaining)", i.task, i.state.to_lowercase(), remaining)
+            })
+            .collect();
+        MaintenanceStatus {
+            engine_hours: self.engine_hours,
+            items,
+            alerts,
+            last_run: self.records.last_run.clone(),
+            exercise_active: self.exercise_until.is_some(),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+// MPC 优化调度
+// Model-predictive dispatch planner: rolling-horizon linear program over PV/load forecasts,
+// tariffs, battery limits and genset costs, solved with a pure-Rust simplex solver
+
+use chrono::{Duration, NaiveDateTime};
+use minilp::{ComparisonOp, OptimizationDirection, Problem, Variable};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::types::{DispatchPlanStep, MpcStatus};
+
+/// Plan step length in minutes, matching the PV and load forecast resolution
+pub const STEP_MINUTES: i64 = 15;
+
+/// MPC planner configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MpcConfig {
+    /// Number of 15-minute steps in the planning horizon
+    pub horizon_steps: usize,
+    /// Interval between re-solves in seconds
+    pub resolve_interval: u64,
+    /// Usable battery capacity in kWh
+    pub battery_capacity: f32,
+    /// Minimum / maximum SOC in the plan (0-100%)
+    pub soc_min: f32,
+    pub soc_max: f32,
+    /// Battery charge / discharge power ratings in kW (the live BMS limits apply below these)
+    pub max_charge_power: f32,
+    pub max_discharge_power: f32,
+    /// One-way charge / discharge efficiency (0-1)
+    pub charge_efficiency: f32,
+    pub discharge_efficiency: f32,
+    /// Battery wear cost per kWh throughput
+    pub cycle_cost: f32,
+    /// Value of energy left in the battery at the end of the horizon, per kWh
+    pub terminal_energy_value: f32,
+    /// Whether a grid connection is available
+    pub grid_available: bool,
+    /// Grid import / export limits in kW
+    pub grid_import_limit: f32,
+    pub grid_export_limit: f32,
+    /// Price paid for exported energy per kWh
+    pub export_price: f32,
+    /// Import price per kWh when no tariff calendar is configured
+    pub flat_price: f32,
+    /// Genset rated power in kW (0 if no genset)
+    pub genset_rating: f32,
+    /// Genset minimum load as a percentage of rating
+    pub genset_min_load_pct: f32,
+    /// Genset fuel cost per kWh generated
+    pub genset_fuel_cost: f32,
+    /// Genset fixed running cost per hour (wear, idle fuel)
+    pub genset_running_cost: f32,
+    /// Penalty per kWh of charger demand not served
+    pub shed_penalty: f32,
+    /// Small penalty per kWh of curtailed PV
+    pub curtailment_penalty: f32,
+}
+
+impl Default for MpcConfig {
+    fn default() -> Self {
+        Self {
+            horizon_steps: 96,
+            resolve_interval: 900,
+            battery_capacity: 200.0,
+            soc_min: 10.0,
+            soc_max: 95.0,
+            max_charge_power: 50.0,
+            max_discharge_power: 50.0,
+            charge_efficiency: 0.95,
+            discharge_efficiency: 0.95,
+            cycle_cost: 0.02,
+            terminal_energy_value: 0.5,
+            grid_available: true,
+            grid_import_limit: 100.0,
+            grid_export_limit: 0.0,
+            export_price: 0.0,
+            flat_price: 0.8,
+            genset_rating: 60.0,
+            genset_min_load_pct: 30.0,
+            genset_fuel_cost: 2.5,
+            genset_running_cost: 10.0,
+            shed_penalty: 10.0,
+            curtailment_penalty: 0.001,
+        }
+    }
+}
+
+/// Forecast and state inputs for one solve
+#[derive(Debug, Clone)]
+pub struct MpcInputs {
+    /// Local time of the first step
+    pub start: NaiveDateTime,
+    /// PV forecast per step in kW
+    pub pv: Vec<f32>,
+    /// Charger demand forecast per step in kW
+    pub load: Vec<f32>,
+    /// Import price per step per kWh
+    pub price: Vec<f32>,
+    /// Current battery SOC (0-100%)
+    pub soc: f32,
+    /// Live battery charge limit in kW
+    pub max_charge_power: f32,
+    /// Live battery discharge limit in kW
+    pub max_discharge_power: f32,
+    /// Multiplier on genset fuel cost (raised when fuel runs low)
+    pub genset_cost_factor: f32,
+}
+
+/// Decision variables of one step
+struct StepVars {
+    charge: Variable,
+    discharge: Variable,
+    import: Variable,
+    export: Variable,
+    genset: Variable,
+    genset_on: Variable,
+    curtail: Variable,
+    shed: Variable,
+    energy: Variable,
+}
+
+/// Rolling-horizon dispatch planner
+#[derive(Debug, Clone)]
+pub struct MpcPlanner {
+    config: MpcConfig,
+    plan: Vec<DispatchPlanStep>,
+    objective: f32,
+    solved_at: Option<Instant>,
+    solve_failures: u32,
+    last_error: Option<String>,
+}
+
+impl MpcPlanner {
+    /// Create a new planner
+    ///
+    /// # Arguments
+    /// * `config` - Planner configuration
+    pub fn new(config: MpcConfig) -> Self {
+        Self {
+            config,
+            plan: Vec::new(),
+            objective: 0.0,
+            solved_at: None,
+            solve_failures: 0,
+            last_error: None,
+        }
+    }
+
+    /// Planner configuration
+    pub fn config(&self) -> &MpcConfig {
+        &self.config
+    }
+
+    /// Whether the plan is due for a re-solve
+    pub fn needs_solve(&self) -> bool {
+        self.solved_at
+            .map(|t| t.elapsed().as_secs() >= self.config.resolve_interval)
+            .unwrap_or(true)
+    }
+
+    /// Build and solve the linear program
+    ///
+    /// # Arguments
+    /// * `inputs` - Forecasts, prices and current SOC
+    ///
+    /// # Returns
+    /// Result indicating success or solver error
+    pub fn solve(&mut self, inputs: &MpcInputs) -> Result<(), String> {
+        // Marks the attempt so a failing solve is retried at the re-solve interval, not every cycle
+        self.solved_at = Some(Instant::now());
+        match self.solve_with_commitment(inputs) {
+            Ok((plan, objective)) => {
+                self.plan = plan;
+                self.objective = objective;
+                self.last_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                // A stale plan would replay from its first step; drop it so dispatch falls back to rules
+                self.plan.clear();
+                self.solve_failures += 1;
+                self.last_error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    /// Solve with the genset commitment relaxed, round it, then re-solve with the commitment fixed so
+    /// the plan respects the genset minimum load and running cost
+    ///
+    /// # Arguments
+    /// * `inputs` - Forecasts, prices and current SOC
+    ///
+    /// # Returns
+    /// Result containing the plan and its objective, or solver error
+    fn solve_with_commitment(&self, inputs: &MpcInputs) -> Result<(Vec<DispatchPlanStep>, f32), String> {
+        let (relaxed, _) = self.build_and_solve(inputs, None)?;
+        let genset_min = self.config.genset_rating * self.config.genset_min_load_pct / 100.0;
+        // The relaxation runs the genset_on fraction at genset/rating, so a step needing less than half
+        // the rating would round off; commit wherever the relaxed plan uses at least half the minimum load
+        let commitment: Vec<bool> = relaxed.iter()
+            .map(|s| s.genset_on || (self.config.genset_rating > 0.0 && s.genset_power >= genset_min / 2.0))
+            .collect();
+        match self.build_and_solve(inputs, Some(&commitment)) {
+            Ok(solved) => Ok(solved),
+            Err(e) if commitment.iter().any(|&on| on) => {
+                // The minimum load cannot be absorbed somewhere; a genset-off plan is always feasible
+                log::warn!("{} with the rounded genset commitment, planning without the genset", e);
+                self.build_and_solve(inputs, Some(&vec![false; commitment.len()]))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Build and solve the linear program
+    ///
+    /// # Arguments
+    /// * `inputs` - Forecasts, prices and current SOC
+    /// * `commitment` - Fixed genset on/off per step, or None to relax the commitment to 0-1
+    ///
+    /// # Returns
+    /// Result containing the plan and its objective, or solver error
+    fn build_and_solve(&self, inputs: &MpcInputs, commitment: Option<&[bool]>) -> Result<(Vec<DispatchPlanStep>, f32), String> {
+        let c = &self.config;
+        let steps = c.horizon_steps.min(inputs.load.len()).min(inputs.price.len());
+        if steps == 0 {
+            return Err("No forecast data for the planning horizon".to_string());
+        }
+        let dt = STEP_MINUTES as f64 / 60.0;
+        let capacity = c.battery_capacity as f64;
+        let e_min = capacity * c.soc_min as f64 / 100.0;
+        let e_max = capacity * c.soc_max as f64 / 100.0;
+        // Start inside the SOC window so the problem stays feasible
+        let e_start = (capacity * inputs.soc as f64 / 100.0).clamp(e_min, e_max);
+        let import_max = if c.grid_available { c.grid_import_limit as f64 } else { 0.0 };
+        let export_max = if c.grid_available { c.grid_export_limit as f64 } else { 0.0 };
+        let charge_max = c.max_charge_power.min(inputs.max_charge_power).max(0.0) as f64;
+        let discharge_max = c.max_discharge_power.min(inputs.max_discharge_power).max(0.0) as f64;
+        let genset_max = c.genset_rating as f64;
+        let genset_min = genset_max * c.genset_min_load_pct as f64 / 100.0;
+        let fuel_cost = c.genset_fuel_cost as f64 * inputs.genset_cost_factor.max(1.0) as f64;
+
+        let mut problem = Problem::new(OptimizationDirection::Minimize);
+        let mut vars: Vec<StepVars> = Vec::with_capacity(steps);
+        for t in 0..steps {
+            let pv = inputs.pv.get(t).copied().unwrap_or(0.0).max(0.0) as f64;
+            let load = inputs.load[t].max(0.0) as f64;
+            let price = inputs.price[t] as f64;
+            let terminal = if t + 1 == steps { -(c.terminal_energy_value as f64) } else { 0.0 };
+            let on_bounds = match commitment.and_then(|fixed| fixed.get(t)) {
+                Some(&on) if on && genset_max > 0.0 => (1.0, 1.0),
+                Some(_) => (0.0, 0.0),
+                None => (0.0, if genset_max > 0.0 { 1.0 } else { 0.0 }),
+            };
+
+            let v = StepVars {
+                charge: problem.add_var(c.cycle_cost as f64 * dt / 2.0, (0.0, charge_max)),
+                discharge: problem.add_var(c.cycle_cost as f64 * dt / 2.0, (0.0, discharge_max)),
+                import: problem.add_var(price * dt, (0.0, import_max)),
+                export: problem.add_var(-(c.export_price as f64) * dt, (0.0, export_max)),
+                genset: problem.add_var(fuel_cost * dt, (0.0, genset_max)),
+                // On/off commitment: relaxed to 0-1 in the first pass, fixed in the second
+                genset_on: problem.add_var(c.genset_running_cost as f64 * dt, on_bounds),
+                curtail: problem.add_var(c.curtailment_penalty as f64 * dt, (0.0, pv)),
+                shed: problem.add_var(c.shed_penalty as f64 * dt, (0.0, load)),
+                energy: problem.add_var(terminal, (e_min, e_max)),
+            };
+
+            // Power balance: pv - curtail + genset + discharge + import = load - shed + charge + export
+            problem.add_constraint(
+                [(v.curtail, -1.0), (v.genset, 1.0), (v.discharge, 1.0), (v.import, 1.0),
+                  (v.shed, 1.0), (v.charge, -1.0), (v.export, -1.0)],
+                ComparisonOp::Eq,
+                load - pv,
+            );
+
+            // Genset minimum load and rating tied to commitment
+            problem.add_constraint([(v.genset, 1.0), (v.genset_on, -genset_min)], ComparisonOp::Ge, 0.0);
+            problem.add_constraint([(v.genset, 1.0), (v.genset_on, -genset_max)], ComparisonOp::Le, 0.0);
+
+            // Battery energy dynamics
+            let eta_c = c.charge_efficiency as f64;
+            let eta_d = c.discharge_efficiency.max(0.01) as f64;
+            match vars.last() {
+                Some(prev) => {
+                    problem.add_constraint(
+                        [(v.energy, 1.0), (prev.energy, -1.0), (v.charge, -eta_c * dt), (v.discharge, dt / eta_d)],
+                        ComparisonOp::Eq,
+                        0.0,
+                    );
+                }
+                None => {
+                    problem.add_constraint(
+                        [(v.energy, 1.0), (v.charge, -eta_c * dt), (v.discharge, dt / eta_d)],
+                        ComparisonOp::Eq,
+                        e_start,
+                    );
+                }
+            }
+            vars.push(v);
+        }
+
+        let solution = problem.solve().map_err(|e| format!("MPC solve failed: {}", e))?;
+
+        let plan = vars.iter().enumerate()
+            .map(|(t, v)| {
+                let load = inputs.load[t].max(0.0);
+                let genset_on = solution[v.genset_on] >= 0.5;
+                DispatchPlanStep {
+                    timestamp: (inputs.start + Duration::minutes(t as i64 * STEP_MINUTES))
+                        .format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    battery_power: (solution[v.discharge] - solution[v.charge]) as f32,
+                    genset_power: solution[v.genset] as f32,
+                    genset_on,
+                    grid_power: (solution[v.import] - solution[v.export]) as f32,
+                    charger_limit: (load - solution[v.shed] as f32).max(0.0),
+                    pv_curtailment: solution[v.curtail] as f32,
+                    soc: (solution[v.energy] / capacity.max(1.0) * 100.0) as f32,
+                }
+            })
+            .collect();
+
+        Ok((plan, solution.objective() as f32))
+    }
+
+    /// Plan step in effect now (the first step until the next re-solve)
+    ///
+    /// # Returns
+    /// Current plan step, or None if no plan is available
+    pub fn current_step(&self) -> Option<&DispatchPlanStep> {
+        let elapsed = self.solved_at?.elapsed().as_secs() as i64;
+        let index = (elapsed / (STEP_MINUTES * 60)) as usize;
+        self.plan.get(index)
+    }
+
+    /// Full plan from the last solve (empty after a failed solve)
+    pub fn plan(&self) -> &[DispatchPlanStep] {
+        &self.plan
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> MpcStatus {
+        let step = self.current_step();
+        MpcStatus {
+            plan_steps: self.plan.len(),
+            objective: self.objective,
+            seconds_since_solve: self.solved_at.map(|t| t.elapsed().as_secs()).unwrap_or(0),
+            battery_setpoint: step.map(|s| s.battery_power).unwrap_or(0.0),
+            genset_setpoint: step.map(|s| s.genset_power).unwrap_or(0.0),
+            charger_limit: step.map(|s| s.charger_limit).unwrap_or(0.0),
+            solve_failures: self.solve_failures,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn off_grid_config() -> MpcConfig {
+        MpcConfig {
+            horizon_steps: 4,
+            grid_available: false,
+            ..MpcConfig::default()
+        }
+    }
+
+    fn inputs(load: f32, soc: f32) -> MpcInputs {
+        MpcInputs {
+            start: NaiveDateTime::parse_from_str("2024-06-03 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            pv: vec![0.0; 4],
+            load: vec![load; 4],
+            price: vec![0.8; 4],
+            soc,
+            max_charge_power: 50.0,
+            max_discharge_power: 50.0,
+            genset_cost_factor: 1.0,
+        }
+    }
+
+    #[test]
+    fn committed_genset_runs_at_least_at_minimum_load() {
+        let mut planner = MpcPlanner::new(off_grid_config());
+        // 15 kW of load with an empty battery: the relaxed genset would run at 15 kW with genset_on 0.25
+        planner.solve(&inputs(15.0, 10.0)).unwrap();
+        let genset_min = 60.0 * 30.0 / 100.0;
+        for step in planner.plan() {
+            assert!(step.genset_on);
+            assert!(step.genset_power >= genset_min - 1e-3);
+            // The surplus above the load charges the battery instead of appearing from nowhere
+            let balance = step.genset_power + step.battery_power - step.charger_limit;
+            assert!(balance.abs() < 1e-3, "unbalanced step: {:?}", step);
+        }
+    }
+
+    #[test]
+    fn battery_covers_small_load_without_the_genset() {
+        let mut planner = MpcPlanner::new(off_grid_config());
+        planner.solve(&inputs(10.0, 80.0)).unwrap();
+        for step in planner.plan() {
+            assert!(!step.genset_on);
+            assert_eq!(step.genset_power, 0.0);
+            assert!((step.battery_power - 10.0).abs() < 1e-3);
+        }
+    }
+}
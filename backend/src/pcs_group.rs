@@ -0,0 +1,410 @@
+// PCS 并机功率分配
+// PCS group on a common battery: splits the site setpoint across units by rating, by part-load
+// efficiency or in sequence, rotates units by run hours and redistributes the share of faulted units
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::devices::pcs::{PcsDevice, PcsMode, PcsReactiveMode};
+use crate::types::{PcsGroupStatus, PcsUnitStatus};
+
+/// One PCS unit in the group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcsUnitConfig {
+    /// PCS identifier (a unit with the site pcs_id reuses the primary PCS)
+    pub id: String,
+    /// Modbus host
+    pub host: String,
+    /// Modbus port
+    pub port: u16,
+    /// Rated power in kW
+    pub rated_power: f32,
+    /// Efficiency curve as (load % of rating, efficiency %) points, ascending by load
+    #[serde(default = "default_efficiency_curve")]
+    pub efficiency_curve: Vec<(f32, f32)>,
+}
+
+fn default_efficiency_curve() -> Vec<(f32, f32)> {
+    vec![(0.0, 0.0), (5.0, 88.0), (10.0, 93.0), (20.0, 96.0), (30.0, 97.0), (50.0, 97.5), (75.0, 97.3), (100.0, 96.8)]
+}
+
+/// How the site setpoint is split across units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PcsSharingStrategy {
+    /// All healthy units in proportion to their rating
+    #[default]
+    ByRating,
+    /// The number of units that maximises part-load efficiency
+    Efficiency,
+    /// Units filled to rating one at a time in rotation order (least run hours first)
+    #[serde(alias = "RoundRobin")]
+    Sequential,
+}
+
+/// PCS group configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PcsGroupConfig {
+    /// Units operated in parallel
+    pub units: Vec<PcsUnitConfig>,
+    /// Power sharing strategy
+    pub strategy: PcsSharingStrategy,
+    /// Interval between rotation order updates in hours
+    pub rotation_interval: f32,
+    /// Efficiency gain in percentage points required to change the number of running units
+    pub staging_hysteresis: f32,
+    /// File unit run hours are persisted to
+    pub run_hours_path: Option<String>,
+}
+
+impl Default for PcsGroupConfig {
+    fn default() -> Self {
+        Self {
+            units: Vec::new(),
+            strategy: PcsSharingStrategy::ByRating,
+            rotation_interval: 24.0,
+            staging_hysteresis: 0.5,
+            run_hours_path: Some("data_cache/pcs_run_hours.json".to_string()),
+        }
+    }
+}
+
+/// Runtime state of one unit
+#[derive(Debug)]
+struct PcsUnit {
+    config: PcsUnitConfig,
+    device: Arc<Mutex<PcsDevice>>,
+    faulted: bool,
+    setpoint: f32,
+    run_hours: f32,
+}
+
+impl PcsUnit {
+    /// Efficiency at a load in kW, interpolated from the curve
+    fn efficiency(&self, load: f32) -> f32 {
+        let pct = load / self.config.rated_power.max(f32::EPSILON) * 100.0;
+        let curve = &self.config.efficiency_curve;
+        match curve.windows(2).find(|w| pct >= w[0].0 && pct <= w[1].0) {
+            Some(w) => {
+                let span = (w[1].0 - w[0].0).max(f32::EPSILON);
+                w[0].1 + (w[1].1 - w[0].1) * (pct - w[0].0) / span
+            }
+            None => curve.last().map(|p| p.1).unwrap_or(100.0),
+        }
+    }
+
+    /// Write the unit mode and setpoint
+    ///
+    /// # Arguments
+    /// * `power` - Unit power in kW (positive = discharging, 0 = standby)
+    fn apply(&mut self, power: f32) -> Result<(), String> {
+        let mode = if power > 0.0 {
+            PcsMode::Discharging
+        } else if power < 0.0 {
+            PcsMode::Charging
+        } else {
+            PcsMode::Standby
+        };
+        let mut pcs_locked = self.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+        pcs_locked.set_mode(mode)
+            .map_err(|e| format!("Failed to set PCS {} mode: {:?}", self.config.id, e))?;
+        pcs_locked.set_power_setpoint(power)
+            .map_err(|e| format!("Failed to set PCS {} power: {:?}", self.config.id, e))?;
+        self.setpoint = power;
+        Ok(())
+    }
+}
+
+/// Parallel PCS group
+#[derive(Debug)]
+pub struct PcsGroup {
+    config: PcsGroupConfig,
+    units: Vec<PcsUnit>,
+    /// Unit indices in rotation order (least run hours first)
+    order: Vec<usize>,
+    last_rotation: Option<Instant>,
+    last_dispatch: Option<Instant>,
+    /// Units running under the efficiency strategy
+    staged: usize,
+    /// Persisted run hours by unit id, applied as units are added
+    restored_hours: HashMap<String, f32>,
+}
+
+impl PcsGroup {
+    /// Create an empty PCS group, restoring persisted run hours if available
+    ///
+    /// # Arguments
+    /// * `config` - Sharing strategy and rotation parameters
+    pub fn new(config: PcsGroupConfig) -> Self {
+        let restored_hours = config.run_hours_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            config,
+            units: Vec::new(),
+            order: Vec::new(),
+            last_rotation: None,
+            last_dispatch: None,
+            staged: 0,
+            restored_hours,
+        }
+    }
+
+    /// Persist unit run hours
+    fn save(&self) {
+        if let Some(ref path) = self.config.run_hours_path {
+            let hours: HashMap<&str, f32> = self.units.iter().map(|u| (u.config.id.as_str(), u.run_hours)).collect();
+            match serde_json::to_string(&hours) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save PCS run hours to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize PCS run hours: {}", e),
+            }
+        }
+    }
+
+    /// Add a unit to the group
+    ///
+    /// # Arguments
+    /// * `config` - Unit rating and efficiency curve
+    /// * `device` - Unit PCS device
+    pub fn add_unit(&mut self, config: PcsUnitConfig, device: Arc<Mutex<PcsDevice>>) {
+        self.order.push(self.units.len());
+        let run_hours = self.restored_hours.get(&config.id).copied().unwrap_or(0.0);
+        self.units.push(PcsUnit {
+            config,
+            device,
+            faulted: false,
+            setpoint: 0.0,
+            run_hours,
+        });
+    }
+
+    /// Read every unit and update its fault state (faulted, or not answering)
+    ///
+    /// # Returns
+    /// Result containing the AC bus voltage and frequency from the first unit read this cycle
+    /// (None if every read failed), or device access error
+    pub fn read(&mut self) -> Result<Option<(f32, f32)>, String> {
+        let mut bus = None;
+        for unit in &mut self.units {
+            let mut pcs_locked = unit.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            // A unit that cannot be read is treated as faulted until it answers again
+            let faulted = match pcs_locked.read_status() {
+                Ok(status) => {
+                    bus = bus.or(Some((status.voltage_ac, status.frequency)));
+                    pcs_locked.mode == PcsMode::Fault
+                }
+                Err(e) => {
+                    log::warn!("Failed to read PCS {} status: {:?}", unit.config.id, e);
+                    true
+                }
+            };
+            if faulted != unit.faulted {
+                if faulted {
+                    log::error!("PCS {} faulted, redistributing its share", unit.config.id);
+                } else {
+                    log::info!("PCS {} recovered from fault", unit.config.id);
+                }
+                unit.faulted = faulted;
+            }
+        }
+        Ok(bus)
+    }
+
+    /// Summed rating of the healthy units in kW
+    pub fn available_power(&self) -> f32 {
+        self.units.iter().filter(|u| !u.faulted).map(|u| u.config.rated_power).sum()
+    }
+
+    /// Re-sort the rotation order by run hours when the rotation interval has elapsed
+    fn rotate(&mut self, now: Instant) {
+        let due = self.last_rotation
+            .map(|t| now.duration_since(t).as_secs_f32() / 3600.0 >= self.config.rotation_interval)
+            .unwrap_or(true);
+        if due {
+            self.last_rotation = Some(now);
+            let units = &self.units;
+            self.order.sort_by(|&a, &b| units[a].run_hours.total_cmp(&units[b].run_hours));
+        }
+    }
+
+    /// Weighted efficiency of running the first `count` healthy units at an equal load fraction
+    fn staged_efficiency(&self, healthy: &[usize], count: usize, power: f32) -> Option<f32> {
+        let running = &healthy[..count];
+        let rating: f32 = running.iter().map(|&i| self.units[i].config.rated_power).sum();
+        if rating < power {
+            return None;
+        }
+        Some(running.iter()
+            .map(|&i| {
+                let unit = &self.units[i];
+                let share = unit.config.rated_power / rating;
+                share * unit.efficiency(power * share)
+            })
+            .sum())
+    }
+
+    /// Split a site setpoint across the healthy units
+    ///
+    /// # Arguments
+    /// * `power` - Site power in kW (positive = discharging, negative = charging)
+    ///
+    /// # Returns
+    /// Result containing the applied site power or PCS control error
+    pub fn dispatch(&mut self, power: f32) -> Result<f32, String> {
+        let now = Instant::now();
+        let dt_hours = self.last_dispatch.map(|t| now.duration_since(t).as_secs_f32() / 3600.0).unwrap_or(0.0);
+        self.last_dispatch = Some(now);
+        let mut whole_hour = false;
+        for unit in self.units.iter_mut().filter(|u| u.setpoint != 0.0) {
+            whole_hour |= (unit.run_hours + dt_hours).floor() > unit.run_hours.floor();
+            unit.run_hours += dt_hours;
+        }
+        // Persist on every whole run hour so a restart loses at most one hour
+        if whole_hour {
+            self.save();
+        }
+        self.rotate(now);
+
+        // A unit that rejects its command is dropped for this cycle and the power re-split over the rest
+        let mut healthy: Vec<usize> = self.order.iter().copied().filter(|&i| !self.units[i].faulted).collect();
+        let sign = if power > 0.0 { 1.0 } else { -1.0 };
+        let applied = loop {
+            let setpoints = self.split(&healthy, power.abs());
+            let failed = healthy.iter().position(|&i| {
+                let unit = &mut self.units[i];
+                match unit.apply(sign * setpoints[i]) {
+                    Ok(()) => false,
+                    Err(e) => {
+                        log::error!("{}; dropping PCS {} from dispatch", e, unit.config.id);
+                        unit.faulted = true;
+                        true
+                    }
+                }
+            });
+            match failed {
+                Some(k) => {
+                    healthy.remove(k);
+                }
+                None => break healthy.iter().map(|&i| setpoints[i]).sum::<f32>(),
+            }
+        };
+        // Faulted units are left in their fault mode
+        for unit in self.units.iter_mut().filter(|u| u.faulted) {
+            unit.setpoint = 0.0;
+        }
+        Ok(sign * applied)
+    }
+
+    /// Unit shares of a site power magnitude, indexed like `units`
+    ///
+    /// # Arguments
+    /// * `healthy` - Indices of the units taking part, in rotation order
+    /// * `power` - Site power magnitude in kW
+    fn split(&mut self, healthy: &[usize], power: f32) -> Vec<f32> {
+        let rating: f32 = healthy.iter().map(|&i| self.units[i].config.rated_power).sum();
+        let magnitude = power.min(rating);
+        let mut setpoints = vec![0.0; self.units.len()];
+        if magnitude <= 0.0 || healthy.is_empty() {
+            return setpoints;
+        }
+        let running: &[usize] = match self.config.strategy {
+            PcsSharingStrategy::ByRating | PcsSharingStrategy::Sequential => healthy,
+            PcsSharingStrategy::Efficiency => {
+                let best = (1..=healthy.len())
+                    .filter_map(|n| self.staged_efficiency(healthy, n, magnitude).map(|e| (n, e)))
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+                let current = (self.staged > 0 && self.staged <= healthy.len())
+                    .then(|| self.staged_efficiency(healthy, self.staged, magnitude))
+                    .flatten();
+                // Keep the current staging unless another count is clearly better
+                self.staged = match (best, current) {
+                    (Some((_, e)), Some(c)) if e - c < self.config.staging_hysteresis => self.staged,
+                    (Some((n, _)), _) => n,
+                    (None, _) => healthy.len(),
+                };
+                &healthy[..self.staged]
+            }
+        };
+        if self.config.strategy == PcsSharingStrategy::Sequential {
+            let mut remaining = magnitude;
+            for &i in running {
+                let share = remaining.min(self.units[i].config.rated_power);
+                setpoints[i] = share;
+                remaining -= share;
+            }
+        } else {
+            let rating: f32 = running.iter().map(|&i| self.units[i].config.rated_power).sum::<f32>().max(f32::EPSILON);
+            for &i in running {
+                setpoints[i] = (magnitude * self.units[i].config.rated_power / rating).min(self.units[i].config.rated_power);
+            }
+        }
+        setpoints
+    }
+
+    /// Command every healthy unit into an operating mode (e.g. OffGrid for island operation)
+    ///
+    /// # Arguments
+    /// * `mode` - PCS operating mode
+    ///
+    /// # Returns
+    /// Result indicating success or PCS control error
+    pub fn set_operating_mode(&mut self, mode: PcsMode) -> Result<(), String> {
+        for unit in self.units.iter().filter(|u| !u.faulted) {
+            let mut pcs_locked = unit.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_mode(mode)
+                .map_err(|e| format!("Failed to set PCS {} mode: {:?}", unit.config.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Send a reactive control command to the healthy units, splitting reactive power by rating
+    ///
+    /// # Arguments
+    /// * `mode` - Reactive power control mode
+    /// * `value` - Power factor for FixedPowerFactor, site reactive power in kvar for FixedReactivePower
+    ///
+    /// # Returns
+    /// Result indicating success or PCS control error
+    pub fn set_reactive(&mut self, mode: PcsReactiveMode, value: f32) -> Result<(), String> {
+        let rating = self.available_power().max(f32::EPSILON);
+        for unit in self.units.iter().filter(|u| !u.faulted) {
+            let value = match mode {
+                PcsReactiveMode::FixedReactivePower => value * unit.config.rated_power / rating,
+                _ => value,
+            };
+            let mut pcs_locked = unit.device.lock().map_err(|_| "Mutex poisoned".to_string())?;
+            pcs_locked.set_reactive_control(mode, value)
+                .map_err(|e| format!("Failed to set PCS {} reactive control: {:?}", unit.config.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> PcsGroupStatus {
+        PcsGroupStatus {
+            strategy: format!("{:?}", self.config.strategy),
+            healthy_units: self.units.iter().filter(|u| !u.faulted).count(),
+            available_power: self.available_power(),
+            units: self.order.iter()
+                .map(|&i| {
+                    let unit = &self.units[i];
+                    PcsUnitStatus {
+                        id: unit.config.id.clone(),
+                        rated_power: unit.config.rated_power,
+                        setpoint: unit.setpoint,
+                        faulted: unit.faulted,
+                        run_hours: unit.run_hours,
+                        efficiency: if unit.setpoint != 0.0 { Some(unit.efficiency(unit.setpoint.abs())) } else { None },
+                    }
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+// 光伏功率预测
+// PV production forecast from site GPS position, solar geometry and clear-sky irradiance,
+// scaled by the array's effective capacity learned from past production
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use crate::types::PvForecastPoint;
+
+/// Solar position in degrees
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SolarPosition {
+    /// Elevation above the horizon
+    pub elevation: f64,
+    /// Azimuth clockwise from north
+    pub azimuth: f64,
+}
+
+/// Compute solar position (NOAA general solar position equations)
+///
+/// # Arguments
+/// * `time` - UTC time
+/// * `latitude` - Site latitude in degrees
+/// * `longitude` - Site longitude in degrees (east positive)
+///
+/// # Returns
+/// Solar elevation and azimuth in degrees
+pub fn solar_position(time: DateTime<Utc>, latitude: f64, longitude: f64) -> SolarPosition {
+    let hour = time.hour() as f64 + time.minute() as f64 / 60.0 + time.second() as f64 / 3600.0;
+    let gamma = 2.0 * std::f64::consts::PI / 365.0 * (time.ordinal() as f64 - 1.0 + (hour - 12.0) / 24.0);
+
+    // Equation of time in minutes and solar declination in radians
+    let eqtime = 229.18 * (0.000075 + 0.001868 * gamma.cos() - 0.032077 * gamma.sin()
+        - 0.014615 * (2.0 * gamma).cos() - 0.040849 * (2.0 * gamma).sin());
+    let decl = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos() + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos() + 0.00148 * (3.0 * gamma).sin();
+
+    let true_solar_minutes = hour * 60.0 + eqtime + 4.0 * longitude;
+    let hour_angle = (true_solar_minutes / 4.0 - 180.0).to_radians();
+    let lat = latitude.to_radians();
+
+    let cos_zenith = (lat.sin() * decl.sin() + lat.cos() * decl.cos() * hour_angle.cos()).clamp(-1.0, 1.0);
+    let zenith = cos_zenith.acos();
+
+    let azimuth = if zenith.sin().abs() < 1e-9 {
+        180.0
+    } else {
+        let cos_az = ((lat.sin() * cos_zenith - decl.sin()) / (lat.cos() * zenith.sin())).clamp(-1.0, 1.0);
+        let az = 180.0 - cos_az.acos().to_degrees();
+        if hour_angle > 0.0 { 360.0 - az } else { az }
+    };
+
+    SolarPosition {
+        elevation: 90.0 - zenith.to_degrees(),
+        azimuth: azimuth.rem_euclid(360.0),
+    }
+}
+
+/// Clear-sky global horizontal irradiance (Meinel model with altitude correction)
+///
+/// # Arguments
+/// * `elevation` - Solar elevation in degrees
+/// * `altitude` - Site altitude in m
+///
+/// # Returns
+/// Irradiance in W/m²
+pub fn clear_sky_ghi(elevation: f64, altitude: f64) -> f64 {
+    if elevation <= 0.0 {
+        return 0.0;
+    }
+    let zenith = 90.0 - elevation;
+    // Kasten-Young air mass
+    let air_mass = 1.0 / (zenith.to_radians().cos() + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+    let h = (altitude / 1000.0).clamp(0.0, 5.0);
+    let dni = 1353.0 * ((1.0 - 0.14 * h) * 0.7f64.powf(air_mass.powf(0.678)) + 0.14 * h);
+    // Diffuse component approximated as 10% of the direct beam
+    1.1 * dni * elevation.to_radians().sin()
+}
+
+/// PV forecaster configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PvForecastConfig {
+    /// Initial guess of the array output at 1000 W/m² in kW, used until production has been learned
+    pub initial_capacity: f32,
+    /// Forecast horizon in hours
+    pub horizon_hours: i64,
+    /// Forecast resolution in minutes
+    pub step_minutes: i64,
+    /// Minimum clear-sky irradiance for a sample to be used for learning, in W/m²
+    pub min_learning_irradiance: f64,
+    /// Time constant in hours over which the current clearness decays to the climatological value
+    pub clearness_decay_hours: f64,
+    /// File the learned capacity and clearness are persisted to
+    pub state_path: Option<String>,
+}
+
+impl Default for PvForecastConfig {
+    fn default() -> Self {
+        Self {
+            initial_capacity: 50.0,
+            horizon_hours: 48,
+            step_minutes: 15,
+            min_learning_irradiance: 150.0,
+            clearness_decay_hours: 3.0,
+            state_path: Some("data_cache/pv_forecast.json".to_string()),
+        }
+    }
+}
+
+/// Persisted learned state
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct LearnedState {
+    effective_capacity: f32,
+    climatological_clearness: f32,
+}
+
+/// PV production forecaster
+#[derive(Debug, Clone)]
+pub struct PvForecaster {
+    config: PvForecastConfig,
+    /// Site position (latitude, longitude, altitude in m)
+    location: Option<(f64, f64, f64)>,
+    /// Learned output at 1000 W/m² clear-sky irradiance in kW
+    effective_capacity: f32,
+    /// Recent ratio of measured to clear-sky production
+    clearness: f32,
+    /// Long-run ratio of measured to clear-sky production
+    climatological_clearness: f32,
+    /// Cached forecast and the time it was produced
+    forecast: Vec<PvForecastPoint>,
+    forecast_time: Option<DateTime<Utc>>,
+    /// Day of the last learned sample, used to persist once per day
+    learned_day: Option<NaiveDate>,
+}
+
+impl PvForecaster {
+    /// Create a new forecaster, restoring the learned state if available
+    ///
+    /// # Arguments
+    /// * `config` - Forecaster configuration
+    pub fn new(config: PvForecastConfig) -> Self {
+        let learned = config.state_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str::<LearnedState>(&s).ok())
+            .filter(|s| s.effective_capacity > 0.0)
+            .unwrap_or(LearnedState {
+                effective_capacity: config.initial_capacity,
+                climatological_clearness: 0.7,
+            });
+        Self {
+            effective_capacity: learned.effective_capacity,
+            config,
+            location: None,
+            clearness: learned.climatological_clearness,
+            climatological_clearness: learned.climatological_clearness,
+            forecast: Vec::new(),
+            forecast_time: None,
+            learned_day: None,
+        }
+    }
+
+    /// Persist the learned capacity and clearness
+    fn save(&self) {
+        if let Some(ref path) = self.config.state_path {
+            let state = LearnedState {
+                effective_capacity: self.effective_capacity,
+                climatological_clearness: self.climatological_clearness,
+            };
+            match serde_json::to_string(&state) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save PV forecast state to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize PV forecast state: {}", e),
+            }
+        }
+    }
+
+    /// Update site position from GPS
+    ///
+    /// # Arguments
+    /// * `latitude` - Latitude in degrees
+    /// * `longitude` - Longitude in degrees
+    /// * `altitude` - Altitude in m
+    pub fn set_location(&mut self, latitude: f64, longitude: f64, altitude: f64) {
+        self.location = Some((latitude, longitude, altitude));
+    }
+
+    /// Clear-sky output of the array at the given time
+    fn clear_sky_power(&self, time: DateTime<Utc>) -> Option<f64> {
+        let (lat, lon, alt) = self.location?;
+        let position = solar_position(time, lat, lon);
+        Some(self.effective_capacity as f64 * clear_sky_ghi(position.elevation, alt) / 1000.0)
+    }
+
+    /// Learn from one production sample
+    ///
+    /// # Arguments
+    /// * `time` - UTC sample time
+    /// * `pv_power` - Measured PV power in kW
+    /// * `irradiance` - Measured plane irradiance in W/m² (0 if no sensor)
+    pub fn observe(&mut self, time: DateTime<Utc>, pv_power: f32, irradiance: f32) {
+        let (lat, lon, alt) = match self.location {
+            Some(l) => l,
+            None => return,
+        };
+        let ghi = clear_sky_ghi(solar_position(time, lat, lon).elevation, alt);
+        if ghi < self.config.min_learning_irradiance {
+            return;
+        }
+
+        if irradiance > 50.0 {
+            // Measured irradiance gives an unbiased capacity sample
+            let sample = pv_power / (irradiance / 1000.0);
+            self.effective_capacity += 0.001 * (sample - self.effective_capacity);
+        } else {
+            // Clouds only lower output, so track the upper envelope of clear-sky-normalised production
+            let sample = pv_power / (ghi as f32 / 1000.0);
+            let rate = if sample > self.effective_capacity { 0.01 } else { 0.0001 };
+            self.effective_capacity += rate * (sample - self.effective_capacity);
+        }
+
+        let clear_power = self.effective_capacity * ghi as f32 / 1000.0;
+        if clear_power > 0.0 {
+            let k = (pv_power / clear_power).clamp(0.0, 1.2);
+            self.clearness += 0.05 * (k - self.clearness);
+            self.climatological_clearness += 0.0005 * (k - self.climatological_clearness);
+        }
+
+        // Learning is slow, so saving once a day loses little on a restart
+        let day = time.date_naive();
+        if self.learned_day != Some(day) {
+            if self.learned_day.is_some() {
+                self.save();
+            }
+            self.learned_day = Some(day);
+        }
+    }
+
+    /// Regenerate the forecast if it is older than one step
+    ///
+    /// # Arguments
+    /// * `now` - Current UTC time
+    pub fn refresh(&mut self, now: DateTime<Utc>) {
+        let stale = self.forecast_time
+            .map(|t| now - t >= Duration::minutes(self.config.step_minutes))
+            .unwrap_or(true);
+        if !stale || self.location.is_none() {
+            return;
+        }
+
+        let steps = self.config.horizon_hours * 60 / self.config.step_minutes;
+        let start = now - Duration::minutes(now.minute() as i64 % self.config.step_minutes)
+            - Duration::seconds(now.second() as i64);
+        self.forecast = (1..=steps)
+            .filter_map(|i| {
+                let time = start + Duration::minutes(i * self.config.step_minutes);
+                let clear_sky = self.clear_sky_power(time)? as f32;
+                // Persistence of current clearness, decaying toward the climatological value
+                let horizon = (time - now).num_minutes() as f64 / 60.0;
+                let weight = (-horizon / self.config.clearness_decay_hours).exp() as f32;
+                let clearness = weight * self.clearness + (1.0 - weight) * self.climatological_clearness;
+                Some(PvForecastPoint {
+                    timestamp: time.to_rfc3339(),
+                    power: clear_sky * clearness,
+                    clear_sky_power: clear_sky,
+                })
+            })
+            .collect();
+        self.forecast_time = Some(now);
+    }
+
+    /// Latest forecast at configured resolution
+    pub fn forecast(&self) -> &[PvForecastPoint] {
+        &self.forecast
+    }
+
+    /// Forecast PV energy over the next hours
+    ///
+    /// # Arguments
+    /// * `hours` - Look-ahead window in hours
+    ///
+    /// # Returns
+    /// Energy in kWh
+    pub fn expected_energy(&self, hours: i64) -> f32 {
+        let steps = (hours * 60 / self.config.step_minutes) as usize;
+        let step_hours = self.config.step_minutes as f32 / 60.0;
+        self.forecast.iter().take(steps).map(|p| p.power * step_hours).sum()
+    }
+
+    /// Learned output at 1000 W/m² in kW
+    pub fn effective_capacity(&self) -> f32 {
+        self.effective_capacity
+    }
+}
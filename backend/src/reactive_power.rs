@@ -0,0 +1,186 @@
+// PCS 无功功率控制
+// Reactive power control for the PCS: fixed power factor, fixed reactive power and
+// Q(U) volt-var droop with a first-order response time
+
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::devices::pcs::PcsReactiveMode;
+use crate::types::ReactivePowerStatus;
+
+/// Reactive power control mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ReactivePowerMode {
+    /// Unity power factor
+    #[default]
+    Disabled,
+    /// Hold a fixed power factor
+    FixedPowerFactor,
+    /// Hold a fixed reactive power
+    FixedReactivePower,
+    /// Reactive power from the AC voltage along the volt-var curve
+    VoltVar,
+}
+
+/// Reactive power control configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReactivePowerConfig {
+    /// Control mode
+    pub mode: ReactivePowerMode,
+    /// Power factor setpoint 0-1 (positive: over-excited, negative: under-excited)
+    pub power_factor: f32,
+    /// Reactive power setpoint in kvar (positive: injecting)
+    pub reactive_power: f32,
+    /// Nominal AC voltage in V
+    pub nominal_voltage: f32,
+    /// Volt-var curve as (voltage % of nominal, reactive power % of rated) points, ascending by voltage
+    pub volt_var_curve: Vec<(f32, f32)>,
+    /// Reactive power the site can provide in kvar
+    pub rated_reactive_power: f32,
+    /// Volt-var open-loop response time constant in seconds
+    pub time_constant: f32,
+    /// Minimum setpoint change worth sending, in kvar
+    pub deadband: f32,
+}
+
+impl Default for ReactivePowerConfig {
+    fn default() -> Self {
+        Self {
+            mode: ReactivePowerMode::Disabled,
+            power_factor: 1.0,
+            reactive_power: 0.0,
+            nominal_voltage: 230.0,
+            volt_var_curve: vec![(92.0, 44.0), (98.0, 0.0), (102.0, 0.0), (108.0, -44.0)],
+            rated_reactive_power: 50.0,
+            time_constant: 5.0,
+            deadband: 0.5,
+        }
+    }
+}
+
+/// Command for the EMS to send to the PCS
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReactiveCommand {
+    /// PCS reactive control mode
+    pub mode: PcsReactiveMode,
+    /// Power factor for FixedPowerFactor, reactive power in kvar for FixedReactivePower
+    pub value: f32,
+}
+
+/// Reactive power controller
+#[derive(Debug, Clone)]
+pub struct ReactivePowerController {
+    config: ReactivePowerConfig,
+    last_update: Option<Instant>,
+    last_sent: Option<ReactiveCommand>,
+    /// Filtered volt-var setpoint in kvar
+    volt_var_setpoint: f32,
+    voltage: Option<f32>,
+}
+
+impl ReactivePowerController {
+    /// Create a new controller
+    ///
+    /// # Arguments
+    /// * `config` - Mode, setpoints and volt-var curve
+    pub fn new(config: ReactivePowerConfig) -> Self {
+        Self {
+            config,
+            last_update: None,
+            last_sent: None,
+            volt_var_setpoint: 0.0,
+            voltage: None,
+        }
+    }
+
+    /// Reactive power from the volt-var curve, held flat beyond the end points
+    ///
+    /// # Arguments
+    /// * `voltage` - AC voltage in V
+    ///
+    /// # Returns
+    /// Reactive power in kvar
+    fn volt_var_target(&self, voltage: f32) -> f32 {
+        let pct = voltage / self.config.nominal_voltage.max(f32::EPSILON) * 100.0;
+        let curve = &self.config.volt_var_curve;
+        let q_pct = match (curve.first(), curve.last()) {
+            (Some(first), _) if pct <= first.0 => first.1,
+            (_, Some(last)) if pct >= last.0 => last.1,
+            _ => curve.windows(2)
+                .find(|w| pct >= w[0].0 && pct <= w[1].0)
+                .map(|w| {
+                    let span = (w[1].0 - w[0].0).max(f32::EPSILON);
+                    w[0].1 + (w[1].1 - w[0].1) * (pct - w[0].0) / span
+                })
+                .unwrap_or(0.0),
+        };
+        q_pct / 100.0 * self.config.rated_reactive_power
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `voltage` - Measured AC voltage in V, if available
+    ///
+    /// # Returns
+    /// Command to send to the PCS, None if the last confirmed command still holds
+    pub fn update(&mut self, now: Instant, voltage: Option<f32>) -> Option<ReactiveCommand> {
+        let dt = self.last_update.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+        self.voltage = voltage.filter(|v| *v > 0.0);
+
+        let command = match self.config.mode {
+            ReactivePowerMode::Disabled => ReactiveCommand { mode: PcsReactiveMode::Disabled, value: 0.0 },
+            ReactivePowerMode::FixedPowerFactor => ReactiveCommand {
+                mode: PcsReactiveMode::FixedPowerFactor,
+                value: self.config.power_factor,
+            },
+            ReactivePowerMode::FixedReactivePower => ReactiveCommand {
+                mode: PcsReactiveMode::FixedReactivePower,
+                value: self.config.reactive_power,
+            },
+            ReactivePowerMode::VoltVar => {
+                // Hold the last setpoint while no voltage is available
+                if let Some(voltage) = self.voltage {
+                    let target = self.volt_var_target(voltage);
+                    let alpha = (dt / self.config.time_constant.max(f32::EPSILON)).min(1.0);
+                    self.volt_var_setpoint += (target - self.volt_var_setpoint) * alpha;
+                }
+                ReactiveCommand { mode: PcsReactiveMode::FixedReactivePower, value: self.volt_var_setpoint }
+            }
+        };
+
+        let changed = match self.last_sent {
+            Some(sent) if sent.mode == command.mode => match command.mode {
+                PcsReactiveMode::FixedReactivePower => (sent.value - command.value).abs() >= self.config.deadband,
+                _ => sent.value != command.value,
+            },
+            _ => true,
+        };
+        changed.then_some(command)
+    }
+
+    /// Record a command the PCS accepted; an unconfirmed command is sent again next cycle
+    ///
+    /// # Arguments
+    /// * `command` - Command written to the PCS
+    pub fn confirm(&mut self, command: ReactiveCommand) {
+        self.last_sent = Some(command);
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> ReactivePowerStatus {
+        let sent = self.last_sent;
+        ReactivePowerStatus {
+            mode: format!("{:?}", self.config.mode),
+            voltage: self.voltage,
+            reactive_power_setpoint: sent
+                .filter(|c| c.mode == PcsReactiveMode::FixedReactivePower)
+                .map(|c| c.value),
+            power_factor_setpoint: sent
+                .filter(|c| c.mode == PcsReactiveMode::FixedPowerFactor)
+                .map(|c| c.value),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// SOC 估算 (安时积分 + OCV 校正)
+// EMS-side state-of-charge estimator: coulomb counting of pack current with coulombic efficiency,
+// open-circuit-voltage correction at rest, bad sample rejection and BMS divergence alarm
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::{Duration, Instant};
+use crate::types::{BatteryStatus, SocEstimateStatus};
+
+/// Interval between saves of the estimate
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// SOC estimator configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SocEstimatorConfig {
+    /// Usable pack capacity in Ah
+    pub capacity_ah: f32,
+    /// Fraction of charge current stored in the pack (0-1)
+    pub coulombic_efficiency: f32,
+    /// Number of cells in series, used to convert pack voltage to cell voltage
+    pub series_cells: u32,
+    /// Cell open-circuit voltage table as (SOC %, volts) points, ascending by SOC
+    pub ocv_table: Vec<(f32, f32)>,
+    /// Current below which the pack counts as resting, in A
+    pub rest_current: f32,
+    /// Rest time before the OCV correction is applied, in seconds
+    pub rest_time: u64,
+    /// Fraction of the OCV error corrected per rest period (0-1)
+    pub ocv_gain: f32,
+    /// Minimum OCV slope for a trusted correction, in volts per % SOC per cell
+    pub min_ocv_slope: f32,
+    /// Samples above this current magnitude are rejected, in A
+    pub max_current: f32,
+    /// Sample gaps longer than this are not integrated, in seconds
+    pub max_sample_gap: f32,
+    /// Estimated vs BMS SOC difference that raises the divergence alarm (0-100%)
+    pub divergence_threshold: f32,
+    /// Dispatch on the estimated SOC instead of the BMS SOC
+    pub use_for_dispatch: bool,
+    /// File the estimate is persisted to, so a restart does not reseed it
+    pub state_path: Option<String>,
+}
+
+impl Default for SocEstimatorConfig {
+    fn default() -> Self {
+        // LFP cells
+        Self {
+            capacity_ah: 280.0,
+            coulombic_efficiency: 0.995,
+            series_cells: 120,
+            ocv_table: vec![
+                (0.0, 2.80), (5.0, 3.15), (10.0, 3.21), (20.0, 3.25), (30.0, 3.28), (40.0, 3.29),
+                (50.0, 3.30), (60.0, 3.31), (70.0, 3.32), (80.0, 3.33), (90.0, 3.35), (95.0, 3.38), (100.0, 3.50),
+            ],
+            rest_current: 2.0,
+            rest_time: 1800,
+            ocv_gain: 0.5,
+            min_ocv_slope: 0.002,
+            max_current: 500.0,
+            max_sample_gap: 10.0,
+            divergence_threshold: 10.0,
+            use_for_dispatch: false,
+            state_path: Some("data_cache/soc_estimate.json".to_string()),
+        }
+    }
+}
+
+/// Persisted estimator state
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct EstimateState {
+    soc: f32,
+}
+
+/// Coulomb-counting SOC estimator
+#[derive(Debug, Clone)]
+pub struct SocEstimator {
+    config: SocEstimatorConfig,
+    soc: Option<f32>,
+    last_save: Option<Instant>,
+    bms_soc: f32,
+    last_sample: Option<Instant>,
+    rest_since: Option<Instant>,
+    rest_corrected: bool,
+    ocv_soc: Option<f32>,
+    last_correction: Option<Instant>,
+    bad_samples: u32,
+    divergence_alarm: bool,
+}
+
+impl SocEstimator {
+    /// Create a new estimator, restoring the persisted estimate if available
+    ///
+    /// # Arguments
+    /// * `config` - Capacity, OCV table and correction parameters
+    pub fn new(config: SocEstimatorConfig) -> Self {
+        let soc = config.state_path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str::<EstimateState>(&s).ok())
+            .map(|s| s.soc)
+            .filter(|soc| (0.0..=100.0).contains(soc));
+        Self {
+            config,
+            soc,
+            last_save: None,
+            bms_soc: 0.0,
+            last_sample: None,
+            rest_since: None,
+            rest_corrected: false,
+            ocv_soc: None,
+            last_correction: None,
+            bad_samples: 0,
+            divergence_alarm: false,
+        }
+    }
+
+    /// SOC from the OCV table for a resting cell voltage
+    ///
+    /// # Arguments
+    /// * `cell_voltage` - Average cell voltage in volts
+    ///
+    /// # Returns
+    /// Tuple of (SOC %, local slope in V per %), or None outside the table
+    fn soc_from_ocv(&self, cell_voltage: f32) -> Option<(f32, f32)> {
+        self.config.ocv_table.windows(2)
+            .find(|w| cell_voltage >= w[0].1 && cell_voltage <= w[1].1)
+            .map(|w| {
+                let (s0, v0) = w[0];
+                let (s1, v1) = w[1];
+                let slope = (v1 - v0) / (s1 - s0).max(f32::EPSILON);
+                (s0 + (cell_voltage - v0) / slope.max(f32::EPSILON), slope)
+            })
+    }
+
+    /// Persist the estimate
+    fn save(&self, soc: f32) {
+        if let Some(ref path) = self.config.state_path {
+            match serde_json::to_string(&EstimateState { soc }) {
+                Ok(data) => {
+                    if let Err(e) = fs::write(path, data) {
+                        log::warn!("Failed to save SOC estimate to {}: {}", path, e);
+                    }
+                }
+                Err(e) => log::warn!("Failed to serialize SOC estimate: {}", e),
+            }
+        }
+    }
+
+    /// Initial estimate when none was persisted
+    ///
+    /// The drifting BMS SOC is used only if the pack is under load or on a flat part of the OCV curve.
+    fn seed(&self, status: &BatteryStatus) -> f32 {
+        let cell_voltage = status.voltage / self.config.series_cells.max(1) as f32;
+        match self.soc_from_ocv(cell_voltage) {
+            Some((ocv_soc, slope)) if status.current.abs() <= self.config.rest_current && slope >= self.config.min_ocv_slope => {
+                log::info!("SOC estimator seeded from OCV: {:.1}% (BMS {:.1}%)", ocv_soc, status.soc);
+                ocv_soc
+            }
+            _ => {
+                log::info!("SOC estimator seeded from BMS: {:.1}%", status.soc);
+                status.soc
+            }
+        }
+    }
+
+    fn is_valid(&self, status: &BatteryStatus) -> bool {
+        let cell_voltage = status.voltage / self.config.series_cells.max(1) as f32;
+        status.current.is_finite()
+            && status.voltage.is_finite()
+            && status.current.abs() <= self.config.max_current
+            && cell_voltage > 1.0 && cell_voltage < 5.0
+    }
+
+    /// Integrate one BMS sample
+    ///
+    /// # Arguments
+    /// * `now` - Sample time
+    /// * `status` - Latest BMS status (positive current discharges the pack)
+    pub fn update(&mut self, now: Instant, status: &BatteryStatus) {
+        if !self.is_valid(status) {
+            self.bad_samples += 1;
+            log::warn!("SOC estimator rejected sample: {:.1} V, {:.1} A", status.voltage, status.current);
+            return;
+        }
+        self.bms_soc = status.soc;
+        let soc = match self.soc {
+            Some(soc) => soc,
+            None => {
+                self.soc = Some(self.seed(status));
+                self.last_sample = Some(now);
+                return;
+            }
+        };
+
+        let dt = self.last_sample.map(|t| now.duration_since(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_sample = Some(now);
+        let mut soc = if dt <= self.config.max_sample_gap {
+            let charge_ah = -status.current * dt / 3600.0;
+            let stored_ah = if charge_ah > 0.0 { charge_ah * self.config.coulombic_efficiency } else { charge_ah };
+            soc + stored_ah / self.config.capacity_ah.max(1.0) * 100.0
+        } else {
+            log::warn!("SOC estimator skipped a {:.1} s sample gap", dt);
+            soc
+        };
+
+        // OCV correction once the pack has rested long enough
+        if status.current.abs() <= self.config.rest_current {
+            let since = *self.rest_since.get_or_insert(now);
+            let cell_voltage = status.voltage / self.config.series_cells.max(1) as f32;
+            self.ocv_soc = self.soc_from_ocv(cell_voltage).map(|(s, _)| s);
+            if !self.rest_corrected && now.duration_since(since).as_secs() >= self.config.rest_time {
+                self.rest_corrected = true;
+                match self.soc_from_ocv(cell_voltage) {
+                    Some((ocv_soc, slope)) if slope >= self.config.min_ocv_slope => {
+                        log::info!("SOC estimator OCV correction: {:.1}% -> OCV {:.1}%", soc, ocv_soc);
+                        soc += self.config.ocv_gain * (ocv_soc - soc);
+                        self.last_correction = Some(now);
+                    }
+                    _ => log::debug!("SOC estimator skipped OCV correction on flat curve region"),
+                }
+            }
+        } else {
+            self.rest_since = None;
+            self.rest_corrected = false;
+            self.ocv_soc = None;
+        }
+
+        let soc = soc.clamp(0.0, 100.0);
+        self.soc = Some(soc);
+        if self.last_save.map(|t| now.duration_since(t) >= SAVE_INTERVAL).unwrap_or(true) {
+            self.last_save = Some(now);
+            self.save(soc);
+        }
+
+        let divergence = (soc - self.bms_soc).abs();
+        let alarm = if self.divergence_alarm {
+            divergence > self.config.divergence_threshold * 0.8
+        } else {
+            divergence > self.config.divergence_threshold
+        };
+        if alarm != self.divergence_alarm {
+            log::warn!("SOC divergence alarm {}: estimated {:.1}%, BMS {:.1}%", if alarm { "raised" } else { "cleared" }, soc, self.bms_soc);
+            self.divergence_alarm = alarm;
+        }
+    }
+
+    /// SOC used for dispatch
+    ///
+    /// # Arguments
+    /// * `bms_soc` - SOC reported by the BMS
+    ///
+    /// # Returns
+    /// Estimated SOC if configured and available, otherwise the BMS SOC
+    pub fn dispatch_soc(&self, bms_soc: f32) -> f32 {
+        match self.soc {
+            Some(soc) if self.config.use_for_dispatch => soc,
+            _ => bms_soc,
+        }
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> SocEstimateStatus {
+        let estimated = self.soc.unwrap_or(self.bms_soc);
+        SocEstimateStatus {
+            bms_soc: self.bms_soc,
+            estimated_soc: estimated,
+            ocv_soc: self.ocv_soc,
+            divergence: estimated - self.bms_soc,
+            divergence_alarm: self.divergence_alarm,
+            resting: self.rest_since.is_some(),
+            seconds_since_ocv_correction: self.last_correction.map(|t| t.elapsed().as_secs()),
+            bad_samples: self.bad_samples,
+            dispatch_source: if self.config.use_for_dispatch && self.soc.is_some() { "Estimator" } else { "Bms" }.to_string(),
+        }
+    }
+}
@@ -0,0 +1,295 @@
+// 发电机同期检查
+// Synchronisation check before the genset is paralleled with the PCS on the AC bus:
+// voltage, frequency and (when measured) phase-angle windows held for a dwell time
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::types::SyncCheckStatus;
+
+/// Sync-check configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncCheckConfig {
+    /// Maximum voltage difference in % of the bus voltage
+    pub voltage_tolerance: f32,
+    /// Maximum frequency difference in Hz
+    pub frequency_tolerance: f32,
+    /// Maximum phase-angle difference in degrees (checked only when measured)
+    pub phase_tolerance: f32,
+    /// Time all criteria must hold before paralleling is allowed, in milliseconds
+    pub dwell_time_ms: u64,
+    /// Time allowed to reach synchronism before the attempt is logged as failed, in seconds
+    pub sync_timeout: u64,
+    /// Bus voltage below which the bus is dead and the genset may close without syncing, in V
+    pub dead_bus_voltage: f32,
+}
+
+impl Default for SyncCheckConfig {
+    fn default() -> Self {
+        Self {
+            voltage_tolerance: 5.0,
+            frequency_tolerance: 0.2,
+            phase_tolerance: 10.0,
+            dwell_time_ms: 500,
+            sync_timeout: 60,
+            dead_bus_voltage: 20.0,
+        }
+    }
+}
+
+/// Measurements compared by the sync check
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncInputs {
+    /// Whether the genset engine is running
+    pub genset_running: bool,
+    /// Genset output voltage in V
+    pub genset_voltage: f32,
+    /// Genset output frequency in Hz
+    pub genset_frequency: f32,
+    /// PCS AC bus voltage in V, None without a fresh reading
+    pub bus_voltage: Option<f32>,
+    /// PCS AC bus frequency in Hz, None without a fresh reading
+    pub bus_frequency: Option<f32>,
+    /// Phase angle between genset and bus in degrees, if measured
+    pub phase_angle: Option<f32>,
+}
+
+/// Sync-check state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncState {
+    Idle,         // Genset not running
+    Checking,     // Waiting for the criteria to hold
+    Paralleled,   // Paralleling allowed until the genset stops
+}
+
+/// Genset-PCS synchronisation check
+#[derive(Debug, Clone)]
+pub struct SyncChecker {
+    config: SyncCheckConfig,
+    state: SyncState,
+    attempt_since: Option<Instant>,
+    in_window_since: Option<Instant>,
+    voltage_difference: f32,
+    frequency_difference: f32,
+    phase_difference: Option<f32>,
+    /// Whether this cycle had a fresh bus reading
+    bus_measured: bool,
+    failed_syncs: u32,
+    last_failure: Option<String>,
+}
+
+impl SyncChecker {
+    /// Create a new sync check
+    ///
+    /// # Arguments
+    /// * `config` - Tolerances and dwell time
+    pub fn new(config: SyncCheckConfig) -> Self {
+        Self {
+            config,
+            state: SyncState::Idle,
+            attempt_since: None,
+            in_window_since: None,
+            voltage_difference: 0.0,
+            frequency_difference: 0.0,
+            phase_difference: None,
+            bus_measured: false,
+            failed_syncs: 0,
+            last_failure: None,
+        }
+    }
+
+    fn enter(&mut self, state: SyncState, now: Instant) {
+        log::info!("Genset sync check: {:?} -> {:?}", self.state, state);
+        self.state = state;
+        self.attempt_since = (state == SyncState::Checking).then_some(now);
+        self.in_window_since = None;
+    }
+
+    /// Whether the measured differences are inside every window
+    fn in_window(&self) -> bool {
+        self.bus_measured
+            && self.voltage_difference.abs() <= self.config.voltage_tolerance
+            && self.frequency_difference.abs() <= self.config.frequency_tolerance
+            && self.phase_difference.map(|p| p.abs() <= self.config.phase_tolerance).unwrap_or(true)
+    }
+
+    /// Evaluate one control cycle
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `inputs` - Genset and bus measurements
+    ///
+    /// # Returns
+    /// Whether the genset may be paralleled with the PCS
+    pub fn update(&mut self, now: Instant, inputs: SyncInputs) -> bool {
+        self.bus_measured = false;
+        if let (Some(bus_voltage), Some(bus_frequency)) = (inputs.bus_voltage, inputs.bus_frequency) {
+            self.bus_measured = true;
+            self.voltage_difference = (inputs.genset_voltage - bus_voltage) / bus_voltage.max(f32::EPSILON) * 100.0;
+            self.frequency_difference = inputs.genset_frequency - bus_frequency;
+        }
+        // Wrap into -180..180 degrees
+        self.phase_difference = inputs.phase_angle.map(|p| (p + 180.0).rem_euclid(360.0) - 180.0);
+
+        if !inputs.genset_running {
+            if self.state != SyncState::Idle {
+                self.enter(SyncState::Idle, now);
+            }
+            return false;
+        }
+
+        match self.state {
+            SyncState::Idle => {
+                self.enter(SyncState::Checking, now);
+                self.check(now, inputs)
+            }
+            SyncState::Checking => self.check(now, inputs),
+            SyncState::Paralleled => true,
+        }
+    }
+
+    /// Advance the dwell timer and log a failed attempt on timeout
+    fn check(&mut self, now: Instant, inputs: SyncInputs) -> bool {
+        // A dead bus may only be closed onto when it was actually measured dead
+        match inputs.bus_voltage {
+            Some(voltage) if self.bus_measured && voltage < self.config.dead_bus_voltage => {
+                log::info!("Genset sync check: bus dead ({:.1} V), closing without synchronisation", voltage);
+                self.enter(SyncState::Paralleled, now);
+                return true;
+            }
+            None => log::warn!("Genset sync check: no fresh PCS bus reading, holding the genset off the bus"),
+            _ => {}
+        }
+
+        if self.in_window() {
+            let since = *self.in_window_since.get_or_insert(now);
+            if now.duration_since(since) >= Duration::from_millis(self.config.dwell_time_ms) {
+                self.enter(SyncState::Paralleled, now);
+                return true;
+            }
+        } else {
+            self.in_window_since = None;
+        }
+
+        let timed_out = self.attempt_since
+            .map(|t| now.duration_since(t) >= Duration::from_secs(self.config.sync_timeout))
+            .unwrap_or(false);
+        if timed_out {
+            self.failed_syncs += 1;
+            let phase = self.phase_difference
+                .map(|p| format!("{:.1}°", p))
+                .unwrap_or_else(|| "not measured".to_string());
+            self.last_failure = Some(format!(
+                "Sync attempt {} failed after {} s (dV={:.1}%, df={:.2} Hz, dphi={})",
+                self.failed_syncs, self.config.sync_timeout, self.voltage_difference, self.frequency_difference, phase
+            ));
+            log::warn!("Genset: {}", self.last_failure.as_deref().unwrap_or_default());
+            // Keep trying with a fresh attempt window
+            self.enter(SyncState::Checking, now);
+        }
+        false
+    }
+
+    /// Tolerances and timings
+    pub fn config(&self) -> &SyncCheckConfig {
+        &self.config
+    }
+
+    /// Whether the genset may currently be paralleled
+    pub fn is_paralleled(&self) -> bool {
+        self.state == SyncState::Paralleled
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> SyncCheckStatus {
+        SyncCheckStatus {
+            state: format!("{:?}", self.state),
+            voltage_difference: self.voltage_difference,
+            frequency_difference: self.frequency_difference,
+            phase_difference: self.phase_difference,
+            in_window: self.state != SyncState::Idle && self.in_window(),
+            seconds_checking: self.attempt_since.map(|t| t.elapsed().as_secs()).unwrap_or(0),
+            failed_syncs: self.failed_syncs,
+            last_failure: self.last_failure.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(genset_voltage: f32, genset_frequency: f32) -> SyncInputs {
+        SyncInputs {
+            genset_running: true,
+            genset_voltage,
+            genset_frequency,
+            bus_voltage: Some(230.0),
+            bus_frequency: Some(50.0),
+            phase_angle: None,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn parallels_after_dwell_time_in_window() {
+        let mut checker = SyncChecker::new(SyncCheckConfig::default());
+        let t0 = Instant::now();
+        assert!(!checker.update(t0, inputs(231.0, 50.05)));
+        assert!(!checker.update(t0 + ms(300), inputs(231.0, 50.05)));
+        assert!(checker.update(t0 + ms(600), inputs(231.0, 50.05)));
+        assert!(checker.is_paralleled());
+    }
+
+    #[test]
+    fn leaving_the_window_restarts_the_dwell() {
+        let mut checker = SyncChecker::new(SyncCheckConfig::default());
+        let t0 = Instant::now();
+        assert!(!checker.update(t0, inputs(231.0, 50.0)));
+        assert!(!checker.update(t0 + ms(400), inputs(231.0, 50.5)));
+        assert!(!checker.update(t0 + ms(800), inputs(231.0, 50.0)));
+        assert!(!checker.update(t0 + ms(1000), inputs(231.0, 50.0)));
+        assert!(checker.update(t0 + ms(1400), inputs(231.0, 50.0)));
+    }
+
+    #[test]
+    fn dead_bus_closes_without_dwell() {
+        let mut checker = SyncChecker::new(SyncCheckConfig::default());
+        let dead_bus = SyncInputs { bus_voltage: Some(0.0), bus_frequency: Some(0.0), ..inputs(230.0, 50.0) };
+        assert!(checker.update(Instant::now(), dead_bus));
+    }
+
+    #[test]
+    fn missing_bus_reading_never_bypasses_the_check() {
+        let mut checker = SyncChecker::new(SyncCheckConfig::default());
+        let t0 = Instant::now();
+        let unknown = SyncInputs { bus_voltage: None, bus_frequency: None, ..inputs(230.0, 50.0) };
+        assert!(!checker.update(t0, unknown));
+        assert!(!checker.update(t0 + ms(1000), unknown));
+        assert!(!checker.status().in_window);
+
+        // A fresh reading restarts the dwell
+        assert!(!checker.update(t0 + ms(1500), inputs(230.0, 50.0)));
+        assert!(checker.update(t0 + ms(2000), inputs(230.0, 50.0)));
+    }
+
+    #[test]
+    fn timeout_logs_a_failed_attempt_and_stopping_resets() {
+        let config = SyncCheckConfig::default();
+        let timeout = Duration::from_secs(config.sync_timeout);
+        let mut checker = SyncChecker::new(config);
+        let t0 = Instant::now();
+        assert!(!checker.update(t0, inputs(250.0, 50.0)));
+        assert!(!checker.update(t0 + timeout, inputs(250.0, 50.0)));
+        let status = checker.status();
+        assert_eq!(status.failed_syncs, 1);
+        assert!(status.last_failure.is_some());
+
+        let stopped = SyncInputs { genset_running: false, ..inputs(0.0, 0.0) };
+        assert!(!checker.update(t0 + timeout + ms(100), stopped));
+        assert_eq!(checker.status().state, "Idle");
+    }
+}
@@ -0,0 +1,377 @@
+// 分时电价日历
+// Time-of-use tariff calendar (seasons, day types, hourly price bands, demand charges) and savings accounting
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use crate::types::TariffStatus;
+
+/// Price band of a tariff window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PriceBand {
+    OffPeak,     // Cheapest window, battery may charge from the grid
+    #[default]
+    MidPeak,     // Shoulder window, battery only absorbs PV surplus
+    Peak,        // Most expensive window, battery discharges
+}
+
+/// Day types a schedule applies to (holidays are treated as weekend days)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DayType {
+    #[default]
+    All,
+    Weekday,
+    Weekend,
+}
+
+impl DayType {
+    fn matches(&self, weekday: Weekday, holiday: bool) -> bool {
+        let weekend = holiday || matches!(weekday, Weekday::Sat | Weekday::Sun);
+        match self {
+            DayType::All => true,
+            DayType::Weekday => !weekend,
+            DayType::Weekend => weekend,
+        }
+    }
+}
+
+/// Hourly price window covering [start_hour, end_hour), wrapping past midnight if end <= start
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceWindow {
+    pub start_hour: u32,
+    pub end_hour: u32,
+    pub band: PriceBand,
+    /// Energy price per kWh
+    pub price: f32,
+}
+
+impl PriceWindow {
+    fn contains(&self, hour: u32) -> bool {
+        if self.start_hour < self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+/// Price windows for one day type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaySchedule {
+    #[serde(default)]
+    pub day_type: DayType,
+    pub windows: Vec<PriceWindow>,
+}
+
+/// Tariff season covering months start_month..=end_month (1-12, may wrap over new year)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Season {
+    pub name: String,
+    pub start_month: u32,
+    pub end_month: u32,
+    pub schedules: Vec<DaySchedule>,
+    /// Demand charge per kW of monthly peak grid import
+    #[serde(default)]
+    pub demand_charge: f32,
+}
+
+impl Season {
+    fn contains(&self, month: u32) -> bool {
+        if self.start_month <= self.end_month {
+            month >= self.start_month && month <= self.end_month
+        } else {
+            month >= self.start_month || month <= self.end_month
+        }
+    }
+}
+
+/// Complete time-of-use tariff calendar
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TariffCalendar {
+    pub seasons: Vec<Season>,
+    /// Price per kWh used when no window matches
+    pub default_price: f32,
+    /// Public holidays ("YYYY-MM-DD"), priced as weekend days
+    #[serde(default)]
+    pub holidays: Vec<String>,
+}
+
+/// Tariff applicable at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TariffSlot {
+    pub band: PriceBand,
+    /// Energy price per kWh
+    pub price: f32,
+    /// Demand charge per kW of monthly peak import
+    pub demand_charge: f32,
+}
+
+impl TariffCalendar {
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.iter()
+            .filter_map(|h| NaiveDate::parse_from_str(h, "%Y-%m-%d").ok())
+            .any(|h| h == date)
+    }
+
+    /// Look up the tariff for the given local time
+    ///
+    /// # Arguments
+    /// * `time` - Local wall-clock time
+    ///
+    /// # Returns
+    /// Price band, energy price and demand charge in effect
+    pub fn slot_at(&self, time: NaiveDateTime) -> TariffSlot {
+        let holiday = self.is_holiday(time.date());
+        let season = match self.seasons.iter().find(|s| s.contains(time.month())) {
+            Some(s) => s,
+            None => return TariffSlot { band: PriceBand::MidPeak, price: self.default_price, demand_charge: 0.0 },
+        };
+
+        let window = season.schedules.iter()
+            .filter(|s| s.day_type.matches(time.weekday(), holiday))
+            .flat_map(|s| s.windows.iter())
+            .find(|w| w.contains(time.hour()));
+
+        match window {
+            Some(w) => TariffSlot { band: w.band, price: w.price, demand_charge: season.demand_charge },
+            None => TariffSlot { band: PriceBand::MidPeak, price: self.default_price, demand_charge: season.demand_charge },
+        }
+    }
+
+    /// Lowest and highest hourly energy price on the given day
+    ///
+    /// # Returns
+    /// Tuple of (min_price, max_price) per kWh
+    pub fn price_range(&self, date: NaiveDate) -> (f32, f32) {
+        (0..24)
+            .filter_map(|h| date.and_hms_opt(h, 0, 0))
+            .map(|t| self.slot_at(t).price)
+            .fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p), hi.max(p)))
+    }
+}
+
+/// Time-of-use dispatch parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TouDispatchConfig {
+    /// Tariff calendar
+    pub calendar: TariffCalendar,
+    /// SOC the battery never discharges below in TOU mode (0-100%)
+    pub reserve_soc: f32,
+    /// SOC to reach when charging from the grid in off-peak windows (0-100%)
+    pub charge_target_soc: f32,
+    /// Grid charging power in off-peak windows in kW
+    pub grid_charge_power: f32,
+    /// Usable battery capacity in kWh, used for expected savings
+    pub battery_capacity: f32,
+    /// Battery round-trip efficiency (0-1)
+    pub round_trip_efficiency: f32,
+    /// Grid import above this level is shaved by the battery in any band, in kW
+    pub demand_limit: Option<f32>,
+    /// Fraction of the next 24 h PV forecast credited against off-peak grid charging (0-1)
+    pub pv_forecast_credit: f32,
+}
+
+impl Default for TouDispatchConfig {
+    fn default() -> Self {
+        Self {
+            calendar: TariffCalendar::default(),
+            reserve_soc: 30.0,
+            charge_target_soc: 95.0,
+            grid_charge_power: 30.0,
+            battery_capacity: 200.0,
+            round_trip_efficiency: 0.9,
+            demand_limit: None,
+            pv_forecast_credit: 0.0,
+        }
+    }
+}
+
+/// Tracks realised savings and monthly demand peaks for TOU dispatch
+#[derive(Debug, Clone, Default)]
+pub struct TariffTracker {
+    day: Option<NaiveDate>,
+    month: Option<(i32, u32)>,
+    slot: TariffSlot,
+    expected_savings: f32,
+    realised_today: f32,
+    realised_total: f32,
+    /// Highest 15-minute average grid import this month in kW
+    monthly_peak_import: f32,
+    /// Demand interval being averaged (date, quarter-hour of the day)
+    interval: Option<(NaiveDate, u32)>,
+    interval_energy: f32,
+    interval_hours: f32,
+}
+
+impl TariffTracker {
+    /// Account for one control cycle
+    ///
+    /// # Arguments
+    /// * `config` - TOU dispatch configuration
+    /// * `now` - Local wall-clock time
+    /// * `battery_power` - Battery power in kW (positive: discharging)
+    /// * `grid_import` - Grid import in kW (negative: exporting)
+    /// * `dt_hours` - Time since the previous cycle in hours
+    pub fn record(&mut self, config: &TouDispatchConfig, now: NaiveDateTime, battery_power: f32, grid_import: f32, dt_hours: f32) {
+        // Demand charges bill the highest 15-minute average, not an instantaneous reading
+        let interval = (now.date(), now.hour() * 4 + now.minute() / 15);
+        if self.interval != Some(interval) {
+            if self.interval_hours > 0.0 {
+                self.monthly_peak_import = self.monthly_peak_import.max(self.interval_energy / self.interval_hours);
+            }
+            self.interval = Some(interval);
+            self.interval_energy = 0.0;
+            self.interval_hours = 0.0;
+        }
+
+        let today = now.date();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.realised_today = 0.0;
+            self.expected_savings = Self::expected_daily_savings(config, today);
+        }
+        let month = (today.year(), today.month());
+        if self.month != Some(month) {
+            self.month = Some(month);
+            self.monthly_peak_import = 0.0;
+        }
+
+        self.slot = config.calendar.slot_at(now);
+
+        // Discharge only saves what would otherwise have been imported (the rest is exported), and
+        // charging only costs the share drawn from the grid (PV surplus is free)
+        let import = grid_import.max(0.0);
+        let avoided_import = battery_power.max(0.0).min((grid_import + battery_power).max(0.0));
+        let grid_charge = (-battery_power).max(0.0).min(import);
+        let saving = (avoided_import - grid_charge) * dt_hours * self.slot.price;
+        self.realised_today += saving;
+        self.realised_total += saving;
+
+        self.interval_energy += import * dt_hours;
+        self.interval_hours += dt_hours;
+    }
+
+    /// Savings from one full arbitrage cycle between the day's cheapest and dearest price
+    fn expected_daily_savings(config: &TouDispatchConfig, date: NaiveDate) -> f32 {
+        let (low, high) = config.calendar.price_range(date);
+        let usable = config.battery_capacity * (config.charge_target_soc - config.reserve_soc).max(0.0) / 100.0;
+        (usable * (high * config.round_trip_efficiency - low)).max(0.0)
+    }
+
+    /// Snapshot for EmsStatus
+    pub fn status(&self) -> TariffStatus {
+        TariffStatus {
+            band: format!("{:?}", self.slot.band),
+            energy_price: self.slot.price,
+            expected_savings: self.expected_savings,
+            realised_savings_today: self.realised_today,
+            realised_savings_total: self.realised_total,
+            monthly_peak_import: self.monthly_peak_import,
+            demand_charge: self.monthly_peak_import * self.slot.demand_charge,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(start_hour: u32, end_hour: u32, band: PriceBand, price: f32) -> PriceWindow {
+        PriceWindow { start_hour, end_hour, band, price }
+    }
+
+    /// Winter season over new year with a weekday peak and a night window wrapping midnight
+    fn calendar() -> TariffCalendar {
+        TariffCalendar {
+            seasons: vec![Season {
+                name: "winter".to_string(),
+                start_month: 11,
+                end_month: 2,
+                schedules: vec![
+                    DaySchedule {
+                        day_type: DayType::Weekday,
+                        windows: vec![
+                            window(22, 6, PriceBand::OffPeak, 0.10),
+                            window(17, 21, PriceBand::Peak, 0.40),
+                        ],
+                    },
+                    DaySchedule {
+                        day_type: DayType::Weekend,
+                        windows: vec![window(0, 24, PriceBand::OffPeak, 0.12)],
+                    },
+                ],
+                demand_charge: 15.0,
+            }],
+            default_price: 0.20,
+            holidays: vec!["2026-01-01".to_string()],
+        }
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).and_then(|d| d.and_hms_opt(h, 0, 0)).expect("valid time")
+    }
+
+    #[test]
+    fn weekday_windows_wrap_past_midnight() {
+        let calendar = calendar();
+        // Monday in a season that wraps over new year
+        assert_eq!(at(2026, 1, 5, 0).weekday(), Weekday::Mon);
+        assert_eq!(calendar.slot_at(at(2026, 1, 5, 23)).band, PriceBand::OffPeak);
+        assert_eq!(calendar.slot_at(at(2026, 1, 5, 5)).band, PriceBand::OffPeak);
+        assert_eq!(calendar.slot_at(at(2026, 1, 5, 6)).band, PriceBand::MidPeak);
+        let peak = calendar.slot_at(at(2025, 12, 1, 18));
+        assert_eq!(peak, TariffSlot { band: PriceBand::Peak, price: 0.40, demand_charge: 15.0 });
+    }
+
+    #[test]
+    fn unmatched_hour_uses_default_price_with_season_demand_charge() {
+        let slot = calendar().slot_at(at(2026, 1, 5, 12));
+        assert_eq!(slot, TariffSlot { band: PriceBand::MidPeak, price: 0.20, demand_charge: 15.0 });
+    }
+
+    #[test]
+    fn holidays_are_priced_as_weekend_days() {
+        let calendar = calendar();
+        // New year's day is a Thursday
+        assert_eq!(calendar.slot_at(at(2026, 1, 1, 18)).band, PriceBand::OffPeak);
+        assert_eq!(calendar.slot_at(at(2026, 1, 3, 18)).price, 0.12);
+        assert_eq!(calendar.slot_at(at(2026, 1, 8, 18)).band, PriceBand::Peak);
+    }
+
+    fn tou_config() -> TouDispatchConfig {
+        TouDispatchConfig { calendar: calendar(), ..Default::default() }
+    }
+
+    #[test]
+    fn only_grid_energy_is_valued() {
+        let config = tou_config();
+        let mut tracker = TariffTracker::default();
+        // Peak: 30 kW discharge of which 10 kW is exported, saving 20 kWh at 0.40
+        tracker.record(&config, at(2026, 1, 5, 18), 30.0, -10.0, 1.0);
+        assert!((tracker.status().realised_savings_today - 8.0).abs() < 1e-4);
+        // Off-peak: 30 kW charge with 20 kW from PV costs 10 kWh at 0.10
+        tracker.record(&config, at(2026, 1, 5, 23), -30.0, 10.0, 1.0);
+        assert!((tracker.status().realised_savings_today - 7.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn demand_peak_uses_quarter_hour_averages() {
+        let config = tou_config();
+        let mut tracker = TariffTracker::default();
+        let start = at(2026, 1, 5, 12);
+        // A one-minute 100 kW spike in a quarter hour otherwise at 20 kW
+        for minute in 0..15 {
+            let import = if minute == 3 { 100.0 } else { 20.0 };
+            tracker.record(&config, start + chrono::Duration::minutes(minute), 0.0, import, 1.0 / 60.0);
+        }
+        assert_eq!(tracker.status().monthly_peak_import, 0.0);
+        tracker.record(&config, start + chrono::Duration::minutes(15), 0.0, 20.0, 1.0 / 60.0);
+        assert!((tracker.status().monthly_peak_import - 25.333).abs() < 0.01);
+    }
+
+    #[test]
+    fn month_outside_every_season_uses_default_price() {
+        let slot = calendar().slot_at(at(2026, 6, 1, 18));
+        assert_eq!(slot, TariffSlot { band: PriceBand::MidPeak, price: 0.20, demand_charge: 0.0 });
+    }
+}
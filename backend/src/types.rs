@@ -11,7 +11,7 @@ pub struct EmsStatus {
     pub total_consumption: f32,
     /// Net power balance (generation - consumption) in kW
     pub power_balance: f32,
-    /// Grid power usage in kW (positive: importing, negative: exporting)
+    /// Grid power usage in kW from the grid meter (positive: importing, negative: exporting)
     pub grid_power: f32,
     /// Battery power flow in kW (positive: discharging, negative: charging)
     pub battery_power: f32,
@@ -29,6 +29,645 @@ pub struct EmsStatus {
     pub system_healthy: bool,
     /// Active system faults/warnings
     pub faults: Vec<String>,
+    /// Time-of-use tariff and savings
+    pub tariff: TariffStatus,
+    /// Production and demand forecast summary
+    pub forecast: ForecastStatus,
+    /// Optimisation-based dispatch planner
+    pub mpc: MpcStatus,
+    /// Genset start/stop controller
+    pub genset_control: GensetControlStatus,
+    pub fuel: FuelStatus,
+    pub maintenance: MaintenanceStatus,
+    pub soc_estimate: SocEstimateStatus,
+    pub battery_health: BatteryHealthStatus,
+    pub battery_limits: BatteryLimitStatus,
+    pub cell_imbalance: CellImbalanceStatus,
+    pub contactor: ContactorStatus,
+    pub battery_racks: BatteryRackStatus,
+    pub pcs_group: PcsGroupStatus,
+    pub genset_fleet: GensetFleetStatus,
+    pub genset_sync: SyncCheckStatus,
+    pub genset_min_load: GensetMinLoadStatus,
+    pub curtailment: CurtailmentStatus,
+    /// Latest grid meter reading (None if no meter or the read failed)
+    pub grid_meter: Option<MeterStatus>,
+    pub import_limit: ImportLimitStatus,
+    pub export_limit: ExportLimitStatus,
+    pub reactive_power: ReactivePowerStatus,
+    pub grid_support: GridSupportStatus,
+    pub islanding: IslandingStatus,
+    pub load_shedding: LoadSheddingStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GensetControlStatus {
+    /// Controller state ("Stopped", "Starting", "WarmingUp", "Running", "CoolingDown", "LockedOut")
+    pub state: String,
+    /// Seconds spent in the current state
+    pub seconds_in_state: u64,
+    /// Seconds since the current run started
+    pub run_seconds: u64,
+    /// Consecutive failed start attempts
+    pub failed_starts: u32,
+    /// Failed start attempts since startup
+    pub total_failed_starts: u32,
+    /// Whether starts are locked out after repeated failures
+    pub locked_out: bool,
+    /// Most recent start failure or unexpected stop
+    pub last_event: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GensetFleetStatus {
+    /// Current lead genset
+    pub lead: Option<String>,
+    /// Units requested to run (lead plus lags)
+    pub requested_units: usize,
+    /// Units with the engine running
+    pub running_units: usize,
+    /// Summed rating of the gensets able to carry load in kW
+    pub loadable_capacity: f32,
+    /// Most recent fallback or rotation
+    pub last_event: Option<String>,
+    /// Units in lead/lag order
+    pub units: Vec<GensetUnitStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GensetUnitStatus {
+    pub id: String,
+    /// "Lead" or "Lag"
+    pub role: String,
+    pub rated_power: f32,
+    /// Measured output in kW
+    pub power: f32,
+    /// Power setpoint in kW
+    pub setpoint: f32,
+    pub engine_hours: u32,
+    /// Start/stop controller of this unit
+    pub control: GensetControlStatus,
+    /// Sync check of this unit (None if not configured)
+    pub sync: Option<SyncCheckStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FuelStatus {
+    /// Filtered tank level (0-100%)
+    pub level: f32,
+    /// Fuel in the tank in litres
+    pub litres: f32,
+    /// Estimated consumption at the current load in litres per hour
+    pub consumption_rate: f32,
+    /// Estimated litres burned since startup
+    pub litres_burned: f32,
+    /// Projected runtime left at the current load in hours
+    pub runtime_left_hours: Option<f32>,
+    /// Low-fuel alarm ("None", "Warning", "Critical")
+    pub alarm: String,
+    /// Recent refuel and suspected theft events
+    pub events: Vec<FuelEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuelEvent {
+    /// Local time of detection (ISO 8601)
+    pub timestamp: String,
+    /// Event type ("Refuel", "Suspected theft", "Unexplained drop")
+    pub kind: String,
+    /// Litres added or lost
+    pub litres: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MaintenanceStatus {
+    /// Genset engine hours
+    pub engine_hours: u32,
+    /// Service tasks and their due state
+    pub items: Vec<MaintenanceItem>,
+    /// Upcoming and overdue service messages
+    pub alerts: Vec<String>,
+    /// Local time the genset was last seen running (ISO 8601)
+    pub last_run: Option<String>,
+    /// Whether an automatic exercise run is in progress
+    pub exercise_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceItem {
+    /// Service task name
+    pub task: String,
+    /// Due state ("Ok", "Upcoming", "Overdue")
+    pub state: String,
+    /// Local time of the last recorded service (ISO 8601)
+    pub last_service: Option<String>,
+    /// Engine hours at the last recorded service
+    pub last_service_hours: Option<u32>,
+    /// Engine hours until due (negative when overdue)
+    pub hours_remaining: Option<i64>,
+    /// Days until due (negative when overdue)
+    pub days_remaining: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SocEstimateStatus {
+    /// SOC reported by the BMS (0-100%)
+    pub bms_soc: f32,
+    /// Coulomb-counted SOC (0-100%)
+    pub estimated_soc: f32,
+    /// SOC from the OCV table while resting (0-100%)
+    pub ocv_soc: Option<f32>,
+    /// Estimated minus BMS SOC in percentage points
+    pub divergence: f32,
+    /// Whether the divergence exceeds the alarm threshold
+    pub divergence_alarm: bool,
+    /// Whether the pack is currently resting
+    pub resting: bool,
+    /// Seconds since the last OCV correction
+    pub seconds_since_ocv_correction: Option<u64>,
+    /// Rejected BMS samples since startup
+    pub bad_samples: u32,
+    /// SOC source used for dispatch ("Bms" or "Estimator")
+    pub dispatch_source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BatteryHealthStatus {
+    /// State of health (0-100%)
+    pub soh: f32,
+    /// SOH source ("Capacity", "Bms", "CycleModel")
+    pub soh_source: String,
+    /// SOH reported by the BMS (0-100%)
+    pub bms_soh: Option<f32>,
+    /// Usable capacity measured from full-charge events in kWh
+    pub capacity_estimate: Option<f32>,
+    /// Equivalent full cycles from energy throughput
+    pub equivalent_full_cycles: f32,
+    /// Cycle count reported by the BMS
+    pub bms_cycle_count: Option<u16>,
+    /// Working time reported by the BMS
+    pub working_time: Option<u32>,
+    /// Rainflow cycle counts per 10% depth-of-discharge bin
+    pub cycle_histogram: Vec<f32>,
+    /// Fatigue damage from rainflow cycles (100% = end of life)
+    pub cycle_damage: f32,
+    /// Estimated days until end of life
+    pub rul_days: Option<f32>,
+    /// Estimated equivalent full cycles until end of life
+    pub rul_cycles: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SohTrendPoint {
+    /// Local date ("YYYY-MM-DD")
+    pub date: String,
+    /// State of health (0-100%)
+    pub soh: f32,
+    /// Equivalent full cycles at that date
+    pub equivalent_full_cycles: f32,
+    /// Source of the SOH value ("Capacity", "Bms" or "CycleModel"; empty on points saved before tagging)
+    #[serde(default)]
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BatteryLimitStatus {
+    /// Live charge power limit in kW
+    pub max_charge_power: f32,
+    /// Live discharge power limit in kW
+    pub max_discharge_power: f32,
+    /// Reason the charge limit is below rating ("None", "Sop", "Temperature", "CellVoltage", "Ramp")
+    pub charge_limit_reason: String,
+    /// Reason the discharge limit is below rating
+    pub discharge_limit_reason: String,
+    /// Number of setpoints limited since startup
+    pub limited_setpoints: u64,
+    /// Recent limited setpoints
+    pub records: Vec<PowerLimitRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BatteryRackStatus {
+    /// Racks currently in dispatch
+    pub online_racks: usize,
+    /// Configured racks
+    pub total_racks: usize,
+    /// Capacity of the online racks in kWh
+    pub online_capacity_kwh: f32,
+    /// Summed SOP charge limit of the online racks in kW
+    pub max_charge_power: f32,
+    /// Summed SOP discharge limit of the online racks in kW
+    pub max_discharge_power: f32,
+    /// Highest minus lowest online rack SOC (0-100%)
+    pub soc_spread: f32,
+    pub racks: Vec<RackStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RackStatus {
+    pub id: String,
+    /// Whether the rack is in dispatch
+    pub online: bool,
+    /// Last SOC reported by the rack BMS
+    pub soc: Option<f32>,
+    /// Measured rack power in kW (positive: discharging)
+    pub power: f32,
+    /// PCS setpoint in kW (positive: discharging)
+    pub setpoint: f32,
+    pub max_charge_power: f32,
+    pub max_discharge_power: f32,
+    pub capacity_kwh: f32,
+    /// Consecutive failed BMS reads
+    pub failed_reads: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PcsGroupStatus {
+    /// Power sharing strategy ("ByRating", "Efficiency", "Sequential")
+    pub strategy: String,
+    /// Units not in fault
+    pub healthy_units: usize,
+    /// Summed rating of the healthy units in kW
+    pub available_power: f32,
+    /// Units in rotation order
+    pub units: Vec<PcsUnitStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PcsUnitStatus {
+    pub id: String,
+    pub rated_power: f32,
+    /// Setpoint in kW (positive: discharging)
+    pub setpoint: f32,
+    pub faulted: bool,
+    /// Hours with a non-zero setpoint since startup
+    pub run_hours: f32,
+    /// Expected efficiency at the current setpoint (%)
+    pub efficiency: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerLimitRecord {
+    /// Local time of the most recent occurrence (ISO 8601)
+    pub timestamp: String,
+    /// "Charge" or "Discharge"
+    pub direction: String,
+    /// Requested power in kW
+    pub requested: f32,
+    /// Applied power in kW
+    pub applied: f32,
+    /// Limiting factor ("Rating", "Sop", "Temperature", "CellVoltage", "Ramp")
+    pub reason: String,
+    /// Consecutive occurrences collapsed into this record
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CellImbalanceStatus {
+    /// Number of cells in the last reading
+    pub cell_count: usize,
+    /// Mean cell voltage in V
+    pub mean_voltage: f32,
+    /// Standard deviation of cell voltage in mV
+    pub voltage_std_dev_mv: f32,
+    /// Highest minus lowest cell voltage in mV
+    pub voltage_spread_mv: f32,
+    /// Highest minus lowest cell temperature in °C
+    pub temperature_spread: f32,
+    /// Number of cells currently balancing
+    pub balancing_cells: usize,
+    /// Cells far from the pack mean in the last reading
+    pub outlier_cells: Vec<u16>,
+    /// Cells persistently below the pack mean, weakest first
+    pub weak_cells: Vec<u16>,
+    /// Average deviation of the weakest cell in mV
+    pub weakest_deviation_mv: f32,
+    /// Whether the voltage spread exceeds the alarm threshold
+    pub spread_alarm: bool,
+    /// Cell values missing from the last reading
+    pub missing_values: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImbalanceTrendPoint {
+    /// Local date ("YYYY-MM-DD")
+    pub date: String,
+    /// Cell voltage spread in mV
+    pub voltage_spread_mv: f32,
+    /// Weakest cell index
+    pub weakest_cell: Option<u16>,
+    /// Average deviation of the weakest cell in mV
+    pub weakest_deviation_mv: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CellHeatmap {
+    /// Per-cell readings
+    pub details: BatteryCellDetails,
+    /// Long-term average deviation from the pack mean per cell in mV
+    pub deviation_mv: Vec<f32>,
+    /// Imbalance summary
+    pub summary: CellImbalanceStatus,
+    /// Daily imbalance trend
+    pub trend: Vec<ImbalanceTrendPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ContactorStatus {
+    /// Sequence state ("NotConfigured", "Open", "Precharging", "Closing", "Closed", "Unloading", "Opening", "Fault")
+    pub state: String,
+    /// Whether the battery has been requested online
+    pub online_requested: bool,
+    /// Contactor feedback (true = closed)
+    pub main_positive: bool,
+    pub main_negative: bool,
+    pub precharge: bool,
+    /// Pack voltage in V
+    pub pack_voltage: f32,
+    /// DC link voltage in V
+    pub link_voltage: f32,
+    /// Latched fault reason
+    pub fault: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CurtailmentStatus {
+    /// Whether PV is currently capped
+    pub active: bool,
+    /// Current PV cap in kW
+    pub cap: Option<f32>,
+    /// Estimated PV power currently lost to the cap in kW
+    pub lost_power: f32,
+    /// Curtailed energy today in kWh
+    pub curtailed_energy_today: f32,
+    /// Curtailed energy since startup in kWh
+    pub curtailed_energy_total: f32,
+    /// Number of times curtailment engaged since startup
+    pub events: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ImportLimitStatus {
+    /// Contracted maximum grid import in kW (0 if not configured)
+    pub import_limit: f32,
+    /// Battery discharge added to hold the limit in kW
+    pub battery_boost: f32,
+    /// Charger power withheld to hold the limit in kW
+    pub charger_curtailment: f32,
+    /// Whether chargers are held at the fail-safe cap because the meter is stale
+    pub failsafe: bool,
+    /// Whether metered import is currently above the limit
+    pub over_limit: bool,
+    /// Number of limit violations since startup
+    pub violations: u32,
+    /// Time spent above the limit since startup in seconds
+    pub seconds_over_limit: f32,
+    /// Highest metered import since startup in kW
+    pub peak_import: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ExportLimitStatus {
+    /// Maximum grid export in kW
+    pub export_limit: f32,
+    /// Battery charge added to hold the limit in kW
+    pub battery_absorb: f32,
+    /// PV power withheld to hold the limit in kW
+    pub pv_curtailment: f32,
+    /// Current PV cap in kW (None: PV uncapped by the export limit)
+    pub pv_cap: Option<f32>,
+    /// Whether PV is held at local consumption because the meter is stale
+    pub failsafe: bool,
+    /// Whether metered export is currently above the limit
+    pub over_limit: bool,
+    /// Number of limit violations since startup
+    pub violations: u32,
+    /// Time spent above the limit since startup in seconds
+    pub seconds_over_limit: f32,
+    /// Highest metered export since startup in kW
+    pub peak_export: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ReactivePowerStatus {
+    /// Control mode ("Disabled", "FixedPowerFactor", "FixedReactivePower", "VoltVar")
+    pub mode: String,
+    /// AC voltage used for volt-var control in V
+    pub voltage: Option<f32>,
+    /// Reactive power setpoint sent to the PCS in kvar
+    pub reactive_power_setpoint: Option<f32>,
+    /// Power factor setpoint sent to the PCS
+    pub power_factor_setpoint: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GridSupportStatus {
+    /// Grid frequency used by the responses in Hz
+    pub frequency: Option<f32>,
+    /// AC voltage used by the responses in V
+    pub voltage: Option<f32>,
+    /// Frequency-watt power offset in kW (positive: more output)
+    pub power_offset: f32,
+    /// Volt-watt output cap in kW
+    pub power_cap: Option<f32>,
+    /// Responses and excursions in progress
+    pub active_events: Vec<String>,
+    /// Whether an excursion has outlasted its ride-through band
+    pub ride_through_exceeded: bool,
+    /// Completed events, oldest first
+    pub events: Vec<GridSupportEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GridSupportEvent {
+    /// Start time (ISO 8601)
+    pub timestamp: String,
+    /// Event kind ("OverFrequency", "UnderFrequency", "VoltWatt", "VoltageExcursion", "FrequencyExcursion")
+    pub kind: String,
+    /// Duration in seconds
+    pub duration: f32,
+    /// Largest deviation from nominal (Hz, or % of nominal voltage)
+    pub extreme_deviation: f32,
+    /// Largest response in kW
+    pub max_response: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GensetMinLoadStatus {
+    /// Minimum genset load in kW (0 if not configured)
+    pub min_load: f32,
+    /// Genset load in % of rating
+    pub load_pct: f32,
+    /// Battery charge added to load the genset in kW
+    pub battery_absorb: f32,
+    /// Whether the genset is currently below its minimum load
+    pub below_min_load: bool,
+    /// Time spent below the minimum load since startup in seconds
+    pub seconds_below_min_load: f32,
+    /// Number of low-load periods since startup
+    pub low_load_events: u32,
+    /// Whether the genset is being stopped because it cannot be kept loaded
+    pub stop_requested: bool,
+    /// Most recent low-load stop
+    pub last_event: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SyncCheckStatus {
+    /// Sync-check state ("NotConfigured", "Idle", "Checking", "Paralleled")
+    pub state: String,
+    /// Genset minus bus voltage in % of the bus voltage
+    pub voltage_difference: f32,
+    /// Genset minus bus frequency in Hz
+    pub frequency_difference: f32,
+    /// Genset minus bus phase angle in degrees (None if not measured)
+    pub phase_difference: Option<f32>,
+    /// Whether all criteria currently hold
+    pub in_window: bool,
+    /// Seconds since the current sync attempt started
+    pub seconds_checking: u64,
+    /// Sync attempts that timed out since startup
+    pub failed_syncs: u32,
+    /// Most recent failed attempt with the measured deviations
+    pub last_failure: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LoadSheddingStatus {
+    /// Priority tiers, highest first
+    pub tiers: Vec<LoadTierStatus>,
+    /// Ids of the loads currently shed
+    pub shed_loads: Vec<String>,
+    /// Most recent shed or restore
+    pub last_event: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LoadTierStatus {
+    /// Priority class ("Critical", "Fleet", "Public")
+    pub priority: String,
+    /// Whether the tier is shed
+    pub shed: bool,
+    /// Seconds since the tier was shed
+    pub seconds_shed: u64,
+    /// Load the tier was drawing when last shed in kW
+    pub shed_load: f32,
+    /// Times the tier has been shed since startup
+    pub shed_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct IslandingStatus {
+    /// Islanding state ("OnGrid", "Disconnecting", "Islanded", "BlackStart", "Resynchronising", "Reconnecting", "Fault"; "NotConfigured" when absent)
+    pub state: String,
+    /// Seconds spent in the current state
+    pub seconds_in_state: u64,
+    /// Whether the grid is within limits (None without a meter reading)
+    pub grid_healthy: Option<bool>,
+    /// Whether the grid breaker reports closed (None without valid feedback)
+    pub breaker_closed: Option<bool>,
+    /// Charger shed level ("None", "NonCritical", "All")
+    pub shed_level: String,
+    /// Black start attempts in the current sequence
+    pub black_start_attempts: u32,
+    /// State transitions since startup
+    pub transitions: u32,
+    /// Most recent state transition
+    pub last_event: Option<String>,
+    /// Latched fault description
+    pub fault: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MpcStatus {
+    /// Number of steps in the current plan
+    pub plan_steps: usize,
+    /// Objective value (cost) of the current plan
+    pub objective: f32,
+    /// Seconds since the last solve
+    pub seconds_since_solve: u64,
+    /// Planned battery power for the current step in kW (positive: discharging)
+    pub battery_setpoint: f32,
+    /// Planned genset power for the current step in kW
+    pub genset_setpoint: f32,
+    /// Planned total charger power limit for the current step in kW
+    pub charger_limit: f32,
+    /// Number of failed solves since startup
+    pub solve_failures: u32,
+    /// Error from the last failed solve
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DispatchPlanStep {
+    /// Start of the plan step (local time)
+    pub timestamp: String,
+    /// Battery power in kW (positive: discharging, negative: charging)
+    pub battery_power: f32,
+    /// Genset power in kW
+    pub genset_power: f32,
+    /// Whether the genset is committed in this step
+    pub genset_on: bool,
+    /// Grid power in kW (positive: importing, negative: exporting)
+    pub grid_power: f32,
+    /// Total charger power limit in kW
+    pub charger_limit: f32,
+    /// Curtailed PV power in kW
+    pub pv_curtailment: f32,
+    /// Planned SOC at the end of the step (0-100%)
+    pub soc: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TariffStatus {
+    /// Active price band ("OffPeak", "MidPeak", "Peak")
+    pub band: String,
+    /// Current energy price per kWh
+    pub energy_price: f32,
+    /// Expected savings for today from one full arbitrage cycle
+    pub expected_savings: f32,
+    /// Realised savings since midnight
+    pub realised_savings_today: f32,
+    /// Realised savings since startup
+    pub realised_savings_total: f32,
+    /// Highest grid import this month in kW
+    pub monthly_peak_import: f32,
+    /// Demand charge accrued this month
+    pub demand_charge: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ForecastStatus {
+    /// Forecast PV energy over the next 24 hours in kWh
+    pub pv_energy_24h: f32,
+    /// Learned PV array output at 1000 W/m² in kW
+    pub pv_effective_capacity: f32,
+    /// Forecast mean charger energy over the next 24 hours in kWh
+    pub load_energy_24h: f32,
+    /// Highest P90 charger demand over the next 24 hours in kW
+    pub load_peak_p90_24h: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PvForecastPoint {
+    /// Start of the forecast interval (RFC 3339, UTC)
+    pub timestamp: String,
+    /// Forecast PV power in kW
+    pub power: f32,
+    /// Clear-sky PV power in kW
+    pub clear_sky_power: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LoadForecastPoint {
+    /// Start of the forecast interval (local time)
+    pub timestamp: String,
+    /// Expected charger demand in kW
+    pub mean: f32,
+    /// 10th percentile charger demand in kW
+    pub p10: f32,
+    /// 90th percentile charger demand in kW
+    pub p90: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -69,6 +708,74 @@ pub struct BatteryCellStatus {
     pub health_percentage: f32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BatteryCellDetails {
+    /// Per-cell readings in pack order
+    pub cells: Vec<CellData>,
+    /// Number of modules in the pack
+    pub module_count: u16,
+    /// Number of cells per module
+    pub cells_per_module: u16,
+    /// Cell values missing from the last read
+    pub missing_values: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CellData {
+    /// Cell index in the pack (0-based)
+    pub index: u16,
+    /// Module the cell belongs to (0-based)
+    pub module: u16,
+    /// Cell voltage in V
+    pub voltage: Option<f32>,
+    /// Cell temperature in °C
+    pub temperature: Option<f32>,
+    /// Whether the cell is being balanced
+    pub balancing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ContactorCommand {
+    pub main_positive: bool,
+    pub main_negative: bool,
+    pub precharge: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ContactorFeedback {
+    /// Auxiliary contact feedback (true = closed)
+    pub main_positive: bool,
+    pub main_negative: bool,
+    pub precharge: bool,
+    /// Pack voltage in V
+    pub pack_voltage: f32,
+    /// DC link (bus side) voltage in V
+    pub link_voltage: f32,
+    /// Whether the BMS permits closing
+    pub permit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MeterStatus {
+    /// Per-phase measurements (L1, L2, L3)
+    pub phases: Vec<PhaseMeasurement>,
+    pub active_power: f32,   // Total active power in kW (positive: importing)
+    pub reactive_power: f32, // Total reactive power in kvar
+    pub power_factor: f32,
+    pub frequency: f32,      // Hz
+    pub import_energy: f64,  // Imported energy counter in kWh
+    pub export_energy: f64,  // Exported energy counter in kWh
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PhaseMeasurement {
+    pub voltage: f32,        // Line-to-neutral voltage in V
+    pub current: f32,        // A
+    pub active_power: f32,   // kW (positive: importing)
+    pub reactive_power: f32, // kvar
+    pub power_factor: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GensetStatus {
     pub running: bool,
@@ -105,6 +812,24 @@ pub struct ChargerStatus {
 pub struct PcsStatus {
     pub mode: String,    // Operating mode (e.g., "Charging", "Discharging", "Standby")
     pub power: f32,      // Active power in kW
+    /// Reactive power in kvar (positive: injecting)
+    pub reactive_power: f32,
+    /// AC voltage in V
+    pub voltage_ac: f32,
+    /// AC current in A
+    pub current_ac: f32,
+    /// DC voltage in V
+    pub voltage_dc: f32,
+    /// DC current in A
+    pub current_dc: f32,
+    /// AC frequency in Hz
+    pub frequency: f32,
+    /// Efficiency percentage (0-100%)
+    pub efficiency: f32,
+    /// Fault status
+    pub fault: bool,
+    /// Active fault codes
+    pub fault_codes: Vec<u16>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]